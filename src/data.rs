@@ -6,7 +6,11 @@ use std::{
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
-use crate::Res;
+use crate::{
+    dos_path::resolve_case,
+    pcb_date::{DateFormat, PcbDate},
+    ExecutionLimits, Res,
+};
 
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct PcbDataType {
@@ -118,7 +122,7 @@ pub struct PcbPaths {
 impl PcbDataType {
     pub fn load(filename: &str, c_drive: &str) -> Res<Self> {
         let mut lines = Vec::new();
-        let reader = BufReader::new(File::open(filename)?);
+        let reader = BufReader::new(File::open(resolve_case(Path::new(filename)))?);
 
         for (i, line) in reader.lines().enumerate() {
             if let Ok(line) = line {
@@ -211,7 +215,9 @@ impl PcbDataType {
     }
 
     pub fn load_users(&self) -> Res<Vec<UserRecord>> {
-        UserRecord::read_users(Path::new(&self.path.usr_file))
+        let mut users = UserRecord::read_users(Path::new(&self.path.usr_file))?;
+        UserRecord::read_users_inf(Path::new(&self.path.inf_file), &mut users)?;
+        Ok(users)
     }
 }
 
@@ -243,7 +249,9 @@ pub struct UserRecord {
     pub bus_data_phone: String,
     pub home_voice_phone: String,
 
+    /// Julian date (see `PcbDate`) of the last call
     pub last_date_on: u16,
+    /// "hh:mm" of the last call
     pub last_time_on: String,
 
     pub expert_mode: bool,
@@ -252,7 +260,8 @@ pub struct UserRecord {
     pub protocol: char,
 
     // packedbyte     PackedFlags;        /* Bit packed flags */
-    // datetype       DateLastDirRead;    /* Date for Last DIR Scan (most recent file) */
+    /// Julian date for last DIR scan (most recent file)
+    pub date_last_dir_read: u16,
     pub security_level: i32,
 
     /// Number of times the caller has connected
@@ -274,13 +283,17 @@ pub struct UserRecord {
 
     pub scroll_flag: bool,
 
-    // unsigned short RegExpDate;         /* Julian date for Registration Expiration Date */
+    /// Julian date for registration expiration date
+    pub reg_exp_date: u16,
+    /// Julian date the password expires, it's stored in the password section of USERS.INF
+    pub password_expire_date: u16,
     // short          ExpSecurityLevel;   /* Expired Security Level */
     // unsigned short LastConference;     /* Number of the conference the caller was in */
     total_dl_bytes: usize,
     total_ul_bytes: usize,
     // bool           DeleteFlag;         /* 1=delete this record, 0=keep */
-    // long           RecNum;             /* Record Number in USERS.INF file */
+    /// Record Number in USERS.INF file
+    rec_num: u32,
     // packedbyte2    Flags;
     // char           Reserved[8];        /* Bytes 390-397 from the USERS file */
     // unsigned long  MsgsRead;           /* Number of messages the user has read in PCB */
@@ -309,7 +322,7 @@ impl UserRecord {
     pub fn read_users(path: &Path) -> Res<Vec<UserRecord>> {
         let mut users = Vec::new();
 
-        let data = fs::read(path)?;
+        let data = fs::read(resolve_case(path))?;
 
         let mut cursor = Cursor::new(data);
         const RECORD_SIZE: u64 = 0x190;
@@ -369,8 +382,12 @@ impl UserRecord {
             let mut reg_exp_date = [0u8; 6];
             cursor.read_exact(&mut reg_exp_date)?;
 
-            // unknown data
-            for _ in 0..0xCF {
+            // unknown data up to the USERS.INF record number at offset 385
+            for _ in 0..0xC0 {
+                cursor.read_u8()?;
+            }
+            let rec_num = cursor.read_u32::<LittleEndian>()?;
+            for _ in 0..0x0B {
                 cursor.read_u8()?;
             }
 
@@ -391,6 +408,11 @@ impl UserRecord {
                 user_comment: String::from_utf8_lossy(&cmt1).trim().to_string(),
                 sysop_comment: String::from_utf8_lossy(&cmt2).trim().to_string(),
                 elapsed_time_on,
+                last_date_on: decode_date(&last_date_on),
+                last_time_on: String::from_utf8_lossy(&last_time_on).trim().to_string(),
+                date_last_dir_read: decode_date(&date_last_dir_read),
+                reg_exp_date: decode_date(&reg_exp_date),
+                rec_num,
 
                 ..Default::default()
             };
@@ -400,6 +422,55 @@ impl UserRecord {
 
         Ok(users)
     }
+
+    /// Reads the password expiration dates from the password section of USERS.INF.
+    pub fn read_users_inf(path: &Path, users: &mut [UserRecord]) -> Res<()> {
+        let data = fs::read(resolve_case(path))?;
+        let mut cursor = Cursor::new(&data);
+
+        // header: version, number of conferences, size of the base record, size of a user record
+        // & number of applications
+        cursor.read_u16::<LittleEndian>()?;
+        cursor.read_u16::<LittleEndian>()?;
+        cursor.read_u16::<LittleEndian>()?;
+        let record_size = cursor.read_u32::<LittleEndian>()? as u64;
+        let num_apps = cursor.read_u16::<LittleEndian>()?;
+
+        let mut password_offset = None;
+        for _ in 0..num_apps {
+            let mut name = [0u8; 15];
+            cursor.read_exact(&mut name)?;
+            // version, record sizes & keyword
+            let mut app_data = [0u8; 15];
+            cursor.read_exact(&mut app_data)?;
+            let offset = cursor.read_u32::<LittleEndian>()? as u64;
+            if name.split(|b| *b == 0).next() == Some(b"PCBPASSWORD") {
+                password_offset = Some(offset);
+            }
+        }
+        let Some(password_offset) = password_offset else {
+            return Ok(());
+        };
+
+        // the password section has the 3 previous passwords, the date of the last change and
+        // the number of changes before the expiration date
+        let first_record = cursor.position();
+        for user in users.iter_mut().filter(|user| user.rec_num > 0) {
+            let pos = first_record + (user.rec_num as u64 - 1) * record_size + password_offset;
+            cursor.set_position(pos + 3 * 13 + 2 + 2);
+            if let Ok(date) = cursor.read_u16::<LittleEndian>() {
+                user.password_expire_date = date;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts a "yymmdd" date of the USERS file to a julian date, invalid dates are 0.
+fn decode_date(yymmdd: &[u8]) -> u16 {
+    PcbDate::from_pcboard_str(&String::from_utf8_lossy(yymmdd))
+        .and_then(|date| u16::try_from(date.to_julian()).ok())
+        .unwrap_or(0)
 }
 
 #[derive(Clone, Debug, Default)]
@@ -425,13 +496,17 @@ pub struct IcyBoardData {
 
     pub yes_char: char,
     pub no_char: char,
+    pub date_format: DateFormat,
+    pub ppe_limits: ExecutionLimits,
+    /// PPEs wait for a debugger client on this local port, `None` runs them normally
+    pub ppe_debug_port: Option<u16>,
 }
 
 impl IcyBoardData {
     pub fn load_data(&mut self) -> Res<()> {
         let pcb_text = Path::new(&self.pcb_data.path.text_loc).join("PCBTEXT");
 
-        let pcb_text = fs::read(pcb_text)?;
+        let pcb_text = fs::read(resolve_case(&pcb_text))?;
 
         let mut res = Vec::new();
         for chunk in pcb_text.chunks(0x50) {
@@ -0,0 +1,77 @@
+//! Case insensitive file name resolution, DOS names like `C:\PCB\GEN\PCBTEXT` need to find
+//! `pcb/gen/PcbText` on case sensitive file systems.
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+/// (directory, lower case name) -> name on disk
+type NameCache = HashMap<(PathBuf, String), OsString>;
+
+fn cache() -> &'static Mutex<NameCache> {
+    static CACHE: OnceLock<Mutex<NameCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Returns the path on disk matching `path` case insensitively.
+/// An exact match wins, otherwise the first matching name in byte order is taken.
+/// Components that don't exist are kept as they are, so new files get the name the PPE used.
+pub fn resolve_case(path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_path_buf();
+    }
+    let mut result = PathBuf::new();
+    let mut components = path.components();
+    for component in components.by_ref() {
+        let Component::Normal(name) = component else {
+            result.push(component);
+            continue;
+        };
+        let exact = result.join(name);
+        if exact.symlink_metadata().is_ok() {
+            result = exact;
+            continue;
+        }
+        match find_name(&result, &name.to_string_lossy()) {
+            Some(found) => result.push(found),
+            None => {
+                result.push(name);
+                break;
+            }
+        }
+    }
+    for component in components {
+        result.push(component);
+    }
+    result
+}
+
+fn find_name(dir: &Path, name: &str) -> Option<OsString> {
+    let key = (dir.to_path_buf(), name.to_lowercase());
+    let mut cache = cache().lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(found) = cache.get(&key) {
+        // the file may have been deleted or renamed since
+        if dir.join(found).symlink_metadata().is_ok() {
+            return Some(found.clone());
+        }
+        cache.remove(&key);
+    }
+    let dir_name = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let mut candidates: Vec<OsString> = fs::read_dir(dir_name)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name())
+        .filter(|file_name| file_name.to_string_lossy().eq_ignore_ascii_case(name))
+        .collect();
+    candidates.sort();
+    let found = candidates.into_iter().next()?;
+    cache.insert(key, found.clone());
+    Some(found)
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::VecDeque,
+    env,
     fs::File,
     io::Read,
     net::{TcpListener, TcpStream},
@@ -27,7 +28,11 @@ mod pcb_parser;
 pub use pcb_parser::*;
 
 use crate::data::{IcyBoardData, Node, PcbDataType};
+use crate::debugger::Debugger;
+use crate::pcb_date::DateFormat;
 pub mod data;
+pub mod dos_path;
+pub mod pcb_date;
 pub mod pcb_text;
 
 pub struct Connection {
@@ -206,8 +211,9 @@ impl ExecutionContext for Connection {
             Ok(None)
         }
     }
-    fn send_to_com(&mut self, data: &str) -> Res<()> {
-        self.com.push_str(data);
+    fn flush_input(&mut self) -> Res<()> {
+        self.com.fill_buffer()?;
+        self.com.buf.clear();
         Ok(())
     }
 }
@@ -247,6 +253,19 @@ fn main() -> Res<()> {
     // once you are done.
     let _handle = log4rs::init_config(config);
 
+    // --ppe-debug <port> runs the PPEs under the debugger
+    let mut ppe_debug_port = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ppe-debug" => {
+                let port = args.next().ok_or("--ppe-debug needs a port")?;
+                ppe_debug_port = Some(port.parse::<u16>()?);
+            }
+            _ => return Err(format!("unknown argument {}", arg).into()),
+        }
+    }
+
     let listener = TcpListener::bind("127.0.0.1:4321")?;
     println!("listen...");
 
@@ -305,14 +324,27 @@ fn main() -> Res<()> {
                 pcb_text: Vec::new(),
                 yes_char: 'Y',
                 no_char: 'N',
+                date_format: DateFormat::default(),
+                ppe_limits: ExecutionLimits::default(),
+                ppe_debug_port,
             };
             pcb_data.load_data();
 
             let prg =
                 ppl_engine::decompiler::load_file("/home/mkrueger/work/pcx_board/lbmenu/MENU.PPE");
 
-            let mut io = DiskIO::new("/home/mkrueger/work/pcx_board");
-            match run(&prg, &mut connection, &mut io, &pcb_data) {
+            // C: is the board like in PCBOARD.DAT, the PPEs & their files are on D:
+            let mut io = DiskIO::new("/home/mkrueger/work/PCBoard/C");
+            io.map_drive('D', "/home/mkrueger/work/pcx_board");
+            let res = match pcb_data.ppe_debug_port {
+                Some(port) => {
+                    Debugger::listen(&format!("127.0.0.1:{}", port)).and_then(|debugger| {
+                        run_with_debugger(&prg, &mut connection, &mut io, &pcb_data, debugger)
+                    })
+                }
+                None => run(&prg, &mut connection, &mut io, &pcb_data),
+            };
+            match res {
                 Ok(_) => {
                     while connection.com.is_data_available().unwrap() {
                         let ch = connection.com.read_char_nonblocking();
@@ -325,6 +357,7 @@ fn main() -> Res<()> {
                     }
                 }
                 Err(e) => {
+                    // the error is already logged by the interpreter, the user returns to the board
                     eprintln!("{}", e);
                 }
             }
@@ -0,0 +1,225 @@
+use chrono::{Datelike, Local, Timelike};
+
+/// Date display/input format used by the board.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DateFormat {
+    /// mm-dd-yy (PCBoard default)
+    #[default]
+    MmDdYy,
+    /// dd-mm-yy
+    DdMmYy,
+    /// yy-mm-dd
+    YyMmDd,
+}
+
+/// A calendar date as PCBoard uses it.
+/// PPL `DATE` values are stored as julian day numbers counting from 01-01-1900 (= 0).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PcbDate {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl PcbDate {
+    pub fn new(year: i32, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+
+    pub fn today() -> Self {
+        let now = Local::now();
+        Self::new(now.year(), now.month() as u8, now.day() as u8)
+    }
+
+    /// Decodes the "yymmdd" dates of the PCBoard USERS file, an empty or invalid date is `None`.
+    pub fn from_pcboard_str(yymmdd: &str) -> Option<Self> {
+        let yymmdd = yymmdd.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        if yymmdd.len() != 6 || !yymmdd.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let year = yymmdd[0..2].parse::<i32>().ok()?;
+        let year = if year < 80 { 2000 + year } else { 1900 + year };
+        let date = Self::new(year, yymmdd[2..4].parse().ok()?, yymmdd[4..6].parse().ok()?);
+        if date.is_valid() {
+            Some(date)
+        } else {
+            None
+        }
+    }
+
+    /// DDATE representation: the date as yyyymmdd number (as in dBase files).
+    pub fn from_ddate(yyyymmdd: i32) -> Self {
+        Self::new(
+            yyyymmdd / 10000,
+            (yyyymmdd / 100 % 100) as u8,
+            (yyyymmdd % 100) as u8,
+        )
+    }
+
+    pub fn to_ddate(&self) -> i32 {
+        self.year * 10000 + self.month as i32 * 100 + self.day as i32
+    }
+
+    /// Day of the week, 0 = Sunday
+    pub fn day_of_week(&self) -> i32 {
+        // 01-01-1900 was a monday
+        (self.to_julian() + 1).rem_euclid(7)
+    }
+
+    pub fn is_leap_year(year: i32) -> bool {
+        year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    }
+
+    pub fn days_in_month(year: i32, month: u8) -> u8 {
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 => {
+                if Self::is_leap_year(year) {
+                    29
+                } else {
+                    28
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (1900..=2178).contains(&self.year)
+            && (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= Self::days_in_month(self.year, self.month)
+    }
+
+    /// Converts a julian day number (days since 01-01-1900) to a calendar date.
+    pub fn from_julian(julian: i32) -> Self {
+        // days since 1970-01-01 are easier to handle (Howard Hinnant's civil_from_days)
+        let z = julian as i64 - 25567 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u8;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Self { year, month, day }
+    }
+
+    /// Converts the date to a julian day number (days since 01-01-1900).
+    pub fn to_julian(&self) -> i32 {
+        let y = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        (era * 146097 + doe - 719468 + 25567) as i32
+    }
+
+    /// Parses a date entered by the user, separators may be '-', '/' or '.'
+    /// Two digit years < 80 are treated as 20xx.
+    pub fn parse(str: &str, format: DateFormat) -> Option<Self> {
+        let parts: Vec<&str> = str.trim().split(['-', '/', '.']).collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        let mut values = [0; 3];
+        for (i, p) in parts.iter().enumerate() {
+            if p.is_empty() || p.len() > 4 || !p.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            values[i] = p.parse::<i32>().ok()?;
+        }
+        let (year, month, day) = match format {
+            DateFormat::MmDdYy => (values[2], values[0], values[1]),
+            DateFormat::DdMmYy => (values[2], values[1], values[0]),
+            DateFormat::YyMmDd => (values[0], values[1], values[2]),
+        };
+        let year = match year {
+            0..=79 => 2000 + year,
+            80..=99 => 1900 + year,
+            _ => year,
+        };
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let date = Self::new(year, month as u8, day as u8);
+        if date.is_valid() {
+            Some(date)
+        } else {
+            None
+        }
+    }
+
+    /// Formats the date with a four digit year (EDATE).
+    pub fn format_long(&self, format: DateFormat) -> String {
+        match format {
+            DateFormat::MmDdYy => format!("{:02}-{:02}-{:04}", self.month, self.day, self.year),
+            DateFormat::DdMmYy => format!("{:02}-{:02}-{:04}", self.day, self.month, self.year),
+            DateFormat::YyMmDd => format!("{:04}-{:02}-{:02}", self.year, self.month, self.day),
+        }
+    }
+
+    pub fn format(&self, format: DateFormat) -> String {
+        let yy = self.year % 100;
+        match format {
+            DateFormat::MmDdYy => format!("{:02}-{:02}-{:02}", self.month, self.day, yy),
+            DateFormat::DdMmYy => format!("{:02}-{:02}-{:02}", self.day, self.month, yy),
+            DateFormat::YyMmDd => format!("{:02}-{:02}-{:02}", yy, self.month, self.day),
+        }
+    }
+}
+
+/// Current local time in seconds since midnight.
+pub fn now_seconds() -> i32 {
+    Local::now().num_seconds_from_midnight() as i32
+}
+
+/// Formats seconds since midnight as "hh:mm:ss".
+pub fn format_time(seconds: i32) -> String {
+    let seconds = seconds.rem_euclid(24 * 60 * 60);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Formats seconds since midnight in civilian format "hh:mm:ss AM".
+pub fn format_time_ap(seconds: i32) -> String {
+    let seconds = seconds.rem_euclid(24 * 60 * 60);
+    let hour = seconds / 3600;
+    let hour12 = match hour % 12 {
+        0 => 12,
+        h => h,
+    };
+    format!(
+        "{:02}:{:02}:{:02} {}",
+        hour12,
+        seconds / 60 % 60,
+        seconds % 60,
+        if hour < 12 { "AM" } else { "PM" }
+    )
+}
+
+/// Parses a "hh:mm" or "hh:mm:ss" time string to seconds since midnight.
+pub fn parse_time(str: &str) -> Option<i32> {
+    let parts: Vec<&str> = str.trim().split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let mut values = [0; 3];
+    for (i, p) in parts.iter().enumerate() {
+        if p.is_empty() || p.len() > 2 || !p.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        values[i] = p.parse::<i32>().ok()?;
+    }
+    if values[0] > 23 || values[1] > 59 || values[2] > 59 {
+        return None;
+    }
+    Some(values[0] * 60 * 60 + values[1] * 60 + values[2])
+}
@@ -0,0 +1,1179 @@
+//! dBase III (DBF) files for the D* statements & functions.
+//!
+//! Like CodeBase, which PCBoard uses, every operation sets a status per channel: 0 on success,
+//! a positive code for EOF/BOF and a negative error code (`DERRMSG` text). Changed records are
+//! written when the record pointer moves, on `DUNLOCK` and when the file is closed.
+//!
+//! Records are locked dBase style on bytes starting at 1,000,000,000: the record number is the
+//! offset, the append lock is at offset 0 and a file lock covers the whole range. Files that
+//! are opened exclusively need no locks.
+use ppl_engine::ast::VariableValue;
+
+use super::{
+    get_real, get_string,
+    ndx::{Index, E4_INDEX, E4_TAG_NAME},
+    DataFile, PCBoardIO,
+};
+use crate::pcb_date::{DateFormat, PcbDate};
+
+pub const CHANNELS: usize = 8;
+
+pub const R4_SUCCESS: i32 = 0;
+pub const R4_AFTER: i32 = 2;
+pub const R4_EOF: i32 = 3;
+pub const R4_BOF: i32 = 4;
+
+pub const E4_CLOSE: i32 = -10;
+pub const E4_CREATE: i32 = -20;
+pub const E4_LOCK: i32 = -50;
+pub const E4_OPEN: i32 = -60;
+pub const E4_READ: i32 = -70;
+pub const E4_UNLOCK: i32 = -110;
+pub const E4_WRITE: i32 = -120;
+pub const E4_DATA: i32 = -200;
+pub const E4_FIELD_NAME: i32 = -210;
+pub const E4_FIELD_TYPE: i32 = -220;
+pub const E4_RECORD_LEN: i32 = -230;
+pub const E4_PARM: i32 = -930;
+
+const VERSION: u8 = 0x03;
+const HEADER_LEN: usize = 32;
+const DESCRIPTOR_LEN: usize = 32;
+const HEADER_END: u8 = 0x0D;
+const EOF_MARKER: u8 = 0x1A;
+const DELETED: u8 = b'*';
+const MAX_RECORD_LEN: usize = 4000;
+const MAX_FIELDS: usize = 128;
+
+const LOCK_OFFSET: u64 = 1_000_000_000;
+
+/// Description of a `DERRMSG` error code.
+pub fn error_message(code: i32) -> &'static str {
+    match code {
+        R4_SUCCESS => "No error",
+        R4_AFTER => "Key not found, positioned on the following record",
+        R4_EOF => "End of file",
+        R4_BOF => "Beginning of file",
+        E4_CLOSE => "Closing file",
+        E4_CREATE => "Creating file",
+        E4_LOCK => "Locking record or file",
+        E4_OPEN => "Opening file",
+        E4_READ => "Reading file",
+        E4_UNLOCK => "Unlocking record or file",
+        E4_WRITE => "Writing to file",
+        E4_DATA => "File is not a data file",
+        E4_FIELD_NAME => "Unrecognized field name",
+        E4_FIELD_TYPE => "Unrecognized field type",
+        E4_RECORD_LEN => "Record length is too large",
+        E4_INDEX => "Index file error",
+        E4_TAG_NAME => "Unrecognized tag name",
+        E4_PARM => "Channel not open or invalid parameter",
+        _ => "Unknown error",
+    }
+}
+
+/// A field of a data file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// C, N, D or L
+    pub field_type: char,
+    pub len: usize,
+    pub decimals: usize,
+    /// offset in the record, the deleted flag is at 0
+    offset: usize,
+}
+
+impl Field {
+    /// Parses a DCREATE field definition: "NAME,TYPE,LEN,DEC". D & L fields have a fixed length.
+    fn parse(info: &str) -> Result<Self, i32> {
+        let mut parts = info.split(',').map(str::trim);
+        let name = parts.next().unwrap_or_default().to_uppercase();
+        if name.is_empty()
+            || name.len() > 10
+            || !name.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(E4_FIELD_NAME);
+        }
+        let field_type = parts
+            .next()
+            .and_then(|t| t.chars().next())
+            .map(|t| t.to_ascii_uppercase())
+            .ok_or(E4_FIELD_TYPE)?;
+        let mut number = || parts.next().map_or(Ok(0), |n| n.parse::<usize>());
+        let (len, decimals) = match (number(), number()) {
+            (Ok(len), Ok(decimals)) => (len, decimals),
+            _ => return Err(E4_FIELD_TYPE),
+        };
+        let (len, decimals) = match field_type {
+            'C' if (1..=254).contains(&len) => (len, 0),
+            'N' if (1..=19).contains(&len) && (decimals == 0 || decimals + 2 <= len) => {
+                (len, decimals)
+            }
+            'D' => (8, 0),
+            'L' => (1, 0),
+            _ => return Err(E4_FIELD_TYPE),
+        };
+        Ok(Field {
+            name,
+            field_type,
+            len,
+            decimals,
+            offset: 0,
+        })
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The bytes of the field in a record.
+    pub(crate) fn raw<'a>(&self, record: &'a [u8]) -> &'a [u8] {
+        &record[self.offset..self.offset + self.len]
+    }
+
+    fn descriptor(&self) -> [u8; DESCRIPTOR_LEN] {
+        let mut descriptor = [0; DESCRIPTOR_LEN];
+        descriptor[..self.name.len()].copy_from_slice(self.name.as_bytes());
+        descriptor[11] = self.field_type as u8;
+        descriptor[16] = self.len as u8;
+        descriptor[17] = self.decimals as u8;
+        descriptor
+    }
+
+    /// Value of the field in a record: C is a STRING without trailing spaces, N an INTEGER
+    /// (REAL with decimals), D a DATE and L a BOOLEAN.
+    fn decode(&self, record: &[u8]) -> VariableValue {
+        let text: String = record[self.offset..self.offset + self.len]
+            .iter()
+            .map(|b| *b as char)
+            .collect();
+        match self.field_type {
+            'N' => {
+                let text = text.trim();
+                match text.parse::<i32>() {
+                    Ok(i) if self.decimals == 0 => VariableValue::Integer(i),
+                    _ => VariableValue::Real(text.parse().unwrap_or(0.0)),
+                }
+            }
+            'D' => match text.trim().parse::<i32>() {
+                Ok(ddate) if text.len() == 8 => {
+                    VariableValue::Date(PcbDate::from_ddate(ddate).to_julian() as u16)
+                }
+                _ => VariableValue::Date(0),
+            },
+            'L' => VariableValue::Boolean(matches!(text.as_str(), "T" | "t" | "Y" | "y")),
+            _ => VariableValue::String(text.trim_end().to_string()),
+        }
+    }
+
+    /// Stores a value in a record, strings are parsed for D fields ("yyyymmdd" or the board's format).
+    pub(crate) fn encode(&self, record: &mut [u8], value: &VariableValue, date_format: DateFormat) {
+        let text = match self.field_type {
+            'N' => {
+                let number = get_real(value).unwrap_or(0.0);
+                let text = format!("{:>1$.2$}", number, self.len, self.decimals);
+                if text.len() > self.len {
+                    "*".repeat(self.len)
+                } else {
+                    text
+                }
+            }
+            'D' => {
+                let date = match value {
+                    VariableValue::Date(julian) | VariableValue::EDate(julian) => {
+                        Some(PcbDate::from_julian(*julian as i32))
+                    }
+                    VariableValue::String(str) if str.trim().is_empty() => None,
+                    VariableValue::String(str) => match str.trim().parse::<i32>() {
+                        Ok(ddate) if str.trim().len() == 8 => Some(PcbDate::from_ddate(ddate)),
+                        _ => PcbDate::parse(str, date_format),
+                    },
+                    _ => None,
+                };
+                date.filter(|date| date.to_julian() > 0)
+                    .map_or(String::new(), |date| format!("{:08}", date.to_ddate()))
+            }
+            'L' => {
+                let set = match value {
+                    VariableValue::Boolean(b) => *b,
+                    VariableValue::String(str) => {
+                        matches!(str.trim().chars().next(), Some('T' | 't' | 'Y' | 'y' | '1'))
+                    }
+                    _ => get_real(value).unwrap_or(0.0) != 0.0,
+                };
+                if set { "T" } else { "F" }.to_string()
+            }
+            _ => get_string(value),
+        };
+        let field = &mut record[self.offset..self.offset + self.len];
+        field.fill(b' ');
+        for (byte, c) in field.iter_mut().zip(text.chars()) {
+            *byte = c as u32 as u8;
+        }
+    }
+}
+
+/// An open data file.
+pub struct DbfFile {
+    file: Box<dyn DataFile>,
+    pub alias: String,
+    pub fields: Vec<Field>,
+    header_len: u64,
+    record_len: usize,
+    /// current record, the record count + 1 at EOF
+    pub recno: u32,
+    record: Vec<u8>,
+    /// the record buffer has changes that aren't written yet
+    pub changed: bool,
+    pub bof: bool,
+    pub eof: bool,
+    /// DNEW was called, the buffer is a new record for DADD
+    appending: bool,
+    exclusive: bool,
+    file_locked: bool,
+    locked_records: Vec<u32>,
+    indexes: Vec<Index>,
+    /// index of the selected tag, records are in record order without one
+    tag: Option<usize>,
+}
+
+impl DbfFile {
+    fn open(file: Box<dyn DataFile>, alias: String, exclusive: bool) -> Result<Self, i32> {
+        let mut dbf = DbfFile {
+            file,
+            alias,
+            fields: Vec::new(),
+            header_len: 0,
+            record_len: 0,
+            recno: 1,
+            record: Vec::new(),
+            changed: false,
+            bof: true,
+            eof: true,
+            appending: false,
+            exclusive,
+            file_locked: false,
+            locked_records: Vec::new(),
+            indexes: Vec::new(),
+            tag: None,
+        };
+        let header = dbf.read(0, HEADER_LEN)?;
+        if header.len() < HEADER_LEN || header[0] & 0x07 != VERSION {
+            return Err(E4_DATA);
+        }
+        dbf.header_len = u16::from_le_bytes([header[8], header[9]]) as u64;
+        dbf.record_len = u16::from_le_bytes([header[10], header[11]]) as usize;
+        // the header needs at least the terminator and has to fit the file
+        let size = dbf.file.size().map_err(|_| E4_READ)?;
+        if dbf.header_len < HEADER_LEN as u64 + 1 || dbf.header_len > size {
+            return Err(E4_DATA);
+        }
+        let descriptors = dbf.read(HEADER_LEN as u64, dbf.header_len as usize - HEADER_LEN)?;
+        let mut offset = 1;
+        for descriptor in descriptors.chunks_exact(DESCRIPTOR_LEN) {
+            if descriptor[0] == HEADER_END {
+                break;
+            }
+            let name = descriptor[..11]
+                .iter()
+                .take_while(|b| **b != 0)
+                .map(|b| *b as char)
+                .collect();
+            let field = Field {
+                name,
+                field_type: descriptor[11] as char,
+                len: descriptor[16] as usize,
+                decimals: descriptor[17] as usize,
+                offset,
+            };
+            offset += field.len;
+            dbf.fields.push(field);
+        }
+        if offset != dbf.record_len {
+            return Err(E4_DATA);
+        }
+        dbf.record = vec![b' '; dbf.record_len];
+        dbf.top()?;
+        Ok(dbf)
+    }
+
+    fn read(&mut self, pos: u64, len: usize) -> Result<Vec<u8>, i32> {
+        self.file.read_at(pos, len).map_err(|err| {
+            log::error!("error reading data file: {}", err);
+            E4_READ
+        })
+    }
+
+    fn write(&mut self, pos: u64, data: &[u8]) -> Result<(), i32> {
+        self.file.write_at(pos, data).map_err(|err| {
+            log::error!("error writing data file: {}", err);
+            E4_WRITE
+        })
+    }
+
+    fn record_pos(&self, recno: u32) -> u64 {
+        self.header_len + (recno as u64 - 1) * self.record_len as u64
+    }
+
+    /// The record count from the header, other nodes may have appended records.
+    pub fn record_count(&mut self) -> Result<u32, i32> {
+        let count = self.read(4, 4)?;
+        match count.try_into() {
+            Ok(count) => Ok(u32::from_le_bytes(count)),
+            Err(_) => Err(E4_READ),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Result<&Field, i32> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or(E4_FIELD_NAME)
+    }
+
+    pub fn get(&self, name: &str) -> Result<VariableValue, i32> {
+        Ok(self.field(name)?.decode(&self.record))
+    }
+
+    pub fn put(
+        &mut self,
+        name: &str,
+        value: &VariableValue,
+        date_format: DateFormat,
+    ) -> Result<(), i32> {
+        let field = self.field(name)?.clone();
+        field.encode(&mut self.record, value, date_format);
+        self.changed = true;
+        Ok(())
+    }
+
+    pub fn blank_field(&mut self, name: &str) -> Result<(), i32> {
+        let field = self.field(name)?;
+        let range = field.offset..field.offset + field.len;
+        self.record[range].fill(b' ');
+        self.changed = true;
+        Ok(())
+    }
+
+    pub fn blank(&mut self) {
+        self.record.fill(b' ');
+        self.changed = true;
+    }
+
+    pub fn deleted(&self) -> bool {
+        self.record[0] == DELETED
+    }
+
+    pub fn set_deleted(&mut self, deleted: bool) {
+        self.record[0] = if deleted { DELETED } else { b' ' };
+        self.changed = true;
+    }
+
+    fn holds_lock(&self, recno: u32) -> bool {
+        self.exclusive || self.file_locked || self.locked_records.contains(&recno)
+    }
+
+    /// Runs `op` with record `recno` (0 is the append lock) locked, if it isn't locked already.
+    fn with_lock<T>(
+        &mut self,
+        recno: u32,
+        op: impl FnOnce(&mut Self) -> Result<T, i32>,
+    ) -> Result<T, i32> {
+        if self.holds_lock(recno) {
+            return op(self);
+        }
+        if !self.try_lock(LOCK_OFFSET + recno as u64, 1)? {
+            return Err(E4_LOCK);
+        }
+        let res = op(self);
+        self.file
+            .unlock(LOCK_OFFSET + recno as u64, 1)
+            .map_err(|_| E4_UNLOCK)?;
+        res
+    }
+
+    fn try_lock(&mut self, pos: u64, len: u64) -> Result<bool, i32> {
+        self.file.lock(pos, len).map_err(|err| {
+            log::error!("error locking data file: {}", err);
+            E4_LOCK
+        })
+    }
+
+    /// Writes the record buffer if it was changed.
+    pub fn flush(&mut self) -> Result<(), i32> {
+        if !self.changed || self.appending || self.eof {
+            return Ok(());
+        }
+        let recno = self.recno;
+        self.with_lock(recno, |dbf| {
+            let old = dbf.read(dbf.record_pos(recno), dbf.record_len)?;
+            let record = dbf.record.clone();
+            dbf.write(dbf.record_pos(recno), &record)?;
+            dbf.update_indexes(recno, Some(&old), Some(&record))
+        })?;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Replaces the key of a record in all indexes, deleted records have no keys.
+    fn update_indexes(
+        &mut self,
+        recno: u32,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), i32> {
+        let key = |index: &Index, record: Option<&[u8]>| {
+            record
+                .filter(|record| record.first() != Some(&DELETED))
+                .map(|record| index.key(&self.fields, record))
+        };
+        for i in 0..self.indexes.len() {
+            let old_key = key(&self.indexes[i], old);
+            let new_key = key(&self.indexes[i], new);
+            if old_key == new_key {
+                continue;
+            }
+            if let Some(old_key) = old_key {
+                self.indexes[i].remove(&old_key, recno)?;
+            }
+            if let Some(new_key) = new_key {
+                self.indexes[i].insert(new_key, recno)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The records that aren't deleted with their record numbers, for building indexes.
+    fn records(&mut self) -> Result<Vec<(u32, Vec<u8>)>, i32> {
+        let count = self.record_count()?;
+        let mut records = Vec::new();
+        for recno in 1..=count {
+            let record = self.read(self.record_pos(recno), self.record_len)?;
+            if record.len() == self.record_len && record[0] != DELETED {
+                records.push((recno, record));
+            }
+        }
+        Ok(records)
+    }
+
+    fn load(&mut self, recno: u32) -> Result<(), i32> {
+        let record = self.read(self.record_pos(recno), self.record_len)?;
+        if record.len() < self.record_len {
+            return Err(E4_READ);
+        }
+        self.record = record;
+        self.recno = recno;
+        self.changed = false;
+        self.appending = false;
+        self.bof = false;
+        self.eof = false;
+        Ok(())
+    }
+
+    /// Moves behind the last record, the buffer is blank.
+    fn set_eof(&mut self, count: u32) {
+        self.record.fill(b' ');
+        self.recno = count + 1;
+        self.changed = false;
+        self.appending = false;
+        self.eof = true;
+    }
+
+    pub fn go(&mut self, recno: u32) -> Result<(), i32> {
+        self.flush()?;
+        if recno == 0 || recno > self.record_count()? {
+            return Err(E4_READ);
+        }
+        self.load(recno)
+    }
+
+    pub fn top(&mut self) -> Result<(), i32> {
+        self.flush()?;
+        if let Some(tag) = self.tag {
+            let first = self.indexes[tag].first()?;
+            return self.load_entry(first);
+        }
+        match self.record_count()? {
+            0 => {
+                self.set_eof(0);
+                self.bof = true;
+                Ok(())
+            }
+            _ => self.load(1),
+        }
+    }
+
+    pub fn bottom(&mut self) -> Result<(), i32> {
+        self.flush()?;
+        if let Some(tag) = self.tag {
+            let last = self.indexes[tag].last()?;
+            return self.load_entry(last);
+        }
+        match self.record_count()? {
+            0 => {
+                self.set_eof(0);
+                self.bof = true;
+                Ok(())
+            }
+            count => self.load(count),
+        }
+    }
+
+    /// Loads the record of an index entry, an empty index is BOF & EOF.
+    fn load_entry(&mut self, entry: Option<(Vec<u8>, u32)>) -> Result<(), i32> {
+        match entry {
+            Some((_, recno)) => self.load(recno),
+            None => {
+                let count = self.record_count()?;
+                self.set_eof(count);
+                self.bof = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Skips `count` records, returns R4_EOF or R4_BOF if that moves past the first or last record.
+    /// With a selected tag the records are skipped in the order of the index.
+    pub fn skip(&mut self, count: i32) -> Result<i32, i32> {
+        self.flush()?;
+        if let Some(tag) = self.tag {
+            return self.skip_tag(tag, count);
+        }
+        let records = self.record_count()?;
+        let target = self.recno as i64 + count as i64;
+        if target > records as i64 {
+            self.set_eof(records);
+            return Ok(R4_EOF);
+        }
+        if target < 1 {
+            if records == 0 {
+                self.set_eof(0);
+            } else {
+                self.load(1)?;
+            }
+            self.bof = true;
+            return Ok(R4_BOF);
+        }
+        self.load(target as u32)?;
+        Ok(R4_SUCCESS)
+    }
+
+    fn skip_tag(&mut self, tag: usize, count: i32) -> Result<i32, i32> {
+        // the position is searched each time, other nodes may have changed the index
+        let mut current = (!self.eof).then(|| {
+            (
+                self.indexes[tag].key(&self.fields, &self.record),
+                self.recno,
+            )
+        });
+        for _ in 0..count.unsigned_abs() {
+            let entry = match (&current, count > 0) {
+                (Some((key, recno)), true) => self.indexes[tag].next(key, *recno)?,
+                (None, true) => None,
+                (Some((key, recno)), false) => self.indexes[tag].prev(key, *recno)?,
+                (None, false) => self.indexes[tag].last()?,
+            };
+            match entry {
+                Some(entry) => current = Some(entry),
+                None if count > 0 => {
+                    let records = self.record_count()?;
+                    self.set_eof(records);
+                    return Ok(R4_EOF);
+                }
+                None => {
+                    let first = self.indexes[tag].first()?;
+                    self.load_entry(first)?;
+                    self.bof = true;
+                    return Ok(R4_BOF);
+                }
+            }
+        }
+        match current {
+            Some((_, recno)) => self.load(recno)?,
+            None => {
+                let records = self.record_count()?;
+                self.set_eof(records);
+            }
+        }
+        Ok(R4_SUCCESS)
+    }
+
+    /// Positions on the first record with a key >= `value` in the selected tag.
+    /// Returns R4_SUCCESS if the key matches, R4_AFTER if a following record was found or R4_EOF.
+    pub fn seek(&mut self, value: &VariableValue, date_format: DateFormat) -> Result<i32, i32> {
+        self.flush()?;
+        let tag = self.tag.ok_or(E4_TAG_NAME)?;
+        let (key, len) = self.indexes[tag].seek_key(&self.fields, value, date_format);
+        match self.indexes[tag].seek(&key)? {
+            Some((found, recno)) => {
+                self.load(recno)?;
+                Ok(if found[..len] == key[..len] {
+                    R4_SUCCESS
+                } else {
+                    R4_AFTER
+                })
+            }
+            None => {
+                let records = self.record_count()?;
+                self.set_eof(records);
+                Ok(R4_EOF)
+            }
+        }
+    }
+
+    /// DNCREATE - creates an index of the records & opens it.
+    pub fn create_index(
+        &mut self,
+        io: &mut dyn PCBoardIO,
+        name: &str,
+        expression: &str,
+    ) -> Result<(), i32> {
+        self.flush()?;
+        let tag = default_alias(name);
+        self.close_index(&tag)?;
+        let file_name = file_name_with_extension(name, "NDX");
+        let file = io
+            .open_data(&file_name, true, self.exclusive)
+            .map_err(|err| {
+                log::error!("can't create {}: {}", file_name, err);
+                E4_CREATE
+            })?;
+        let records = self.records()?;
+        let index = Index::create(
+            file,
+            tag,
+            expression,
+            &self.fields,
+            self.exclusive,
+            &records,
+        )?;
+        self.indexes.push(index);
+        Ok(())
+    }
+
+    /// DNOPEN - opens an index, its tag name is the file name.
+    pub fn open_index(&mut self, io: &mut dyn PCBoardIO, name: &str) -> Result<(), i32> {
+        let tag = default_alias(name);
+        self.close_index(&tag)?;
+        let file_name = file_name_with_extension(name, "NDX");
+        let file = io
+            .open_data(&file_name, false, self.exclusive)
+            .map_err(|err| {
+                log::error!("can't open {}: {}", file_name, err);
+                E4_OPEN
+            })?;
+        let index = Index::open(file, tag, &self.fields, self.exclusive)?;
+        self.indexes.push(index);
+        Ok(())
+    }
+
+    /// Closes the index of a tag, the record order is used if it was selected.
+    pub fn close_index(&mut self, tag: &str) -> Result<(), i32> {
+        let Some(i) = self.find_tag(tag) else {
+            return Ok(());
+        };
+        self.flush()?;
+        self.indexes.remove(i);
+        self.tag = match self.tag {
+            Some(selected) if selected == i => None,
+            Some(selected) if selected > i => Some(selected - 1),
+            selected => selected,
+        };
+        Ok(())
+    }
+
+    pub fn close_indexes(&mut self) -> Result<(), i32> {
+        self.flush()?;
+        self.indexes.clear();
+        self.tag = None;
+        Ok(())
+    }
+
+    /// DTAG - selects the index order, "" selects the record order.
+    pub fn select_tag(&mut self, tag: &str) -> Result<(), i32> {
+        self.tag = if tag.trim().is_empty() {
+            None
+        } else {
+            Some(self.find_tag(tag).ok_or(E4_TAG_NAME)?)
+        };
+        Ok(())
+    }
+
+    /// Name of the selected tag, "" in record order.
+    pub fn tag_name(&self) -> &str {
+        self.tag.map_or("", |tag| &self.indexes[tag].tag)
+    }
+
+    fn find_tag(&self, tag: &str) -> Option<usize> {
+        let tag = default_alias(tag);
+        self.indexes.iter().position(|index| index.tag == tag)
+    }
+
+    /// DNEW - starts a blank new record, DADD appends it.
+    pub fn new_record(&mut self) -> Result<(), i32> {
+        self.flush()?;
+        self.record.fill(b' ');
+        self.changed = false;
+        self.appending = true;
+        Ok(())
+    }
+
+    /// Appends the record buffer & makes it the current record.
+    pub fn append(&mut self) -> Result<(), i32> {
+        let record = self.record.clone();
+        let recno = self.with_lock(0, |dbf| {
+            let recno = dbf.record_count()? + 1;
+            let mut data = record;
+            data.push(EOF_MARKER);
+            dbf.write(dbf.record_pos(recno), &data)?;
+            let mut header = Vec::with_capacity(7);
+            header.extend(update_date());
+            header.extend(recno.to_le_bytes());
+            dbf.write(1, &header)?;
+            let record = dbf.record.clone();
+            dbf.update_indexes(recno, None, Some(&record))?;
+            Ok(recno)
+        })?;
+        self.recno = recno;
+        self.changed = false;
+        self.appending = false;
+        self.bof = false;
+        self.eof = false;
+        Ok(())
+    }
+
+    /// Removes the deleted records, needs exclusive access or a file lock.
+    pub fn pack(&mut self) -> Result<(), i32> {
+        self.flush()?;
+        if !self.exclusive && !self.file_locked {
+            return Err(E4_LOCK);
+        }
+        let count = self.record_count()?;
+        let mut kept = 0;
+        for recno in 1..=count {
+            let record = self.read(self.record_pos(recno), self.record_len)?;
+            if record.first() == Some(&DELETED) {
+                continue;
+            }
+            kept += 1;
+            if kept != recno {
+                self.write(self.record_pos(kept), &record)?;
+            }
+        }
+        let end = self.record_pos(kept + 1);
+        self.write(end, &[EOF_MARKER])?;
+        self.file.set_size(end + 1).map_err(|_| E4_WRITE)?;
+        let mut header = Vec::with_capacity(7);
+        header.extend(update_date());
+        header.extend(kept.to_le_bytes());
+        self.write(1, &header)?;
+        let records = self.records()?;
+        for i in 0..self.indexes.len() {
+            self.indexes[i].rebuild(&self.fields, &records)?;
+        }
+        self.top()
+    }
+
+    pub fn lock_file(&mut self) -> Result<(), i32> {
+        if self.exclusive || self.file_locked {
+            return Ok(());
+        }
+        if !self.try_lock(LOCK_OFFSET, LOCK_OFFSET)? {
+            return Err(E4_LOCK);
+        }
+        self.file_locked = true;
+        Ok(())
+    }
+
+    /// Locks records, either all of them get locked or none.
+    pub fn lock_records(&mut self, records: &[u32]) -> Result<(), i32> {
+        let mut locked = Vec::new();
+        for recno in records {
+            if self.holds_lock(*recno) || locked.contains(recno) {
+                continue;
+            }
+            match self.try_lock(LOCK_OFFSET + *recno as u64, 1) {
+                Ok(true) => locked.push(*recno),
+                res => {
+                    for recno in locked {
+                        let _ = self.file.unlock(LOCK_OFFSET + recno as u64, 1);
+                    }
+                    return res.and(Err(E4_LOCK));
+                }
+            }
+        }
+        self.locked_records.extend(locked);
+        Ok(())
+    }
+
+    /// Writes pending changes & releases all locks.
+    pub fn unlock(&mut self) -> Result<(), i32> {
+        self.flush()?;
+        let mut res = Ok(());
+        for recno in std::mem::take(&mut self.locked_records) {
+            if self.file.unlock(LOCK_OFFSET + recno as u64, 1).is_err() {
+                res = Err(E4_UNLOCK);
+            }
+        }
+        if self.file_locked {
+            self.file_locked = false;
+            if self.file.unlock(LOCK_OFFSET, LOCK_OFFSET).is_err() {
+                res = Err(E4_UNLOCK);
+            }
+        }
+        res
+    }
+}
+
+impl Drop for DbfFile {
+    fn drop(&mut self) {
+        if let Err(code) = self.flush() {
+            log::error!(
+                "can't write record of {}: {}",
+                self.alias,
+                error_message(code)
+            );
+        }
+    }
+}
+
+/// Date of the last update for the header: year - 1900, month, day
+fn update_date() -> [u8; 3] {
+    let today = PcbDate::today();
+    [(today.year - 1900) as u8, today.month, today.day]
+}
+
+/// Adds an extension to file names without one.
+fn file_name_with_extension(name: &str, extension: &str) -> String {
+    let file_name = name.rsplit(['\\', '/', ':']).next().unwrap_or_default();
+    if file_name.contains('.') {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, extension)
+    }
+}
+
+/// Default alias: the file name without extension
+fn default_alias(name: &str) -> String {
+    let file_name = name.rsplit(['\\', '/', ':']).next().unwrap_or_default();
+    file_name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .to_uppercase()
+}
+
+/// The dBase channels of a PPE with the status of their last operation.
+#[derive(Default)]
+pub struct Dbase {
+    channels: [Option<DbfFile>; CHANNELS],
+    status: [i32; CHANNELS],
+}
+
+impl Dbase {
+    /// Records the result of an operation on `channel` as its status & returns the status.
+    pub fn set_status(&mut self, channel: usize, res: Result<i32, i32>) -> i32 {
+        let status = res.unwrap_or_else(|code| code);
+        self.status[channel] = status;
+        status
+    }
+
+    pub fn status(&self, channel: usize) -> i32 {
+        self.status[channel]
+    }
+
+    pub fn file(&self, channel: usize) -> Result<&DbfFile, i32> {
+        self.channels[channel].as_ref().ok_or(E4_PARM)
+    }
+
+    pub fn file_mut(&mut self, channel: usize) -> Result<&mut DbfFile, i32> {
+        self.channels[channel].as_mut().ok_or(E4_PARM)
+    }
+
+    /// Runs an operation on an open channel & sets the channel status.
+    pub fn with_file(
+        &mut self,
+        channel: usize,
+        op: impl FnOnce(&mut DbfFile) -> Result<i32, i32>,
+    ) -> i32 {
+        let res = self.file_mut(channel).and_then(op);
+        self.set_status(channel, res)
+    }
+
+    /// Creates a data file with fields given as "NAME,TYPE,LEN,DEC" & opens it on `channel`.
+    pub fn create(
+        &mut self,
+        io: &mut dyn PCBoardIO,
+        channel: usize,
+        name: &str,
+        exclusive: bool,
+        fields: &[String],
+    ) -> i32 {
+        let res = self.create_file(io, channel, name, exclusive, fields);
+        self.set_status(channel, res.map(|_| R4_SUCCESS))
+    }
+
+    fn create_file(
+        &mut self,
+        io: &mut dyn PCBoardIO,
+        channel: usize,
+        name: &str,
+        exclusive: bool,
+        fields: &[String],
+    ) -> Result<(), i32> {
+        self.close_channel(channel)?;
+        let mut parsed: Vec<Field> = Vec::new();
+        for info in fields.iter().filter(|info| !info.trim().is_empty()) {
+            let field = Field::parse(info)?;
+            if parsed.iter().any(|f| f.name == field.name) {
+                return Err(E4_FIELD_NAME);
+            }
+            parsed.push(field);
+        }
+        let record_len = 1 + parsed.iter().map(|field| field.len).sum::<usize>();
+        if parsed.is_empty() || parsed.len() > MAX_FIELDS || record_len > MAX_RECORD_LEN {
+            return Err(E4_RECORD_LEN);
+        }
+        let header_len = HEADER_LEN + parsed.len() * DESCRIPTOR_LEN + 1;
+        let mut header = vec![0; HEADER_LEN];
+        header[0] = VERSION;
+        header[1..4].copy_from_slice(&update_date());
+        header[8..10].copy_from_slice(&(header_len as u16).to_le_bytes());
+        header[10..12].copy_from_slice(&(record_len as u16).to_le_bytes());
+        for field in &parsed {
+            header.extend(field.descriptor());
+        }
+        header.push(HEADER_END);
+        header.push(EOF_MARKER);
+
+        let file_name = file_name_with_extension(name, "DBF");
+        let mut file = io.open_data(&file_name, true, exclusive).map_err(|err| {
+            log::error!("can't create {}: {}", file_name, err);
+            E4_CREATE
+        })?;
+        file.write_at(0, &header).map_err(|_| E4_WRITE)?;
+        self.channels[channel] = Some(DbfFile::open(file, default_alias(name), exclusive)?);
+        Ok(())
+    }
+
+    pub fn open(
+        &mut self,
+        io: &mut dyn PCBoardIO,
+        channel: usize,
+        name: &str,
+        exclusive: bool,
+    ) -> i32 {
+        let res = self.close_channel(channel).and_then(|_| {
+            let file_name = file_name_with_extension(name, "DBF");
+            let file = io.open_data(&file_name, false, exclusive).map_err(|err| {
+                log::error!("can't open {}: {}", file_name, err);
+                E4_OPEN
+            })?;
+            self.channels[channel] = Some(DbfFile::open(file, default_alias(name), exclusive)?);
+            Ok(R4_SUCCESS)
+        });
+        self.set_status(channel, res)
+    }
+
+    fn close_channel(&mut self, channel: usize) -> Result<(), i32> {
+        match self.channels[channel].take() {
+            Some(mut file) => file.unlock().map_err(|_| E4_CLOSE),
+            None => Ok(()),
+        }
+    }
+
+    pub fn close(&mut self, channel: usize) -> i32 {
+        let res = match self.channels[channel] {
+            Some(_) => self.close_channel(channel).map(|_| R4_SUCCESS),
+            None => Err(E4_PARM),
+        };
+        self.set_status(channel, res)
+    }
+
+    pub fn close_all(&mut self) -> i32 {
+        let mut status = R4_SUCCESS;
+        for channel in 0..CHANNELS {
+            if self.channels[channel].is_some() {
+                let res = self.close_channel(channel).map(|_| R4_SUCCESS);
+                if self.set_status(channel, res) != R4_SUCCESS {
+                    status = E4_CLOSE;
+                }
+            }
+        }
+        status
+    }
+
+    pub fn set_alias(&mut self, channel: usize, alias: &str) -> i32 {
+        self.with_file(channel, |file| {
+            file.alias = alias.trim().to_uppercase();
+            Ok(R4_SUCCESS)
+        })
+    }
+
+    pub fn new_record(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.new_record().map(|_| R4_SUCCESS))
+    }
+
+    /// DADD - appends the record buffer, usually a record started with DNEW
+    pub fn add(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.append().map(|_| R4_SUCCESS))
+    }
+
+    /// DAPPEND - appends a blank record
+    pub fn append_blank(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| {
+            file.new_record()?;
+            file.append().map(|_| R4_SUCCESS)
+        })
+    }
+
+    pub fn top(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.top().map(|_| R4_SUCCESS))
+    }
+
+    pub fn bottom(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.bottom().map(|_| R4_SUCCESS))
+    }
+
+    pub fn go(&mut self, channel: usize, recno: i32) -> i32 {
+        self.with_file(channel, |file| {
+            let recno = u32::try_from(recno).map_err(|_| E4_READ)?;
+            file.go(recno).map(|_| R4_SUCCESS)
+        })
+    }
+
+    pub fn skip(&mut self, channel: usize, count: i32) -> i32 {
+        self.with_file(channel, |file| file.skip(count))
+    }
+
+    pub fn blank(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| {
+            file.blank();
+            Ok(R4_SUCCESS)
+        })
+    }
+
+    pub fn blank_field(&mut self, channel: usize, name: &str) -> i32 {
+        self.with_file(channel, |file| file.blank_field(name).map(|_| R4_SUCCESS))
+    }
+
+    /// DDELETE/DRECALL - marks the current record as deleted or not deleted
+    pub fn set_deleted(&mut self, channel: usize, deleted: bool) -> i32 {
+        self.with_file(channel, |file| {
+            file.set_deleted(deleted);
+            Ok(R4_SUCCESS)
+        })
+    }
+
+    pub fn pack(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.pack().map(|_| R4_SUCCESS))
+    }
+
+    /// DLOCK - locks the current record
+    pub fn lock(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| {
+            let recno = file.recno;
+            file.lock_records(&[recno]).map(|_| R4_SUCCESS)
+        })
+    }
+
+    /// DLOCKF - locks the file, other nodes can't lock records or append
+    pub fn lock_file(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.lock_file().map(|_| R4_SUCCESS))
+    }
+
+    /// DLOCKR/DLOCKG - locks records, all or none of them
+    pub fn lock_records(&mut self, channel: usize, records: &[i32]) -> i32 {
+        self.with_file(channel, |file| {
+            let records = records
+                .iter()
+                .map(|recno| u32::try_from(*recno).map_err(|_| E4_LOCK))
+                .collect::<Result<Vec<_>, _>>()?;
+            file.lock_records(&records).map(|_| R4_SUCCESS)
+        })
+    }
+
+    pub fn unlock(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.unlock().map(|_| R4_SUCCESS))
+    }
+
+    /// DNCREATE - creates an index with a key expression, the tag is the file name
+    pub fn create_index(
+        &mut self,
+        io: &mut dyn PCBoardIO,
+        channel: usize,
+        name: &str,
+        expression: &str,
+    ) -> i32 {
+        self.with_file(channel, |file| {
+            file.create_index(io, name, expression).map(|_| R4_SUCCESS)
+        })
+    }
+
+    pub fn open_index(&mut self, io: &mut dyn PCBoardIO, channel: usize, name: &str) -> i32 {
+        self.with_file(channel, |file| {
+            file.open_index(io, name).map(|_| R4_SUCCESS)
+        })
+    }
+
+    pub fn close_index(&mut self, channel: usize, name: &str) -> i32 {
+        self.with_file(channel, |file| file.close_index(name).map(|_| R4_SUCCESS))
+    }
+
+    pub fn close_indexes(&mut self, channel: usize) -> i32 {
+        self.with_file(channel, |file| file.close_indexes().map(|_| R4_SUCCESS))
+    }
+
+    /// DTAG - selects the order DTOP/DBOTTOM/DSKIP use
+    pub fn select_tag(&mut self, channel: usize, name: &str) -> i32 {
+        self.with_file(channel, |file| file.select_tag(name).map(|_| R4_SUCCESS))
+    }
+
+    /// DSEEK - returns R4_SUCCESS, R4_AFTER, R4_EOF or an error
+    pub fn seek(&mut self, channel: usize, value: &VariableValue, date_format: DateFormat) -> i32 {
+        self.with_file(channel, |file| file.seek(value, date_format))
+    }
+
+    /// Value of a field of the current record, `None` if the channel isn't open or there is no such field.
+    pub fn get(&mut self, channel: usize, name: &str) -> Option<VariableValue> {
+        let res = self.file(channel).and_then(|file| file.get(name));
+        self.set_status(
+            channel,
+            res.as_ref().map(|_| R4_SUCCESS).map_err(|code| *code),
+        );
+        res.ok()
+    }
+
+    pub fn put(
+        &mut self,
+        channel: usize,
+        name: &str,
+        value: &VariableValue,
+        date_format: DateFormat,
+    ) -> i32 {
+        self.with_file(channel, |file| {
+            file.put(name, value, date_format).map(|_| R4_SUCCESS)
+        })
+    }
+
+    /// DSELECT - the channel of an alias, -1 if no file has it
+    pub fn select(&self, alias: &str) -> i32 {
+        self.channels
+            .iter()
+            .position(|file| {
+                file.as_ref()
+                    .is_some_and(|file| file.alias.eq_ignore_ascii_case(alias.trim()))
+            })
+            .map_or(-1, |channel| channel as i32)
+    }
+
+    /// DNEXT - the first free channel, -1 if all are used
+    pub fn next_free(&self) -> i32 {
+        self.channels
+            .iter()
+            .position(Option::is_none)
+            .map_or(-1, |channel| channel as i32)
+    }
+}
@@ -0,0 +1,308 @@
+//! Source level debugger for PPEs, driven by a line protocol on a local socket.
+//! It's turned on with `--ppe-debug <port>`, each PPE then waits for a client on that port.
+//!
+//! The debugger stops before the first statement. Each command is answered with
+//! its output followed by `OK` or `ERR <message>`. When execution stops the debugger sends
+//! `STOPPED <reason> <location> <statement>`, `TERMINATED [<error>]` is sent when the PPE ends.
+//! Locations are `<procedure>:<statement index>`, the main program is `MAIN`.
+//!
+//! | Command                   | Description                                              |
+//! |---------------------------|----------------------------------------------------------|
+//! | `break <target>`          | sets a breakpoint on a label, `<index>` or `<proc>:<index>` |
+//! | `clear <target>`          | removes a breakpoint                                     |
+//! | `breakpoints`             | lists the breakpoints                                    |
+//! | `continue`, `c`           | runs to the next breakpoint                              |
+//! | `step`, `s`               | executes one statement, steps into GOSUB & procedures    |
+//! | `next`, `n`               | executes one statement, steps over GOSUB & procedures    |
+//! | `out`, `o`                | runs until the current GOSUB/procedure returns           |
+//! | `stack`, `bt`             | shows the call stack                                     |
+//! | `print <var>`, `p <var>`  | shows a variable, array elements are `name(i[,j[,k]])`   |
+//! | `set <var> <value>`       | changes a variable                                       |
+//! | `level <n>`               | sets the debug level (same as `DBGLEVEL`)                |
+//! | `detach`                  | continues without debugger                               |
+//! | `quit`                    | stops the PPE                                            |
+//!
+//! With a debug level > 0 every executed statement is sent as `TRACE <location> <statement>`.
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+};
+
+use ppl_engine::ast::{Statement, VariableValue};
+
+use crate::{get_array_value, set_array_value, Interpreter, Res};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StepMode {
+    Run,
+    /// stop at the next statement
+    Into,
+    /// stop at the next statement with a call depth <= depth
+    Over(usize),
+    /// stop at the next statement with a call depth < depth
+    Out(usize),
+}
+
+pub struct Debugger {
+    reader: Box<dyn BufRead>,
+    writer: Box<dyn Write>,
+    /// (scope, statement index)
+    breakpoints: HashSet<(usize, usize)>,
+    step_mode: StepMode,
+}
+
+impl Debugger {
+    pub fn new(reader: Box<dyn BufRead>, writer: Box<dyn Write>) -> Self {
+        Self {
+            reader,
+            writer,
+            breakpoints: HashSet::new(),
+            step_mode: StepMode::Into,
+        }
+    }
+
+    /// Waits for a debugger client on `addr` (for example "127.0.0.1:4322").
+    pub fn listen(addr: &str) -> Res<Self> {
+        let listener = TcpListener::bind(addr)?;
+        log::info!("waiting for PPE debugger on {}", addr);
+        let (stream, client) = listener.accept()?;
+        log::info!("PPE debugger connected from {}", client);
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Self::new(Box::new(reader), Box::new(stream)))
+    }
+
+    fn send(&mut self, line: &str) -> Res<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Tells the client that the PPE has ended, `error` is the runtime error that stopped it.
+    pub fn terminated(&mut self, error: Option<&str>) -> Res<()> {
+        match error {
+            Some(err) => self.send(&format!("TERMINATED {}", err)),
+            None => self.send("TERMINATED"),
+        }
+    }
+
+    /// Called before a statement gets executed, returns false if the client detached.
+    fn before_statement(&mut self, interpreter: &mut Interpreter, stmt: &Statement) -> Res<bool> {
+        let location = current_location(interpreter)?;
+        if interpreter.debug_level > 0 {
+            self.send(&format!("TRACE {} {:?}", location, stmt))?;
+        }
+        let depth = interpreter.call_depth();
+        let frame = interpreter.frame()?;
+        let reason = match self.step_mode {
+            StepMode::Into => "step",
+            StepMode::Over(d) if depth <= d => "step",
+            StepMode::Out(d) if depth < d => "step",
+            _ if self.breakpoints.contains(&(frame.scope, frame.cur_ptr)) => "breakpoint",
+            _ => return Ok(true),
+        };
+        self.step_mode = StepMode::Run;
+        self.send(&format!("STOPPED {} {} {:?}", reason, location, stmt))?;
+
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                // connection closed
+                return Ok(false);
+            }
+            let line = line.trim();
+            let (cmd, arg) = line.split_once(' ').unwrap_or((line, ""));
+            let arg = arg.trim();
+            match cmd {
+                "" => continue,
+                "continue" | "c" => {
+                    self.send("OK")?;
+                    return Ok(true);
+                }
+                "step" | "s" => {
+                    self.step_mode = StepMode::Into;
+                    self.send("OK")?;
+                    return Ok(true);
+                }
+                "next" | "n" => {
+                    self.step_mode = StepMode::Over(depth);
+                    self.send("OK")?;
+                    return Ok(true);
+                }
+                "out" | "o" => {
+                    self.step_mode = StepMode::Out(depth);
+                    self.send("OK")?;
+                    return Ok(true);
+                }
+                "detach" => {
+                    self.send("OK")?;
+                    return Ok(false);
+                }
+                "quit" => {
+                    interpreter.is_running = false;
+                    self.send("OK")?;
+                    return Ok(true);
+                }
+                _ => {
+                    let res = self.execute_command(interpreter, cmd, arg);
+                    match res {
+                        Ok(()) => self.send("OK")?,
+                        Err(err) => self.send(&format!("ERR {}", err))?,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Commands that don't resume the execution.
+    fn execute_command(&mut self, interpreter: &mut Interpreter, cmd: &str, arg: &str) -> Res<()> {
+        match cmd {
+            "break" | "b" => {
+                let bp = resolve_target(interpreter, arg)?;
+                self.breakpoints.insert(bp);
+            }
+            "clear" => {
+                let bp = resolve_target(interpreter, arg)?;
+                if !self.breakpoints.remove(&bp) {
+                    return Err(format!("no breakpoint at {}", arg).into());
+                }
+            }
+            "breakpoints" => {
+                let mut breakpoints: Vec<_> = self.breakpoints.iter().copied().collect();
+                breakpoints.sort_unstable();
+                for (scope, ptr) in breakpoints {
+                    let name = interpreter.symbols.scope(scope).name.clone();
+                    self.send(&format!("{}:{}", name, ptr))?;
+                }
+            }
+            "stack" | "bt" => {
+                let mut lines = Vec::new();
+                for frame in interpreter.cur_frame.iter().rev() {
+                    let name = &interpreter.symbols.scope(frame.scope).name;
+                    lines.push(format!("{}:{}", name, frame.cur_ptr));
+                    for ptr in frame.gosub_stack.iter().rev() {
+                        lines.push(format!("{}:{} GOSUB", name, ptr));
+                    }
+                }
+                for line in lines {
+                    self.send(&line)?;
+                }
+            }
+            "print" | "p" => {
+                let (name, indices) = parse_variable(arg)?;
+                let Some(value) = interpreter.get_variable(&name) else {
+                    return Err(format!("variable {} not found", name).into());
+                };
+                let value = if indices.is_empty() {
+                    value.clone()
+                } else {
+                    get_array_value(value, &indices)?
+                };
+                self.send(&format!("{} = {:?}", arg, value))?;
+            }
+            "set" => {
+                let (var, value) = arg.split_once(' ').ok_or("usage: set <variable> <value>")?;
+                let (name, indices) = parse_variable(var)?;
+                let value = parse_value(value.trim());
+                if indices.is_empty() {
+                    if interpreter.get_variable(&name).is_none() {
+                        return Err(format!("variable {} not found", name).into());
+                    }
+                    interpreter.set_variable(&name, value)?;
+                } else {
+                    let Some(arr) = interpreter.get_variable_mut(&name)? else {
+                        return Err(format!("variable {} not found", name).into());
+                    };
+                    set_array_value(arr, &indices, value)?;
+                }
+            }
+            "level" => {
+                interpreter.debug_level = arg.parse()?;
+            }
+            _ => return Err(format!("unknown command {}", cmd).into()),
+        }
+        Ok(())
+    }
+}
+
+/// Gives the debugger & the debug log a chance to look at the next statement.
+/// Returns false if the statement must not be executed because the debugger stopped the PPE.
+pub fn before_statement(interpreter: &mut Interpreter, stmt: &Statement) -> Res<bool> {
+    if interpreter.debug_level > 0 {
+        log::debug!("{} {:?}", current_location(interpreter)?, stmt);
+    }
+    let Some(mut debugger) = interpreter.debugger.take() else {
+        return Ok(true);
+    };
+    if debugger.before_statement(interpreter, stmt)? {
+        interpreter.debugger = Some(debugger);
+    }
+    Ok(interpreter.is_running)
+}
+
+fn current_location(interpreter: &Interpreter) -> Res<String> {
+    let frame = interpreter.frame()?;
+    Ok(format!(
+        "{}:{}",
+        interpreter.symbols.scope(frame.scope).name,
+        frame.cur_ptr
+    ))
+}
+
+/// Resolves a breakpoint target: a label, a statement index of the main program or `<proc>:<index>`.
+fn resolve_target(interpreter: &Interpreter, target: &str) -> Res<(usize, usize)> {
+    if let Ok(ptr) = target.parse::<usize>() {
+        return Ok((0, ptr));
+    }
+    if let Some((name, ptr)) = target.split_once(':') {
+        let ptr = ptr.parse::<usize>()?;
+        let scopes = interpreter.symbols.scopes();
+        return match scopes
+            .iter()
+            .position(|s| s.name.eq_ignore_ascii_case(name))
+        {
+            Some(scope) => Ok((scope, ptr)),
+            None => Err(format!("procedure {} not found", name).into()),
+        };
+    }
+    // a jump continues after the label statement
+    for (i, scope) in interpreter.symbols.scopes().iter().enumerate() {
+        if let Some(ptr) = scope.label_table.get(target) {
+            return Ok((i, *ptr + 1));
+        }
+    }
+    Err(format!("label {} not found", target).into())
+}
+
+/// Parses `name` or `name(i[,j[,k]])`.
+fn parse_variable(var: &str) -> Res<(String, Vec<i32>)> {
+    let Some((name, rest)) = var.split_once('(') else {
+        return Ok((var.to_string(), Vec::new()));
+    };
+    let Some(indices) = rest.strip_suffix(')') else {
+        return Err(format!("invalid variable {}", var).into());
+    };
+    let mut res = Vec::new();
+    for i in indices.split(',') {
+        res.push(i.trim().parse::<i32>()?);
+    }
+    Ok((name.to_string(), res))
+}
+
+fn parse_value(value: &str) -> VariableValue {
+    if let Some(str) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        return VariableValue::String(str.to_string());
+    }
+    if let Ok(i) = value.parse::<i32>() {
+        return VariableValue::Integer(i);
+    }
+    match value.to_ascii_uppercase().as_str() {
+        "TRUE" => VariableValue::Boolean(true),
+        "FALSE" => VariableValue::Boolean(false),
+        _ => VariableValue::String(value.to_string()),
+    }
+}
@@ -19,4 +19,68 @@ pub enum IcyError {
 
     #[error("Variable {0} not found.")]
     VariableNotFound(String),
+
+    #[error("Label {0} not found.")]
+    LabelNotFound(String),
+
+    #[error("Procedure {0} not found.")]
+    ProcedureNotFound(String),
+
+    #[error("Function {0} not found.")]
+    FunctionNotFound(String),
+
+    #[error("Function {0} didn't return a value.")]
+    FunctionNoReturnValue(String),
+
+    #[error("Invalid parameter declaration {0}")]
+    InvalidParameterDeclaration(String),
+
+    #[error("Boolean value expected, got {0}")]
+    BoolExpected(String),
+
+    #[error("Unsupported operand for {0}: {1}")]
+    UnsupportedOperand(&'static str, String),
+
+    #[error("{0} is no array.")]
+    ArrayExpected(String),
+
+    #[error("Array index {0} out of bounds (size {1})")]
+    ArrayIndexOutOfBounds(i32, usize),
+
+    #[error("Unsupported variable type {0}")]
+    UnsupportedVariableType(String),
+
+    #[error("Unsupported statement {0}")]
+    UnsupportedStatement(String),
+
+    #[error("Unsupported function {0}")]
+    UnsupportedFunction(String),
+
+    #[error("Statement {0} is not implemented.")]
+    StatementNotImplemented(&'static str),
+
+    #[error("Function {0} is not implemented.")]
+    FunctionNotImplemented(&'static str),
+
+    #[error("Maximum call depth of {0} exceeded.")]
+    CallDepthExceeded(usize),
+
+    #[error("Maximum of {0} statements exceeded.")]
+    StatementLimitExceeded(u64),
+
+    #[error("PPE ran {0} seconds without user input.")]
+    IdleTimeExceeded(u64),
+
+    #[error("Emulated DOS memory is full.")]
+    OutOfMemory,
+
+    #[error("No stack frame.")]
+    NoStackFrame,
+
+    #[error("{file}: error executing '{statement}': {error}")]
+    RuntimeError {
+        file: String,
+        statement: String,
+        error: String,
+    },
 }
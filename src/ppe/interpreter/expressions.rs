@@ -1,13 +1,12 @@
-use std::collections::HashMap;
-
 use ppl_engine::{
     ast::{convert_to, BinOp, Constant, Declaration, Expression, VariableType, VariableValue},
     tables::{FuncOpCode, FunctionDefinition, CONSTANT_VALUES, PPL_FALSE, PPL_TRUE},
 };
 
+use crate::pcb_date::{format_time, parse_time, DateFormat, PcbDate};
 use crate::{
-    calc_table, errors::IcyError, ppe::interpreter::execute_statement, Interpreter,
-    InterpreterError, Res, StackFrame,
+    call_implementation, default_value, errors::IcyError, get_array_value, Interpreter,
+    InterpreterError, Res,
 };
 
 /// .
@@ -24,24 +23,13 @@ use crate::{
 /// Panics if .
 pub fn evaluate_exp(interpreter: &mut Interpreter, expr: &Expression) -> Res<VariableValue> {
     match expr {
-        Expression::Identifier(str) => {
-            for frame in interpreter.cur_frame.last().unwrap().values.iter() {
-                println!("{:?}", frame.0);
-                if frame.0 == str {
-                    return Ok(frame.1.clone());
-                }
+        Expression::Identifier(str) => match interpreter.get_variable(str) {
+            Some(value) => Ok(value.clone()),
+            None => {
+                log::trace!("variable {} not found", str);
+                Err(Box::new(IcyError::VariableNotFound(str.clone())))
             }
-
-            if interpreter.cur_frame.len() > 1 {
-                for frame in interpreter.cur_frame[0].values.iter() {
-                    if frame.0 == str {
-                        return Ok(frame.1.clone());
-                    }
-                }
-            }
-            println!("{}", std::backtrace::Backtrace::force_capture());
-            Err(Box::new(IcyError::VariableNotFound(str.clone())))
-        }
+        },
         Expression::Const(constant) => match constant {
             Constant::Boolean(true) => Ok(VariableValue::Integer(PPL_TRUE)),
             Constant::Boolean(false) => Ok(VariableValue::Integer(PPL_FALSE)),
@@ -64,86 +52,27 @@ pub fn evaluate_exp(interpreter: &mut Interpreter, expr: &Expression) -> Res<Var
             call_function(interpreter, func_def, params)
         }
         Expression::FunctionCall(name, parameters) => {
-            for f in &interpreter.prg.function_implementations {
-                if let Declaration::Function(pname, params, _return_type) = &f.declaration {
-                    if name != pname {
-                        continue;
-                    }
-                    let label_table = calc_table(&f.block);
+            if let Some((index, scope)) = interpreter.symbols.get_function(name) {
+                let f = &interpreter.prg.function_implementations[index];
+                if let Declaration::Function(_, params, return_type) = &f.declaration {
+                    let prg_frame = call_implementation(interpreter, f, scope, params, parameters)?;
 
-                    let mut prg_frame = StackFrame {
-                        values: HashMap::new(),
-                        gosub_stack: vec![],
-                        cur_ptr: 0,
-                        label_table,
+                    // the return value is assigned to the function name
+                    let slot = interpreter.symbols.scope(scope).get_slot(name);
+                    return match slot.and_then(|slot| prg_frame.get(slot)) {
+                        Some(val) => Ok(convert_to(*return_type, val)),
+                        None => default_value(*return_type),
                     };
-
-                    for i in 0..parameters.len() {
-                        if let Declaration::Variable(var_type, infos) = &params[i] {
-                            let value = evaluate_exp(interpreter, &parameters[i])?;
-                            for info in infos {
-                                prg_frame
-                                    .values
-                                    .insert(info.get_name().clone(), convert_to(*var_type, &value));
-                            }
-                        } else {
-                            panic!("invalid parameter declaration {:?}", params[i]);
-                        }
-                    }
-
-                    interpreter.cur_frame.push(prg_frame);
-
-                    while interpreter.cur_frame.last().unwrap().cur_ptr < f.block.statements.len() {
-                        let stmt =
-                            &f.block.statements[interpreter.cur_frame.last().unwrap().cur_ptr];
-                        execute_statement(interpreter, stmt)?;
-                        interpreter.cur_frame.last_mut().unwrap().cur_ptr += 1;
-                    }
-                    let prg_frame = interpreter.cur_frame.pop().unwrap();
-
-                    if let Some(val) = prg_frame.values.get(name) {
-                        return Ok(val.clone());
-                    } else {
-                        panic!("function didn't return a value  {}", name);
-                    }
                 }
             }
-            if interpreter
-                .cur_frame
-                .last()
-                .unwrap()
-                .values
-                .get(name)
-                .is_some()
-            {
-                let expr = &evaluate_exp(interpreter, &parameters[0])?.clone();
-                let var_value = interpreter
-                    .cur_frame
-                    .last()
-                    .unwrap()
-                    .values
-                    .get(name)
-                    .unwrap()
-                    .clone();
-                if parameters.len() == 1 {
-                    let i = get_int(expr)? - 1;
-                    if let VariableValue::Dim1(_, data) = &var_value {
-                        return Ok(data[i as usize].clone());
-                    }
-                }
-                /*
-                if parameters.len() == 2 {
-                    if let VariableValue::Dim2(t, data) = var_value {
-                        return data[get_int(&evaluate_exp(interpreter,&parameters[0]))][get_int(&evaluate_exp(interpreter,&parameters[1]))];
-                    }
-                }
-                if parameters.len() == 3 {
-                    if let VariableValue::Dim3(t, data) = var_value {
-                        return data[get_int(&evaluate_exp(interpreter,&parameters[0]))][get_int(&evaluate_exp(interpreter,&parameters[1]))][get_int(&evaluate_exp(interpreter,&parameters[2]))];
-                    }
-                }*/
+            let mut indices = Vec::new();
+            for p in parameters {
+                indices.push(get_int(&evaluate_exp(interpreter, p)?)?);
             }
-            panic!("function not found {}", name);
+            if let Some(arr) = interpreter.get_variable_mut(name)? {
+                return get_array_value(arr, &indices);
+            }
+            Err(Box::new(IcyError::FunctionNotFound(name.clone())))
         }
         Expression::Not(expr) => {
             let value = evaluate_exp(interpreter, expr)?;
@@ -154,38 +83,34 @@ pub fn evaluate_exp(interpreter: &mut Interpreter, expr: &Expression) -> Res<Var
                     PPL_FALSE
                 })),
                 VariableValue::Boolean(x) => Ok(VariableValue::Boolean(!x)),
-                _ => {
-                    panic!("unsupported for minus {:?} value {:?}", expr, value);
-                }
+                _ => Err(Box::new(IcyError::UnsupportedOperand(
+                    "not",
+                    format!("{:?}", value),
+                ))),
             }
         }
         Expression::Minus(expr) => {
             let value = evaluate_exp(interpreter, expr)?;
             match value {
-                VariableValue::Integer(x) => Ok(VariableValue::Integer(-x)),
-                _ => {
-                    panic!("unsupported for minus {:?} value {:?}", expr, value);
-                }
+                VariableValue::Integer(x) => Ok(VariableValue::Integer(x.wrapping_neg())),
+                VariableValue::Unsigned(x) => Ok(VariableValue::Unsigned(x.wrapping_neg())),
+                VariableValue::Word(x) => Ok(VariableValue::Word(x.wrapping_neg())),
+                VariableValue::SWord(x) => Ok(VariableValue::SWord(x.wrapping_neg())),
+                VariableValue::Byte(x) => Ok(VariableValue::Byte(x.wrapping_neg())),
+                VariableValue::SByte(x) => Ok(VariableValue::SByte(x.wrapping_neg())),
+                VariableValue::Money(x) => Ok(VariableValue::Money(-x)),
+                VariableValue::Real(x) => Ok(VariableValue::Real(-x)),
+                _ => Err(Box::new(IcyError::UnsupportedOperand(
+                    "minus",
+                    format!("{:?}", value),
+                ))),
             }
         }
         Expression::BinaryExpression(op, l_value, r_value) => match op {
-            BinOp::Add => {
-                Ok(evaluate_exp(interpreter, l_value)? + evaluate_exp(interpreter, r_value)?)
-            }
-            BinOp::Sub => {
-                Ok(evaluate_exp(interpreter, l_value)? - evaluate_exp(interpreter, r_value)?)
-            }
-            BinOp::Mul => {
-                Ok(evaluate_exp(interpreter, l_value)? * evaluate_exp(interpreter, r_value)?)
-            }
-            BinOp::Div => {
-                Ok(evaluate_exp(interpreter, l_value)? / evaluate_exp(interpreter, r_value)?)
-            }
-            BinOp::Mod => {
-                Ok(evaluate_exp(interpreter, l_value)?.modulo(evaluate_exp(interpreter, r_value)?))
-            }
-            BinOp::PoW => {
-                Ok(evaluate_exp(interpreter, l_value)?.pow(evaluate_exp(interpreter, r_value)?))
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::PoW => {
+                let l = evaluate_exp(interpreter, l_value)?;
+                let r = evaluate_exp(interpreter, r_value)?;
+                arithmetic(op, l, r)
             }
             BinOp::Eq => {
                 let l = evaluate_exp(interpreter, l_value)?;
@@ -229,6 +154,102 @@ pub fn get_int(val: &VariableValue) -> Res<i32> {
     }
 }
 
+/// Evaluates an arithmetic operator, MONEY & REAL operands are calculated as floating point.
+fn arithmetic(op: &BinOp, l: VariableValue, r: VariableValue) -> Res<VariableValue> {
+    match (&l, &r) {
+        // the difference of two dates is the number of days between them
+        (
+            VariableValue::Date(a) | VariableValue::EDate(a),
+            VariableValue::Date(b) | VariableValue::EDate(b),
+        ) if matches!(op, BinOp::Sub) => return Ok(VariableValue::Integer(*a as i32 - *b as i32)),
+        (VariableValue::String(_), _) | (_, VariableValue::String(_))
+            if matches!(op, BinOp::Add) =>
+        {
+            return Ok(l + r);
+        }
+        (VariableValue::Money(_) | VariableValue::Real(_), _)
+        | (_, VariableValue::Money(_) | VariableValue::Real(_)) => {}
+        _ => {
+            return Ok(match op {
+                BinOp::Add => l + r,
+                BinOp::Sub => l - r,
+                BinOp::Mul => l * r,
+                BinOp::Div => l / r,
+                BinOp::Mod => l.modulo(r),
+                _ => l.pow(r),
+            })
+        }
+    }
+    let (a, b) = (get_real(&l)?, get_real(&r)?);
+    let res = match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        // like integer division a division by zero is 0
+        BinOp::Div if b == 0.0 => 0.0,
+        BinOp::Div => a / b,
+        BinOp::Mod if b == 0.0 => 0.0,
+        BinOp::Mod => a % b,
+        _ => a.powf(b),
+    };
+    // MONEY wins over REAL, the result is rounded to cents
+    if matches!(l, VariableValue::Money(_)) || matches!(r, VariableValue::Money(_)) {
+        Ok(VariableValue::Money(round_money(res)))
+    } else {
+        Ok(VariableValue::Real(res))
+    }
+}
+
+/// Rounds a money value to cents.
+pub fn round_money(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Formats a money value with two decimals: "$1.50", "-$0.25"
+pub fn format_money(value: f64) -> String {
+    if value < 0.0 {
+        format!("-${:.2}", -value)
+    } else {
+        format!("${:.2}", value)
+    }
+}
+
+pub fn get_real(val: &VariableValue) -> Res<f64> {
+    match val {
+        VariableValue::Money(f) | VariableValue::Real(f) => Ok(*f),
+        VariableValue::Unsigned(i) => Ok(*i as f64),
+        VariableValue::String(str) => Ok(str.trim().parse().unwrap_or(0.0)),
+        _ => Ok(get_int(val)? as f64),
+    }
+}
+
+/// Integer value of a number or string, callers truncate it to their type like PPL does.
+pub fn get_i64(x: &VariableValue) -> Res<i64> {
+    match x {
+        VariableValue::Money(f) | VariableValue::Real(f) => Ok(*f as i64),
+        VariableValue::Unsigned(i) => Ok(*i as i64),
+        VariableValue::String(str) => {
+            let str = str.trim();
+            Ok(str
+                .parse::<i64>()
+                .unwrap_or_else(|_| str.parse::<f64>().unwrap_or(0.0) as i64))
+        }
+        _ => Ok(get_int(x)? as i64),
+    }
+}
+
+/// Converts `value` to the integer type of `like`, values that don't fit wrap around.
+pub fn with_int_type(like: &VariableValue, value: i64) -> VariableValue {
+    match like {
+        VariableValue::Unsigned(_) => VariableValue::Unsigned(value as u32),
+        VariableValue::Word(_) => VariableValue::Word(value as u16),
+        VariableValue::SWord(_) => VariableValue::SWord(value as i16),
+        VariableValue::Byte(_) => VariableValue::Byte(value as u8),
+        VariableValue::SByte(_) => VariableValue::SByte(value as i8),
+        _ => VariableValue::Integer(value as i32),
+    }
+}
+
 pub fn get_string(val: &VariableValue) -> String {
     match val {
         VariableValue::Boolean(b) => {
@@ -244,18 +265,54 @@ pub fn get_string(val: &VariableValue) -> String {
         VariableValue::SWord(i) => i.to_string(),
         VariableValue::Byte(i) => i.to_string(),
         VariableValue::SByte(i) => i.to_string(),
-        VariableValue::Money(i) => i.to_string(), // TODO
+        VariableValue::Money(i) => format_money(*i),
         VariableValue::Real(i) => i.to_string(),
         VariableValue::String(i) => i.clone(),
-        VariableValue::Date(i) => i.to_string(),      // TODO
-        VariableValue::EDate(i) => i.to_string(),     // TODO
-        VariableValue::Time(i) => i.to_string(),      // TODO
+        VariableValue::Date(i) => PcbDate::from_julian(*i as i32).format(DateFormat::default()),
+        VariableValue::EDate(i) => {
+            PcbDate::from_julian(*i as i32).format_long(DateFormat::default())
+        }
+        VariableValue::Time(i) => format_time(*i),
         VariableValue::Dim1(_, _) => val.to_string(), // TODO
         VariableValue::Dim2(_, _) => val.to_string(), // TODO
         VariableValue::Dim3(_, _) => val.to_string(), // TODO
     }
 }
 
+/// Converts a value to a string for displaying it to the user, dates use the board's date format.
+pub fn get_display_string(interpreter: &Interpreter, val: &VariableValue) -> String {
+    let format = interpreter.icb_data.date_format;
+    match val {
+        VariableValue::Date(i) => PcbDate::from_julian(*i as i32).format(format),
+        VariableValue::EDate(i) => PcbDate::from_julian(*i as i32).format_long(format),
+        VariableValue::Time(i) => format_time(*i),
+        VariableValue::Money(i) => format_money(*i),
+        _ => val.to_string(),
+    }
+}
+
+/// Converts a value to a date, strings are parsed in the board's date format.
+/// Invalid dates are 0 (01-01-1900).
+pub fn get_date(interpreter: &Interpreter, val: &VariableValue) -> Res<PcbDate> {
+    let julian = match val {
+        VariableValue::Date(i) | VariableValue::EDate(i) => *i as i32,
+        VariableValue::String(str) => {
+            PcbDate::parse(str, interpreter.icb_data.date_format).map_or(0, |date| date.to_julian())
+        }
+        _ => get_int(val)?,
+    };
+    Ok(PcbDate::from_julian(julian))
+}
+
+/// Converts a value to a time in seconds since midnight, strings are parsed as "hh:mm[:ss]".
+pub fn get_time(val: &VariableValue) -> Res<i32> {
+    match val {
+        VariableValue::Time(i) => Ok(*i),
+        VariableValue::String(str) => Ok(parse_time(str).unwrap_or(0)),
+        _ => get_int(val),
+    }
+}
+
 fn call_function(
     interpreter: &mut Interpreter,
     func_def: &'static FunctionDefinition,
@@ -289,7 +346,7 @@ fn call_function(
             evaluate_exp(interpreter, &params[0])?,
             evaluate_exp(interpreter, &params[1])?,
         ),
-        FuncOpCode::ABORT => predefined_functions::abort(interpreter),
+        FuncOpCode::ABORT => predefined_functions::abort(interpreter)?,
         FuncOpCode::LTRIM => predefined_functions::ltrim(
             evaluate_exp(interpreter, &params[0])?,
             evaluate_exp(interpreter, &params[1])?,
@@ -307,11 +364,11 @@ fn call_function(
             evaluate_exp(interpreter, &params[0])?,
             evaluate_exp(interpreter, &params[1])?,
             evaluate_exp(interpreter, &params[2])?,
-        ),
+        )?,
         FuncOpCode::STRIPSTR => predefined_functions::strip_string(
             evaluate_exp(interpreter, &params[0])?,
             evaluate_exp(interpreter, &params[1])?,
-        ),
+        )?,
         FuncOpCode::RTRIM => predefined_functions::rtrim(
             evaluate_exp(interpreter, &params[0])?,
             evaluate_exp(interpreter, &params[1])?,
@@ -327,31 +384,43 @@ fn call_function(
         FuncOpCode::U_NAME => predefined_functions::u_name(interpreter),
         FuncOpCode::U_LDATE => predefined_functions::u_ldate(interpreter),
         FuncOpCode::U_LTIME => predefined_functions::u_ltime(interpreter),
-        FuncOpCode::U_LDIR => predefined_functions::u_ldir(interpreter),
-        FuncOpCode::U_LMR => predefined_functions::u_lmr(interpreter),
-        FuncOpCode::U_LOGONS => predefined_functions::u_logons(interpreter),
-        FuncOpCode::U_FUL => predefined_functions::u_ful(interpreter),
-        FuncOpCode::U_FDL => predefined_functions::u_fdl(interpreter),
-        FuncOpCode::U_BDLDAY => predefined_functions::u_bdlday(interpreter),
-        FuncOpCode::U_TIMEON => predefined_functions::u_timeon(interpreter),
-        FuncOpCode::U_BDL => predefined_functions::u_bdl(interpreter),
-        FuncOpCode::U_BUL => predefined_functions::u_bul(interpreter),
-        FuncOpCode::U_MSGRD => predefined_functions::u_msgrd(interpreter),
-        FuncOpCode::U_MSGWR => predefined_functions::u_msgwr(interpreter),
+        FuncOpCode::U_LDIR => predefined_functions::u_ldir(interpreter)?,
+        FuncOpCode::U_LMR => predefined_functions::u_lmr(interpreter)?,
+        FuncOpCode::U_LOGONS => predefined_functions::u_logons(interpreter)?,
+        FuncOpCode::U_FUL => predefined_functions::u_ful(interpreter)?,
+        FuncOpCode::U_FDL => predefined_functions::u_fdl(interpreter)?,
+        FuncOpCode::U_BDLDAY => predefined_functions::u_bdlday(interpreter)?,
+        FuncOpCode::U_TIMEON => predefined_functions::u_timeon(interpreter)?,
+        FuncOpCode::U_BDL => predefined_functions::u_bdl(interpreter)?,
+        FuncOpCode::U_BUL => predefined_functions::u_bul(interpreter)?,
+        FuncOpCode::U_MSGRD => predefined_functions::u_msgrd(interpreter)?,
+        FuncOpCode::U_MSGWR => predefined_functions::u_msgwr(interpreter)?,
 
-        FuncOpCode::YEAR => predefined_functions::year(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::MONTH => predefined_functions::month(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DAY => predefined_functions::day(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DOW => predefined_functions::dow(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::HOUR => predefined_functions::hour(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::MIN => predefined_functions::min(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::SEC => predefined_functions::sec(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::TIMEAP => predefined_functions::timeap(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::YEAR => {
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::year(interpreter, date)?
+        }
+        FuncOpCode::MONTH => {
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::month(interpreter, date)?
+        }
+        FuncOpCode::DAY => {
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::day(interpreter, date)?
+        }
+        FuncOpCode::DOW => {
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dow(interpreter, date)?
+        }
+        FuncOpCode::HOUR => predefined_functions::hour(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::MIN => predefined_functions::min(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::SEC => predefined_functions::sec(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::TIMEAP => predefined_functions::timeap(evaluate_exp(interpreter, &params[0])?)?,
 
-        FuncOpCode::VER => predefined_functions::ver(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::NOCHAR => predefined_functions::nochar(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::VER => predefined_functions::ver(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::NOCHAR => predefined_functions::nochar(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::YESCHAR => {
-            predefined_functions::yeschar(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::yeschar(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::STRIPATX => {
             predefined_functions::strip_atx(evaluate_exp(interpreter, &params[0])?)
@@ -361,33 +430,34 @@ fn call_function(
             predefined_functions::tostring(evaluate_exp(interpreter, &params[0])?)
         }
         FuncOpCode::MASK_PWD => {
-            predefined_functions::mask_pwd(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_pwd(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MASK_ALPHA => {
-            predefined_functions::mask_alpha(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_alpha(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MASK_NUM => {
-            predefined_functions::mask_num(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_num(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MASK_ALNUM => {
-            predefined_functions::mask_alnum(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_alnum(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MASK_FILE => {
-            predefined_functions::mask_file(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_file(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MASK_PATH => {
-            predefined_functions::mask_path(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_path(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MASK_ASCII => {
-            predefined_functions::mask_ascii(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mask_ascii(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CURCONF => {
-            predefined_functions::curconf(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::curconf(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::PCBDAT => predefined_functions::pcbdat(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::PCBDAT => predefined_functions::pcbdat(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::PPEPATH => predefined_functions::ppepath(interpreter),
         FuncOpCode::VALDATE => {
-            predefined_functions::valdate(evaluate_exp(interpreter, &params[0])?)
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::valdate(interpreter, date)
         }
         FuncOpCode::VALTIME => {
             predefined_functions::valtime(evaluate_exp(interpreter, &params[0])?)
@@ -404,37 +474,52 @@ fn call_function(
         FuncOpCode::UN_NAME => predefined_functions::un_name(interpreter),
         FuncOpCode::UN_CITY => predefined_functions::un_city(interpreter),
         FuncOpCode::UN_OPER => predefined_functions::un_oper(interpreter),
-        FuncOpCode::CURSEC => predefined_functions::cursec(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::CURSEC => predefined_functions::cursec(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::GETTOKEN => predefined_functions::gettoken(interpreter),
         FuncOpCode::MINLEFT => {
-            predefined_functions::minleft(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::MINON => predefined_functions::minon(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::GETENV => predefined_functions::getenv(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::CALLID => predefined_functions::callid(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGAL => predefined_functions::regal(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGAH => predefined_functions::regah(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGBL => predefined_functions::regbl(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGBH => predefined_functions::regbh(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGCL => predefined_functions::regcl(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGCH => predefined_functions::regch(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGDL => predefined_functions::regdl(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGDH => predefined_functions::regdh(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGAX => predefined_functions::regax(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGBX => predefined_functions::regbx(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGCX => predefined_functions::regcx(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGDX => predefined_functions::regdx(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGSI => predefined_functions::regsi(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGDI => predefined_functions::regdi(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGF => predefined_functions::regf(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGCF => predefined_functions::regcf(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGDS => predefined_functions::regds(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::REGES => predefined_functions::reges(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::B2W => predefined_functions::b2w(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::PEEKB => predefined_functions::peekb(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::PEEKW => predefined_functions::peekw(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::MKADDR => predefined_functions::mkaddr(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::EXIST => predefined_functions::exist(evaluate_exp(interpreter, &params[0])?),
+            predefined_functions::minleft(evaluate_exp(interpreter, &params[0])?)?
+        }
+        FuncOpCode::MINON => predefined_functions::minon(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::GETENV => predefined_functions::getenv(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::CALLID => predefined_functions::callid(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::REGAL => predefined_functions::regal(interpreter),
+        FuncOpCode::REGAH => predefined_functions::regah(interpreter),
+        FuncOpCode::REGBL => predefined_functions::regbl(interpreter),
+        FuncOpCode::REGBH => predefined_functions::regbh(interpreter),
+        FuncOpCode::REGCL => predefined_functions::regcl(interpreter),
+        FuncOpCode::REGCH => predefined_functions::regch(interpreter),
+        FuncOpCode::REGDL => predefined_functions::regdl(interpreter),
+        FuncOpCode::REGDH => predefined_functions::regdh(interpreter),
+        FuncOpCode::REGAX => predefined_functions::regax(interpreter),
+        FuncOpCode::REGBX => predefined_functions::regbx(interpreter),
+        FuncOpCode::REGCX => predefined_functions::regcx(interpreter),
+        FuncOpCode::REGDX => predefined_functions::regdx(interpreter),
+        FuncOpCode::REGSI => predefined_functions::regsi(interpreter),
+        FuncOpCode::REGDI => predefined_functions::regdi(interpreter),
+        FuncOpCode::REGF => predefined_functions::regf(interpreter),
+        FuncOpCode::REGCF => predefined_functions::regcf(interpreter),
+        FuncOpCode::REGDS => predefined_functions::regds(interpreter),
+        FuncOpCode::REGES => predefined_functions::reges(interpreter),
+        FuncOpCode::B2W => predefined_functions::b2w(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+        )?,
+        FuncOpCode::PEEKB => {
+            let address = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::peekb(interpreter, address)?
+        }
+        FuncOpCode::PEEKW => {
+            let address = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::peekw(interpreter, address)?
+        }
+        FuncOpCode::MKADDR => predefined_functions::mkaddr(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+        )?,
+        FuncOpCode::EXIST => {
+            let file = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::exist(interpreter, file)
+        }
         FuncOpCode::I2S => {
             let int = get_int(&evaluate_exp(interpreter, &params[0])?)?;
             let base = get_int(&evaluate_exp(interpreter, &params[1])?)?;
@@ -446,70 +531,83 @@ fn call_function(
             predefined_functions::s2i(&s, base)?
         }
         FuncOpCode::CARRIER => {
-            predefined_functions::carrier(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::TOKENSTR => {
-            predefined_functions::tokenstr(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::carrier(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::CDON => predefined_functions::cdon(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::TOKENSTR => predefined_functions::tokenstr(interpreter),
+        FuncOpCode::CDON => predefined_functions::cdon(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::LANGEXT => {
-            predefined_functions::langext(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::langext(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::ANSION => predefined_functions::ansion(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::ANSION => predefined_functions::ansion(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::VALCC => predefined_functions::valcc(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::FMTCC => predefined_functions::fmtcc(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::CCTYPE => predefined_functions::cctype(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::GETX => predefined_functions::getx(interpreter),
         FuncOpCode::GETY => predefined_functions::gety(interpreter),
-        FuncOpCode::BAND => predefined_functions::band(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::BOR => predefined_functions::bor(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::BXOR => predefined_functions::bxor(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::BNOT => predefined_functions::bnot(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::BAND => predefined_functions::band(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+        )?,
+        FuncOpCode::BOR => predefined_functions::bor(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+        )?,
+        FuncOpCode::BXOR => predefined_functions::bxor(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+        )?,
+        FuncOpCode::BNOT => predefined_functions::bnot(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::U_PWDHIST => {
-            predefined_functions::u_pwdhist(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::u_pwdhist(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::U_PWDLC => {
-            predefined_functions::u_pwdlc(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::u_pwdlc(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::U_PWDTC => {
-            predefined_functions::u_pwdtc(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::u_pwdtc(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::U_STAT => predefined_functions::u_stat(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::U_STAT => predefined_functions::u_stat(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::DEFCOLOR => {
-            predefined_functions::defcolor(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::defcolor(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::ABS => predefined_functions::abs(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::GRAFMODE => {
-            predefined_functions::grafmode(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::grafmode(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::PSA => predefined_functions::psa(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::PSA => predefined_functions::psa(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::FILEINF => {
-            predefined_functions::fileinf(evaluate_exp(interpreter, &params[0])?)
+            let file = evaluate_exp(interpreter, &params[0])?;
+            let option = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::fileinf(interpreter, file, option)?
         }
         FuncOpCode::PPENAME => predefined_functions::ppename(interpreter),
-        FuncOpCode::MKDATE => predefined_functions::mkdate(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::MKDATE => predefined_functions::mkdate(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+            evaluate_exp(interpreter, &params[2])?,
+        )?,
         FuncOpCode::CURCOLOR => {
-            predefined_functions::curcolor(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::curcolor(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::KINKEY => predefined_functions::kinkey(interpreter)?,
         FuncOpCode::MINKEY => predefined_functions::minkey(interpreter)?,
         FuncOpCode::MAXNODE => predefined_functions::maxnode(interpreter),
-        FuncOpCode::SLPATH => predefined_functions::slpath(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::SLPATH => predefined_functions::slpath(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::HELPPATH => {
-            predefined_functions::helppath(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::helppath(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TEMPPATH => {
-            predefined_functions::temppath(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::temppath(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::MODEM => predefined_functions::modem(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::MODEM => predefined_functions::modem(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::LOGGEDON => {
-            predefined_functions::loggedon(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::loggedon(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CALLNUM => {
-            predefined_functions::callnum(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::callnum(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MGETBYTE => {
-            predefined_functions::mgetbyte(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::mgetbyte(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TOKCOUNT => predefined_functions::tokcount(interpreter),
         FuncOpCode::U_RECNUM => {
@@ -517,290 +615,406 @@ fn call_function(
             predefined_functions::u_recnum(interpreter, user_name)?
         }
         FuncOpCode::U_INCONF => {
-            predefined_functions::u_inconf(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::u_inconf(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::PEEKDW => predefined_functions::peekdw(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DBGLEVEL => {
-            predefined_functions::dbglevel(evaluate_exp(interpreter, &params[0])?)
+        FuncOpCode::PEEKDW => {
+            let address = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::peekdw(interpreter, address)?
         }
+        FuncOpCode::DBGLEVEL => predefined_functions::dbglevel(interpreter),
         FuncOpCode::SCRTEXT => {
-            predefined_functions::scrtext(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::scrtext(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::SHOWSTAT => {
-            predefined_functions::showstat(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::showstat(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::PAGESTAT => {
-            predefined_functions::pagestat(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::pagestat(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TOBIGSTR => {
-            predefined_functions::tobigstr(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::tobigstr(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TOBOOLEAN => {
-            predefined_functions::toboolean(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::toboolean(evaluate_exp(interpreter, &params[0])?)?
+        }
+        FuncOpCode::TOBYTE => predefined_functions::tobyte(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::TODATE => {
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::todate(interpreter, date)?
         }
-        FuncOpCode::TOBYTE => predefined_functions::tobyte(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::TODATE => predefined_functions::todate(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::TODREAL => {
-            predefined_functions::todreal(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::todreal(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TOEDATE => {
-            predefined_functions::toedate(evaluate_exp(interpreter, &params[0])?)
+            let date = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::toedate(interpreter, date)?
         }
         FuncOpCode::TOINTEGER => {
-            predefined_functions::tointeger(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::tointeger(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TOMONEY => {
-            predefined_functions::tomoney(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::tomoney(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::TOREAL => predefined_functions::toreal(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::TOREAL => predefined_functions::toreal(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::TOSBYTE => {
-            predefined_functions::tosbyte(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::tosbyte(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::TOSWORD => {
-            predefined_functions::tosword(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::tosword(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::TOTIME => predefined_functions::totime(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::TOTIME => predefined_functions::totime(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::TOUNSIGNED => {
-            predefined_functions::tounsigned(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::tounsigned(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::TOWORD => predefined_functions::toword(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::MIXED => predefined_functions::mixed(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::ALIAS => predefined_functions::alias(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::TOWORD => predefined_functions::toword(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::MIXED => predefined_functions::mixed(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::ALIAS => predefined_functions::alias(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::CONFREG => {
-            predefined_functions::confreg(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::confreg(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CONFEXP => {
-            predefined_functions::confexp(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::confexp(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CONFSEL => {
-            predefined_functions::confsel(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::confsel(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CONFSYS => {
-            predefined_functions::confsys(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::confsys(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::CONFMW => predefined_functions::confmw(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::CONFMW => predefined_functions::confmw(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::LPRINTED => {
-            predefined_functions::lprinted(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::lprinted(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::ISNONSTOP => {
-            predefined_functions::isnonstop(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::isnonstop(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::ERRCORRECT => {
-            predefined_functions::errcorrect(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::errcorrect(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CONFALIAS => {
-            predefined_functions::confalias(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::confalias(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::USERALIAS => {
-            predefined_functions::useralias(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::useralias(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CURUSER => {
-            predefined_functions::curuser(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::curuser(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CHATSTAT => {
-            predefined_functions::chatstat(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::chatstat(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::DEFANS => predefined_functions::defans(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::DEFANS => predefined_functions::defans(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::LASTANS => {
-            predefined_functions::lastans(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::lastans(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::MEGANUM => {
-            predefined_functions::meganum(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::meganum(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::EVTTIMEADJ => {
-            predefined_functions::evttimeadj(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::ISBITSET => {
-            predefined_functions::isbitset(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::FMTREAL => {
-            predefined_functions::fmtreal(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::evttimeadj(evaluate_exp(interpreter, &params[0])?)?
         }
+        FuncOpCode::ISBITSET => predefined_functions::isbitset(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+        )?,
+        FuncOpCode::FMTREAL => predefined_functions::fmtreal(
+            evaluate_exp(interpreter, &params[0])?,
+            evaluate_exp(interpreter, &params[1])?,
+            evaluate_exp(interpreter, &params[2])?,
+        )?,
         FuncOpCode::FLAGCNT => {
-            predefined_functions::flagcnt(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::KBDBUFSIZE => {
-            predefined_functions::kbdbufsize(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::PPLBUFSIZE => {
-            predefined_functions::pplbufsize(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::KBDFILUSED => {
-            predefined_functions::kbdfilused(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::flagcnt(evaluate_exp(interpreter, &params[0])?)?
         }
+        FuncOpCode::KBDBUFSIZE => predefined_functions::kbdbufsize(interpreter),
+        FuncOpCode::PPLBUFSIZE => predefined_functions::pplbufsize(interpreter),
+        FuncOpCode::KBDFILUSED => predefined_functions::kbdfilused(interpreter),
         FuncOpCode::LOMSGNUM => {
-            predefined_functions::lomsgnum(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::lomsgnum(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::HIMSGNUM => {
-            predefined_functions::himsgnum(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::himsgnum(evaluate_exp(interpreter, &params[0])?)?
         }
 
         FuncOpCode::DRIVESPACE => {
-            predefined_functions::drivespace(evaluate_exp(interpreter, &params[0])?)
+            let drive = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::drivespace(interpreter, drive)?
         }
         FuncOpCode::OUTBYTES => predefined_functions::outbytes(),
         FuncOpCode::HICONFNUM => {
-            predefined_functions::hiconfnum(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::hiconfnum(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::INBYTES => predefined_functions::inbytes(interpreter),
-        FuncOpCode::CRC32 => predefined_functions::crc32(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::PCBMAC => predefined_functions::pcbmac(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::CRC32 => predefined_functions::crc32(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::PCBMAC => predefined_functions::pcbmac(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::ACTMSGNUM => {
-            predefined_functions::actmsgnum(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::STACKLEFT => {
-            predefined_functions::stackleft(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::STACKERR => {
-            predefined_functions::stackerr(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::actmsgnum(evaluate_exp(interpreter, &params[0])?)?
         }
+        FuncOpCode::STACKLEFT => predefined_functions::stackleft(interpreter),
+        FuncOpCode::STACKERR => predefined_functions::stackerr(interpreter),
 
         FuncOpCode::DGETALIAS => {
-            predefined_functions::dgetalias(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dgetalias(interpreter, channel)?
+        }
+        FuncOpCode::DBOF => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dbof(interpreter, channel)?
         }
-        FuncOpCode::DBOF => predefined_functions::dbof(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DCHANGED => {
-            predefined_functions::dchanged(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dchanged(interpreter, channel)?
         }
         FuncOpCode::DDECIMALS => {
-            predefined_functions::ddecimals(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::ddecimals(interpreter, channel, name)?
         }
         FuncOpCode::DDELETED => {
-            predefined_functions::ddeleted(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::ddeleted(interpreter, channel)?
+        }
+        FuncOpCode::DEOF => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::deof(interpreter, channel)?
+        }
+        FuncOpCode::DERR => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::derr(interpreter, channel)?
         }
-        FuncOpCode::DEOF => predefined_functions::deof(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DERR => predefined_functions::derr(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DFIELDS => {
-            predefined_functions::dfields(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dfields(interpreter, channel)?
         }
         FuncOpCode::DLENGTH => {
-            predefined_functions::dlength(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dlength(interpreter, channel, name)?
+        }
+        FuncOpCode::DNAME => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let number = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dname(interpreter, channel, number)?
         }
-        FuncOpCode::DNAME => predefined_functions::dname(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DRECCOUNT => {
-            predefined_functions::dreccount(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dreccount(interpreter, channel)?
         }
-        FuncOpCode::DRECNO => predefined_functions::drecno(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DTYPE => predefined_functions::dtype(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::FNEXT => predefined_functions::fnext(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DNEXT => predefined_functions::dnext(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::DRECNO => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::drecno(interpreter, channel)?
+        }
+        FuncOpCode::DTYPE => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dtype(interpreter, channel, name)?
+        }
+        FuncOpCode::FNEXT => predefined_functions::fnext(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::DNEXT => predefined_functions::dnext(interpreter),
         FuncOpCode::TODDATE => {
-            predefined_functions::toddate(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::toddate(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::DCLOSEALL => {
-            predefined_functions::dcloseall(evaluate_exp(interpreter, &params[0])?)
+        FuncOpCode::DCLOSEALL => predefined_functions::dcloseall(interpreter),
+        FuncOpCode::DOPEN => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            let exclusive = evaluate_exp(interpreter, &params[2])?;
+            predefined_functions::dopen(interpreter, channel, name, exclusive)?
+        }
+        FuncOpCode::DCLOSE => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dclose(interpreter, channel)?
         }
-        FuncOpCode::DOPEN => predefined_functions::dopen(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DCLOSE => predefined_functions::dclose(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DSETALIAS => {
-            predefined_functions::dsetalias(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let alias = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dsetalias(interpreter, channel, alias)?
+        }
+        FuncOpCode::DPACK => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dpack(interpreter, channel)?
+        }
+        FuncOpCode::DLOCKF => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dlockf(interpreter, channel)?
+        }
+        FuncOpCode::DLOCK => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dlock(interpreter, channel)?
+        }
+        FuncOpCode::DLOCKR => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let recno = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dlockr(interpreter, channel, recno)?
         }
-        FuncOpCode::DPACK => predefined_functions::dpack(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DLOCKF => predefined_functions::dlockf(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DLOCK => predefined_functions::dlock(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DLOCKR => predefined_functions::dlockr(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DUNLOCK => {
-            predefined_functions::dunlock(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dunlock(interpreter, channel)?
+        }
+        FuncOpCode::DNOPEN => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dnopen(interpreter, channel, name)?
         }
-        FuncOpCode::DNOPEN => predefined_functions::dnopen(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DNCLOSE => {
-            predefined_functions::dnclose(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dnclose(interpreter, channel, name)?
         }
         FuncOpCode::DNCLOSEALL => {
-            predefined_functions::dncloseall(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dncloseall(interpreter, channel)?
+        }
+        FuncOpCode::DNEW => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dnew(interpreter, channel)?
+        }
+        FuncOpCode::DADD => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dadd(interpreter, channel)?
         }
-        FuncOpCode::DNEW => predefined_functions::dnew(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DADD => predefined_functions::dadd(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DAPPEND => {
-            predefined_functions::dappend(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dappend(interpreter, channel)?
+        }
+        FuncOpCode::DTOP => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dtop(interpreter, channel)?
+        }
+        FuncOpCode::DGO => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let recno = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dgo(interpreter, channel, recno)?
         }
-        FuncOpCode::DTOP => predefined_functions::dtop(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DGO => predefined_functions::dgo(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DBOTTOM => {
-            predefined_functions::dbottom(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dbottom(interpreter, channel)?
+        }
+        FuncOpCode::DSKIP => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let count = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dskip(interpreter, channel, count)?
+        }
+        FuncOpCode::DBLANK => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dblank(interpreter, channel)?
         }
-        FuncOpCode::DSKIP => predefined_functions::dskip(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DBLANK => predefined_functions::dblank(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DDELETE => {
-            predefined_functions::ddelete(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::ddelete(interpreter, channel)?
         }
         FuncOpCode::DRECALL => {
-            predefined_functions::drecall(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::drecall(interpreter, channel)?
+        }
+        FuncOpCode::DTAG => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dtag(interpreter, channel)?
+        }
+        FuncOpCode::DSEEK => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let value = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dseek(interpreter, channel, value)?
         }
-        FuncOpCode::DTAG => predefined_functions::dtag(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DSEEK => predefined_functions::dseek(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DFBLANK => {
-            predefined_functions::dfblank(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dfblank(interpreter, channel, name)?
+        }
+        FuncOpCode::DGET => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            predefined_functions::dget(interpreter, channel, name)?
+        }
+        FuncOpCode::DPUT => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            let name = evaluate_exp(interpreter, &params[1])?;
+            let value = evaluate_exp(interpreter, &params[2])?;
+            predefined_functions::dput(interpreter, channel, name, value)?
+        }
+        FuncOpCode::DFCOPY => {
+            let from = evaluate_exp(interpreter, &params[0])?;
+            let from_name = evaluate_exp(interpreter, &params[1])?;
+            let to = evaluate_exp(interpreter, &params[2])?;
+            let to_name = evaluate_exp(interpreter, &params[3])?;
+            predefined_functions::dfcopy(interpreter, from, from_name, to, to_name)?
         }
-        FuncOpCode::DGET => predefined_functions::dget(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DPUT => predefined_functions::dput(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::DFCOPY => predefined_functions::dfcopy(evaluate_exp(interpreter, &params[0])?),
         FuncOpCode::DSELECT => {
-            predefined_functions::dselect(evaluate_exp(interpreter, &params[0])?)
+            let alias = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dselect(interpreter, alias)?
         }
         FuncOpCode::DCHKSTAT => {
-            predefined_functions::dchkstat(evaluate_exp(interpreter, &params[0])?)
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::dchkstat(interpreter, channel)?
         }
 
         FuncOpCode::PCBACCOUNT => {
-            predefined_functions::pcbaccount(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::pcbaccount(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::PCBACCSTAT => {
-            predefined_functions::pcbaccstat(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::pcbaccstat(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::DERRMSG => {
-            predefined_functions::derrmsg(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::derrmsg(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::ACCOUNT => {
-            predefined_functions::account(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::account(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::SCANMSGHDR => {
-            predefined_functions::scanmsghdr(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::scanmsghdr(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CHECKRIP => {
-            predefined_functions::checkrip(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::checkrip(evaluate_exp(interpreter, &params[0])?)?
         }
-        FuncOpCode::RIPVER => predefined_functions::ripver(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::RIPVER => predefined_functions::ripver(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::QWKLIMITS => {
-            predefined_functions::qwklimits(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::qwklimits(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::FINDFIRST => {
-            predefined_functions::findfirst(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::FINDNEXT => {
-            predefined_functions::findnext(evaluate_exp(interpreter, &params[0])?)
+            let spec = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::findfirst(interpreter, spec)?
         }
+        FuncOpCode::FINDNEXT => predefined_functions::findnext(interpreter),
         FuncOpCode::USELMRS => {
-            predefined_functions::uselmrs(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::uselmrs(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::CONFINFO => {
-            predefined_functions::confinfo(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::confinfo(evaluate_exp(interpreter, &params[0])?)?
+        }
+        FuncOpCode::TINKEY => {
+            let ticks = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::tinkey(interpreter, ticks)?
         }
-        FuncOpCode::TINKEY => predefined_functions::tinkey(interpreter)?,
-        FuncOpCode::CWD => predefined_functions::cwd(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::INSTRR => predefined_functions::instrr(evaluate_exp(interpreter, &params[0])?),
+        FuncOpCode::CWD => predefined_functions::cwd(interpreter),
+        FuncOpCode::INSTRR => predefined_functions::instrr(evaluate_exp(interpreter, &params[0])?)?,
         FuncOpCode::FDORDAKA => {
-            predefined_functions::fdordaka(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::fdordaka(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::FDORDORG => {
-            predefined_functions::fdordorg(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::fdordorg(evaluate_exp(interpreter, &params[0])?)?
         }
         FuncOpCode::FDORDAREA => {
-            predefined_functions::fdordarea(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::FDOQRD => predefined_functions::fdoqrd(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::GETDRIVE => {
-            predefined_functions::getdrive(evaluate_exp(interpreter, &params[0])?)
+            predefined_functions::fdordarea(evaluate_exp(interpreter, &params[0])?)?
         }
+        FuncOpCode::FDOQRD => predefined_functions::fdoqrd(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::GETDRIVE => predefined_functions::getdrive(interpreter),
         FuncOpCode::SETDRIVE => {
-            predefined_functions::setdrive(evaluate_exp(interpreter, &params[0])?)
-        }
-        FuncOpCode::BS2I => predefined_functions::bs2i(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::BD2I => predefined_functions::bd2i(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::I2BS => predefined_functions::i2bs(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::I2BD => predefined_functions::i2bd(evaluate_exp(interpreter, &params[0])?),
-        FuncOpCode::FTELL => predefined_functions::ftell(evaluate_exp(interpreter, &params[0])?),
-        _ => panic!("Unsupported function {:?}", func_def),
+            let drive = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::setdrive(interpreter, drive)?
+        }
+        FuncOpCode::BS2I => predefined_functions::bs2i(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::BD2I => predefined_functions::bd2i(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::I2BS => predefined_functions::i2bs(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::I2BD => predefined_functions::i2bd(evaluate_exp(interpreter, &params[0])?)?,
+        FuncOpCode::FTELL => {
+            let channel = evaluate_exp(interpreter, &params[0])?;
+            predefined_functions::ftell(interpreter, channel)?
+        }
+        _ => {
+            return Err(Box::new(IcyError::UnsupportedFunction(format!(
+                "{:?}",
+                func_def
+            ))))
+        }
     })
 }
 
@@ -1,11 +1,15 @@
 #![allow(clippy::needless_pass_by_value)]
-use std::fs::File;
-use std::path::Path;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
 
 use super::super::errors::IcyError;
-use super::get_int;
-use crate::{get_string, Interpreter, Res};
-use easy_reader::EasyReader;
+use super::{get_date, get_i64, get_int, get_real, get_time, round_money, with_int_type};
+use crate::dbase;
+use crate::input::{cc_type, format_cc, is_valid_cc, join_tokens};
+use crate::keys::read_key;
+use crate::pcb_date::{format_time_ap, now_seconds, parse_time, PcbDate};
+use crate::{from_cp437, get_string, is_true, match_wildcard, split_file_name, Interpreter, Res};
+use chrono::{DateTime, Datelike, Local, Timelike};
 use ppl_engine::ast::{convert_to, VariableType, VariableValue};
 use radix_fmt::radix;
 use rand::Rng; // 0.8.5
@@ -177,8 +181,8 @@ pub fn instr(str: VariableValue, sub: VariableValue) -> VariableValue {
 }
 
 /// Returns a flag indicating if the user has aborted the display of information.
-pub fn abort(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO ABORT") // TODO
+pub fn abort(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ABORT")))
 }
 
 /// Trim specified characters from the beginning of a string
@@ -218,11 +222,13 @@ pub fn replace(str: VariableValue, old: VariableValue, new: VariableValue) -> Va
     }
 
     let mut res = String::new();
-    let old = old.chars().next().unwrap();
-    let new = new.chars().next().unwrap();
+    let old = old.chars().next().unwrap_or_default();
+    let new = new.chars().next();
     for c in str.chars() {
         if c == old {
-            res.push(new);
+            if let Some(new) = new {
+                res.push(new);
+            }
         } else {
             res.push(c);
         }
@@ -241,8 +247,10 @@ pub fn strip(str: VariableValue, ch: VariableValue) -> VariableValue {
     let VariableValue::String(ch) = ch else {
         return VariableValue::String(String::new());
     };
+    let Some(ch) = ch.chars().next() else {
+        return VariableValue::String(str);
+    };
     let mut res = String::new();
-    let ch = ch.chars().next().unwrap();
     for c in str.chars() {
         if c != ch {
             res.push(c);
@@ -311,12 +319,12 @@ pub fn replace_string(
     _str: VariableValue,
     _old: VariableValue,
     _new: VariableValue,
-) -> VariableValue {
-    panic!("TODO")
+) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("REPLACE_STRING")))
 }
 
-pub fn strip_string(_str: VariableValue, _strip: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn strip_string(_str: VariableValue, _strip: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("STRIP_STRING")))
 }
 
 /// Trim specified characters from the end of a string
@@ -363,12 +371,14 @@ pub fn random(upper: VariableValue) -> Res<VariableValue> {
     Ok(VariableValue::Integer(rng.gen_range(0..upper)))
 }
 
+/// Returns the current date
 pub fn date() -> VariableValue {
-    panic!("TODO")
+    VariableValue::Date(PcbDate::today().to_julian() as u16)
 }
 
+/// Returns the current time
 pub fn time() -> VariableValue {
-    panic!("TODO")
+    VariableValue::Time(now_seconds())
 }
 
 pub fn u_name(interpreter: &Interpreter) -> VariableValue {
@@ -379,151 +389,141 @@ pub fn u_name(interpreter: &Interpreter) -> VariableValue {
     )
 }
 
+/// Returns the date the user was last on
 pub fn u_ldate(interpreter: &Interpreter) -> VariableValue {
-    // interpreter.pcb_data.users[interpreter.cur_user].last_date_on
-    // TODO
-    VariableValue::Date(0)
+    VariableValue::Date(interpreter.icb_data.users[interpreter.cur_user].last_date_on)
 }
 
+/// Returns the time the user was last on
 pub fn u_ltime(interpreter: &Interpreter) -> VariableValue {
-    // TODO
-    VariableValue::Time(0)
+    let last_time_on = &interpreter.icb_data.users[interpreter.cur_user].last_time_on;
+    VariableValue::Time(parse_time(last_time_on).unwrap_or(0))
 }
 
-pub fn u_ldir(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_ldir(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_LDIR")))
 }
-pub fn u_lmr(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_lmr(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_LMR")))
 }
-pub fn u_logons(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_logons(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_LOGONS")))
 }
-pub fn u_ful(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_ful(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_FUL")))
 }
-pub fn u_fdl(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_fdl(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_FDL")))
 }
-pub fn u_bdlday(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_bdlday(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_BDLDAY")))
 }
-pub fn u_timeon(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_timeon(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_TIMEON")))
 }
-pub fn u_bdl(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_bdl(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_BDL")))
 }
-pub fn u_bul(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_bul(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_BUL")))
 }
-pub fn u_msgrd(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_msgrd(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_MSGRD")))
 }
-pub fn u_msgwr(interpreter: &Interpreter) -> VariableValue {
-    panic!("TODO") // TODO
+pub fn u_msgwr(interpreter: &Interpreter) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_MSGWR")))
 }
 
-pub fn year(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the year of a date (1900-2079)
+pub fn year(interpreter: &Interpreter, date: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(get_date(interpreter, &date)?.year))
 }
-pub fn month(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the month of a date (1-12)
+pub fn month(interpreter: &Interpreter, date: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(
+        get_date(interpreter, &date)?.month as i32,
+    ))
 }
-pub fn day(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the day of the month of a date (1-31)
+pub fn day(interpreter: &Interpreter, date: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(
+        get_date(interpreter, &date)?.day as i32,
+    ))
 }
-pub fn dow(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the day of the week of a date (0 = Sunday - 6 = Saturday)
+pub fn dow(interpreter: &Interpreter, date: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(
+        get_date(interpreter, &date)?.day_of_week(),
+    ))
 }
-pub fn hour(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the hour of a time (0-23)
+pub fn hour(time: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(get_time(&time)? / 3600))
 }
-pub fn min(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the minute of a time (0-59)
+pub fn min(time: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(get_time(&time)? / 60 % 60))
 }
-pub fn sec(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the seconds of a time (0-59)
+pub fn sec(time: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(get_time(&time)? % 60))
 }
-pub fn timeap(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns a time in civilian format ("hh:mm:ss AM")
+pub fn timeap(time: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::String(format_time_ap(get_time(&time)?)))
 }
-pub fn ver(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn ver(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("VER")))
 }
-pub fn nochar(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn nochar(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("NOCHAR")))
 }
-pub fn yeschar(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn yeschar(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("YESCHAR")))
 }
 
+/// Returns a key if one is available, doesn't wait
+/// # Returns
+///  `VariableValue::String` - the key or "" if no key was pressed
+/// # Remarks
+/// Special keys are returned as "UP", "DOWN", "LEFT", "RIGHT", "HOME", "END", "PGUP", "PGDN", "INS", "DEL" and "F1"-"F12"
 pub fn inkey(interpreter: &mut Interpreter) -> Res<VariableValue> {
-    if let Some(ch) = interpreter.ctx.get_char()? {
-        if ch as u8 == 127 {
-            return Ok(VariableValue::String("DEL".to_string()));
-        }
-        if ch == '\x1B' {
-            if let Some(ch) = interpreter.ctx.get_char()? {
-                if ch == '[' {
-                    if let Some(ch) = interpreter.ctx.get_char()? {
-                        match ch {
-                            'A' => return Ok(VariableValue::String("UP".to_string())),
-                            'B' => return Ok(VariableValue::String("DOWN".to_string())),
-                            'C' => return Ok(VariableValue::String("RIGHT".to_string())),
-                            'D' => return Ok(VariableValue::String("LEFT".to_string())),
-
-                            'H' => return Ok(VariableValue::String("HOME".to_string())),
-                            'K' => return Ok(VariableValue::String("END".to_string())),
-
-                            'V' => return Ok(VariableValue::String("PGUP".to_string())),
-                            'U' => return Ok(VariableValue::String("PGDN".to_string())),
-
-                            '@' => return Ok(VariableValue::String("INS".to_string())),
-
-                            _ => return Ok(VariableValue::String(ch.to_string())),
-                        }
-                    }
-                }
-            }
-            return Ok(VariableValue::String("\x1B".to_string()));
-        }
-        Ok(VariableValue::String(ch.to_string()))
-    } else {
-        Ok(VariableValue::String(String::new()))
-    }
+    Ok(VariableValue::String(read_key(
+        interpreter,
+        Some(Duration::ZERO),
+    )?))
 }
 
 pub fn tostring(x: VariableValue) -> VariableValue {
     VariableValue::String(x.to_string())
 }
 
-pub fn mask_pwd(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_pwd(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_PWD")))
 }
-pub fn mask_alpha(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_alpha(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_ALPHA")))
 }
-pub fn mask_num(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_num(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_NUM")))
 }
-pub fn mask_alnum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_alnum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_ALNUM")))
 }
-pub fn mask_file(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_file(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_FILE")))
 }
-pub fn mask_path(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_path(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_PATH")))
 }
-pub fn mask_ascii(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mask_ascii(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MASK_ASCII")))
 }
-pub fn curconf(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn curconf(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CURCONF")))
 }
-pub fn pcbdat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn pcbdat(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("PCBDAT")))
 }
 pub fn ppepath(interpreter: &Interpreter) -> VariableValue {
     let Some(dir) = interpreter.prg.file_name.parent() else {
@@ -534,11 +534,24 @@ pub fn ppepath(interpreter: &Interpreter) -> VariableValue {
     VariableValue::String(res)
 }
 
-pub fn valdate(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Checks if a string is a valid date in the board's date format
+/// # Arguments
+///  * `str` - A date string
+/// # Returns
+///  `VariableValue::Boolean` - true if `str` is a valid date
+pub fn valdate(interpreter: &Interpreter, str: VariableValue) -> VariableValue {
+    VariableValue::Boolean(
+        PcbDate::parse(&str.to_string(), interpreter.icb_data.date_format).is_some(),
+    )
 }
-pub fn valtime(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Checks if a string is a valid time ("hh:mm" or "hh:mm:ss")
+/// # Arguments
+///  * `str` - A time string
+/// # Returns
+///  `VariableValue::Boolean` - true if `str` is a valid time
+pub fn valtime(str: VariableValue) -> VariableValue {
+    VariableValue::Boolean(parse_time(&str.to_string()).is_some())
 }
 pub fn pcbnode(interpreter: &Interpreter) -> VariableValue {
     VariableValue::Integer(interpreter.icb_data.pcb_data.node_number as i32)
@@ -556,14 +569,13 @@ pub fn readline(
         return Err(Box::new(IcyError::ParameterIntegerExpected(1)));
     };
 
-    let file = File::open(file_name)?;
-    let mut reader = EasyReader::new(file)?;
-    for _ in 1..line {
-        reader.next_line()?;
-    }
-    Ok(VariableValue::String(
-        reader.next_line()?.unwrap_or_default(),
-    ))
+    let content = interpreter.io.read_file(&file_name)?;
+    let line = from_cp437(&content)
+        .lines()
+        .nth((line.max(1) - 1) as usize)
+        .unwrap_or_default()
+        .to_string();
+    Ok(VariableValue::String(line))
 }
 
 pub fn sysopsec(interpreter: &Interpreter) -> VariableValue {
@@ -603,8 +615,8 @@ pub fn un_oper(interpreter: &Interpreter) -> VariableValue {
         VariableValue::String(String::new())
     }
 }
-pub fn cursec(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn cursec(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CURSEC")))
 }
 
 pub fn gettoken(interpreter: &mut Interpreter) -> VariableValue {
@@ -614,87 +626,123 @@ pub fn gettoken(interpreter: &mut Interpreter) -> VariableValue {
         VariableValue::String(String::new())
     }
 }
-pub fn minleft(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn minon(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn getenv(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn callid(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regal(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regah(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regbl(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regbh(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regcl(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regch(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regdl(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regdh(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regax(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regbx(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regcx(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regdx(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regsi(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regdi(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regf(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regcf(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn regds(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn reges(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn b2w(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn peekb(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn peekw(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn mkaddr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn exist(x: VariableValue) -> VariableValue {
+pub fn minleft(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MINLEFT")))
+}
+pub fn minon(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MINON")))
+}
+pub fn getenv(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("GETENV")))
+}
+pub fn callid(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CALLID")))
+}
+/// Returns the low byte of AX after DOINTR
+pub fn regal(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte(registers.ax as u8)
+}
+/// Returns the high byte of AX after DOINTR
+pub fn regah(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte((registers.ax >> 8) as u8)
+}
+/// Returns the low byte of BX after DOINTR
+pub fn regbl(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte(registers.bx as u8)
+}
+/// Returns the high byte of BX after DOINTR
+pub fn regbh(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte((registers.bx >> 8) as u8)
+}
+/// Returns the low byte of CX after DOINTR
+pub fn regcl(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte(registers.cx as u8)
+}
+/// Returns the high byte of CX after DOINTR
+pub fn regch(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte((registers.cx >> 8) as u8)
+}
+/// Returns the low byte of DX after DOINTR
+pub fn regdl(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte(registers.dx as u8)
+}
+/// Returns the high byte of DX after DOINTR
+pub fn regdh(interpreter: &Interpreter) -> VariableValue {
+    let registers = &interpreter.memory.registers;
+    VariableValue::Byte((registers.dx >> 8) as u8)
+}
+/// Returns the AX register after DOINTR
+pub fn regax(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.ax)
+}
+/// Returns the BX register after DOINTR
+pub fn regbx(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.bx)
+}
+/// Returns the CX register after DOINTR
+pub fn regcx(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.cx)
+}
+/// Returns the DX register after DOINTR
+pub fn regdx(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.dx)
+}
+/// Returns the SI register after DOINTR
+pub fn regsi(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.si)
+}
+/// Returns the DI register after DOINTR
+pub fn regdi(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.di)
+}
+/// Returns the flags register after DOINTR
+pub fn regf(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.flags)
+}
+/// Returns TRUE if the carry flag is set after DOINTR, it signals an error
+pub fn regcf(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Boolean(interpreter.memory.registers.carry())
+}
+/// Returns the DS register after DOINTR
+pub fn regds(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.ds)
+}
+/// Returns the ES register after DOINTR
+pub fn reges(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Word(interpreter.memory.registers.es)
+}
+/// Combines a low and a high byte to a word
+pub fn b2w(lo: VariableValue, hi: VariableValue) -> Res<VariableValue> {
+    let (lo, hi) = (get_i64(&lo)? as u8, get_i64(&hi)? as u8);
+    Ok(VariableValue::Word(u16::from_le_bytes([lo, hi])))
+}
+/// Reads a byte of the emulated DOS memory at `address` (segment:offset)
+pub fn peekb(interpreter: &mut Interpreter, address: VariableValue) -> Res<VariableValue> {
+    let bytes = interpreter.peek(get_i64(&address)?, 1);
+    Ok(VariableValue::Byte(bytes[0]))
+}
+/// Reads a word of the emulated DOS memory at `address` (segment:offset)
+pub fn peekw(interpreter: &mut Interpreter, address: VariableValue) -> Res<VariableValue> {
+    let bytes = interpreter.peek(get_i64(&address)?, 2);
+    Ok(VariableValue::Word(u16::from_le_bytes([
+        bytes[0], bytes[1],
+    ])))
+}
+/// Combines a segment and an offset to an address for PEEK/POKE
+pub fn mkaddr(segment: VariableValue, offset: VariableValue) -> Res<VariableValue> {
+    let address = (get_i64(&segment)? as u16 as u32) << 16 | get_i64(&offset)? as u16 as u32;
+    Ok(VariableValue::Integer(address as i32))
+}
+pub fn exist(interpreter: &Interpreter, x: VariableValue) -> VariableValue {
     if let VariableValue::String(file) = x {
-        VariableValue::Boolean(Path::new(&file).exists())
+        VariableValue::Boolean(interpreter.io.file_exists(&file))
     } else {
         VariableValue::Boolean(false)
     }
@@ -721,29 +769,50 @@ pub fn s2i(src: &str, base: i32) -> Res<VariableValue> {
     let i = i32::from_str_radix(src, base as u32)?;
     Ok(VariableValue::Integer(i))
 }
-pub fn carrier(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn carrier(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CARRIER")))
 }
-pub fn tokenstr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the remaining tokens as a stacked command line and clears the token stack
+/// # Returns
+///  `VariableValue::String` - the tokens separated by semicolons
+pub fn tokenstr(interpreter: &mut Interpreter) -> VariableValue {
+    let tokens = std::mem::take(&mut interpreter.cur_tokens);
+    VariableValue::String(join_tokens(&tokens))
 }
-pub fn cdon(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn cdon(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CDON")))
 }
-pub fn langext(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn langext(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("LANGEXT")))
 }
-pub fn ansion(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn ansion(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ANSION")))
 }
-pub fn valcc(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Checks a credit card number (Luhn check digit and length)
+/// # Arguments
+///  * `str` - A credit card number, spaces and dashes are ignored
+/// # Returns
+///  `VariableValue::Boolean` - true if `str` is a valid credit card number
+pub fn valcc(str: VariableValue) -> VariableValue {
+    VariableValue::Boolean(is_valid_cc(&str.to_string()))
 }
-pub fn fmtcc(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Formats a credit card number for display
+/// # Arguments
+///  * `str` - A credit card number
+/// # Returns
+///  `VariableValue::String` - the number in groups of 4 digits (American Express 4-6-5)
+pub fn fmtcc(str: VariableValue) -> VariableValue {
+    VariableValue::String(format_cc(&str.to_string()))
 }
-pub fn cctype(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Returns the issuer of a credit card number
+/// # Arguments
+///  * `str` - A credit card number
+/// # Returns
+///  `VariableValue::String` - "VISA", "MASTERCARD", "AMERICAN EXPRESS", "DISCOVER", "DINERS CLUB", "JCB", "ENROUTE" or "UNKNOWN"
+pub fn cctype(str: VariableValue) -> VariableValue {
+    VariableValue::String(cc_type(&str.to_string()).to_string())
 }
 
 pub fn getx(interpreter: &mut Interpreter) -> VariableValue {
@@ -757,32 +826,36 @@ pub fn gety(interpreter: &mut Interpreter) -> VariableValue {
     VariableValue::Integer(y + 1)
 }
 
-pub fn band(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Bitwise and, the result has the type of the first argument
+pub fn band(x: VariableValue, y: VariableValue) -> Res<VariableValue> {
+    Ok(with_int_type(&x, get_i64(&x)? & get_i64(&y)?))
 }
-pub fn bor(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Bitwise or, the result has the type of the first argument
+pub fn bor(x: VariableValue, y: VariableValue) -> Res<VariableValue> {
+    Ok(with_int_type(&x, get_i64(&x)? | get_i64(&y)?))
 }
-pub fn bxor(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Bitwise exclusive or, the result has the type of the first argument
+pub fn bxor(x: VariableValue, y: VariableValue) -> Res<VariableValue> {
+    Ok(with_int_type(&x, get_i64(&x)? ^ get_i64(&y)?))
 }
-pub fn bnot(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Bitwise not, only the bits of the argument's type are flipped: BNOT(BYTE 0) = 255
+pub fn bnot(x: VariableValue) -> Res<VariableValue> {
+    Ok(with_int_type(&x, !get_i64(&x)?))
 }
-pub fn u_pwdhist(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn u_pwdhist(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_PWDHIST")))
 }
-pub fn u_pwdlc(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn u_pwdlc(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_PWDLC")))
 }
-pub fn u_pwdtc(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn u_pwdtc(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_PWDTC")))
 }
-pub fn u_stat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn u_stat(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_STAT")))
 }
-pub fn defcolor(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn defcolor(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("DEFCOLOR")))
 }
 pub fn abs(x: VariableValue) -> Res<VariableValue> {
     match x {
@@ -807,16 +880,63 @@ pub fn abs(x: VariableValue) -> Res<VariableValue> {
     }
 }
 
-pub fn grafmode(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn grafmode(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("GRAFMODE")))
 }
 
-pub fn psa(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn psa(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("PSA")))
 }
 
-pub fn fileinf(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns information about a file
+/// # Arguments
+///  * `file` - the file name
+///  * `option` - 1 = exists, 2 = date, 3 = time, 4 = size, 5 = attributes, 6 = drive ("C:"),
+///    7 = path ("\PCB\"), 8 = base name, 9 = extension (".TXT")
+/// # Remarks
+/// Drive, path, base name & extension are taken from `file`, the file doesn't need to exist.
+pub fn fileinf(
+    interpreter: &Interpreter,
+    file: VariableValue,
+    option: VariableValue,
+) -> Res<VariableValue> {
+    let file = get_string(&file);
+    let io = &interpreter.io;
+    let modified = || -> Option<DateTime<Local>> {
+        let date: SystemTime = io.get_file_date(&file).ok()?;
+        Some(date.into())
+    };
+    let (dir, name) = split_file_name(&file);
+    let (drive, path) = match dir.find(':') {
+        Some(i) => dir.split_at(i + 1),
+        None => ("", dir),
+    };
+    let (base, ext) = match name.rfind('.') {
+        Some(i) => name.split_at(i),
+        None => (name, ""),
+    };
+    Ok(match get_int(&option)? {
+        1 => VariableValue::Boolean(io.file_exists(&file)),
+        2 => {
+            let julian = modified().map_or(0, |date| {
+                PcbDate::new(date.year(), date.month() as u8, date.day() as u8).to_julian()
+            });
+            VariableValue::Date(julian as u16)
+        }
+        3 => VariableValue::Time(
+            modified().map_or(0, |date| date.num_seconds_from_midnight() as i32),
+        ),
+        4 => VariableValue::Integer(io.get_file_size(&file) as i32),
+        5 => VariableValue::Integer(io.get_file_attributes(&file).unwrap_or(0) as i32),
+        6 => VariableValue::String(drive.to_string()),
+        7 => VariableValue::String(path.to_string()),
+        8 => VariableValue::String(base.to_string()),
+        9 => VariableValue::String(ext.to_string()),
+        option => {
+            log::warn!("FILEINF: invalid option {}", option);
+            VariableValue::Integer(0)
+        }
+    })
 }
 
 pub fn ppename(interpreter: &Interpreter) -> VariableValue {
@@ -828,11 +948,22 @@ pub fn ppename(interpreter: &Interpreter) -> VariableValue {
     VariableValue::String(res)
 }
 
-pub fn mkdate(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Creates a date from year, month and day
+/// # Returns
+///  `VariableValue::Date` - 0 if the date isn't valid
+pub fn mkdate(year: VariableValue, month: VariableValue, day: VariableValue) -> Res<VariableValue> {
+    let (year, month, day) = (get_int(&year)?, get_int(&month)?, get_int(&day)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Ok(VariableValue::Date(0));
+    }
+    let date = PcbDate::new(year, month as u8, day as u8);
+    match u16::try_from(date.to_julian()) {
+        Ok(julian) if date.is_valid() => Ok(VariableValue::Date(julian)),
+        _ => Ok(VariableValue::Date(0)),
+    }
 }
-pub fn curcolor(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn curcolor(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CURCOLOR")))
 }
 pub fn kinkey(interpreter: &mut Interpreter) -> Res<VariableValue> {
     inkey(interpreter)
@@ -843,26 +974,26 @@ pub fn minkey(interpreter: &mut Interpreter) -> Res<VariableValue> {
 pub fn maxnode(interpreter: &mut Interpreter) -> VariableValue {
     VariableValue::Integer(interpreter.icb_data.nodes.len() as i32)
 }
-pub fn slpath(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn slpath(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("SLPATH")))
 }
-pub fn helppath(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn helppath(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("HELPPATH")))
 }
-pub fn temppath(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn temppath(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("TEMPPATH")))
 }
-pub fn modem(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn modem(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MODEM")))
 }
-pub fn loggedon(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn loggedon(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("LOGGEDON")))
 }
-pub fn callnum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn callnum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CALLNUM")))
 }
-pub fn mgetbyte(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mgetbyte(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MGETBYTE")))
 }
 pub fn tokcount(interpreter: &mut Interpreter) -> VariableValue {
     VariableValue::Integer(interpreter.cur_tokens.len() as i32)
@@ -883,389 +1014,815 @@ pub fn u_recnum(interpreter: &mut Interpreter, user_name: VariableValue) -> Res<
     Ok(VariableValue::Integer(record_num))
 }
 
-pub fn u_inconf(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn u_inconf(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("U_INCONF")))
 }
-pub fn peekdw(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Reads a double word of the emulated DOS memory at `address` (segment:offset)
+pub fn peekdw(interpreter: &mut Interpreter, address: VariableValue) -> Res<VariableValue> {
+    let bytes = interpreter.peek(get_i64(&address)?, 4);
+    Ok(VariableValue::Integer(i32::from_le_bytes([
+        bytes[0], bytes[1], bytes[2], bytes[3],
+    ])))
 }
-pub fn dbglevel(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the debug level set with DBGLEVEL.
+pub fn dbglevel(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.debug_level)
 }
-pub fn scrtext(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn scrtext(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("SCRTEXT")))
 }
-pub fn showstat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn showstat(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("SHOWSTAT")))
 }
-pub fn pagestat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn pagestat(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("PAGESTAT")))
 }
-pub fn tobigstr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a BIGSTR
+pub fn tobigstr(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::String(get_string(&x)))
 }
-pub fn toboolean(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a BOOLEAN, every value != 0 is TRUE
+pub fn toboolean(x: VariableValue) -> Res<VariableValue> {
+    match x {
+        VariableValue::Boolean(b) => Ok(VariableValue::Boolean(b)),
+        _ => Ok(VariableValue::Boolean(get_real(&x)? != 0.0)),
+    }
 }
-pub fn tobyte(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a BYTE (0 - 255), larger values wrap around
+pub fn tobyte(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Byte(get_i64(&x)? as u8))
 }
-pub fn todate(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a DATE, strings are parsed in the board's date format
+pub fn todate(interpreter: &Interpreter, x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Date(
+        get_date(interpreter, &x)?.to_julian() as u16
+    ))
 }
-pub fn todreal(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a DREAL
+pub fn todreal(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Real(get_real(&x)?))
 }
-pub fn toedate(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to an EDATE, strings are parsed in the board's date format
+pub fn toedate(interpreter: &Interpreter, x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::EDate(
+        get_date(interpreter, &x)?.to_julian() as u16
+    ))
 }
-pub fn tointeger(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to an INTEGER, larger values wrap around
+pub fn tointeger(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(get_i64(&x)? as i32))
 }
-pub fn tomoney(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to MONEY, rounded to cents
+pub fn tomoney(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Money(round_money(get_real(&x)?)))
 }
-pub fn toreal(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a REAL
+pub fn toreal(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Real(get_real(&x)?))
 }
-pub fn tosbyte(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a SBYTE (-128 - 127), larger values wrap around
+pub fn tosbyte(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::SByte(get_i64(&x)? as i8))
 }
-pub fn tosword(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a SWORD (-32768 - 32767), larger values wrap around
+pub fn tosword(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::SWord(get_i64(&x)? as i16))
 }
-pub fn totime(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a TIME, strings are parsed as "hh:mm[:ss]"
+pub fn totime(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Time(get_time(&x)?))
 }
-pub fn tounsigned(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to an UNSIGNED (0 - 4294967295), larger values wrap around
+pub fn tounsigned(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Unsigned(get_i64(&x)? as u32))
 }
-pub fn toword(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a value to a WORD (0 - 65535), larger values wrap around
+pub fn toword(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Word(get_i64(&x)? as u16))
 }
-pub fn mixed(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn mixed(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MIXED")))
 }
-pub fn alias(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn alias(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ALIAS")))
 }
-pub fn confreg(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confreg(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFREG")))
 }
-pub fn confexp(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confexp(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFEXP")))
 }
-pub fn confsel(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confsel(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFSEL")))
 }
-pub fn confsys(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confsys(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFSYS")))
 }
-pub fn confmw(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confmw(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFMW")))
 }
-pub fn lprinted(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn lprinted(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("LPRINTED")))
 }
-pub fn isnonstop(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn isnonstop(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ISNONSTOP")))
 }
-pub fn errcorrect(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn errcorrect(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ERRCORRECT")))
 }
-pub fn confalias(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confalias(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFALIAS")))
 }
-pub fn useralias(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn useralias(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("USERALIAS")))
 }
-pub fn curuser(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn curuser(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CURUSER")))
 }
-pub fn chatstat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn chatstat(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CHATSTAT")))
 }
-pub fn defans(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn defans(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("DEFANS")))
 }
-pub fn lastans(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn lastans(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("LASTANS")))
 }
-pub fn meganum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn meganum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("MEGANUM")))
 }
-pub fn evttimeadj(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn evttimeadj(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("EVTTIMEADJ")))
 }
-pub fn isbitset(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Checks if bit `bit` (0 = least significant) of `x` is set
+pub fn isbitset(x: VariableValue, bit: VariableValue) -> Res<VariableValue> {
+    let bit = get_int(&bit)?;
+    if !(0..64).contains(&bit) {
+        return Ok(VariableValue::Boolean(false));
+    }
+    Ok(VariableValue::Boolean(get_i64(&x)? & (1 << bit) != 0))
 }
-pub fn fmtreal(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Formats a number right aligned in `width` columns with `decimals` decimal places
+/// # Remarks
+/// Numbers that don't fit are not truncated
+pub fn fmtreal(
+    value: VariableValue,
+    width: VariableValue,
+    decimals: VariableValue,
+) -> Res<VariableValue> {
+    let value = get_real(&value)?;
+    let width = get_int(&width)?.max(0) as usize;
+    let decimals = get_int(&decimals)?.max(0) as usize;
+    Ok(VariableValue::String(format!(
+        "{:>width$.decimals$}",
+        value,
+        width = width,
+        decimals = decimals
+    )))
 }
-pub fn flagcnt(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn flagcnt(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("FLAGCNT")))
 }
-pub fn kbdbufsize(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the number of keys in the keyboard buffer (KBDSTRING)
+pub fn kbdbufsize(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.keyboard.keyboard.len() as i32)
 }
-pub fn pplbufsize(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Returns the number of keys in the PPL keyboard buffer (KBDSTUFF)
+pub fn pplbufsize(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.keyboard.ppl.len() as i32)
 }
-pub fn kbdfilused(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Returns true if keys stuffed by KBDFILE are still pending
+pub fn kbdfilused(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Boolean(!interpreter.keyboard.file.is_empty())
 }
-pub fn lomsgnum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn lomsgnum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("LOMSGNUM")))
 }
-pub fn himsgnum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn himsgnum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("HIMSGNUM")))
 }
 
-pub fn drivespace(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the free bytes on a drive ("C:"), values above 2 GB are reported as 2 GB like on DOS
+pub fn drivespace(interpreter: &Interpreter, drive: VariableValue) -> Res<VariableValue> {
+    let space = match interpreter.io.drive_space(&get_string(&drive)) {
+        Ok(space) => space.min(i32::MAX as u64) as i32,
+        Err(err) => {
+            log::warn!("DRIVESPACE: {}", err);
+            -1
+        }
+    };
+    Ok(VariableValue::Integer(space))
 }
 pub fn outbytes() -> VariableValue {
     VariableValue::Integer(0)
 }
-pub fn hiconfnum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn hiconfnum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("HICONFNUM")))
 }
 
 pub fn inbytes(interpreter: &mut Interpreter) -> VariableValue {
     VariableValue::Integer(interpreter.ctx.inbytes())
 }
 
-pub fn crc32(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn crc32(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CRC32")))
 }
-pub fn pcbmac(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn pcbmac(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("PCBMAC")))
 }
-pub fn actmsgnum(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn actmsgnum(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ACTMSGNUM")))
 }
-pub fn stackleft(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the free bytes of the (emulated) PPL stack.
+pub fn stackleft(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.stack_left() as i32)
 }
-pub fn stackerr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns TRUE if a GOSUB or procedure/function call exceeded the stack.
+pub fn stackerr(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Boolean(interpreter.state.stack_error)
 }
 
-pub fn dgetalias(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dbof(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dchanged(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn ddecimals(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn ddeleted(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn deof(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn derr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dfields(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dlength(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dname(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dreccount(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the alias of a data file, "" if the channel isn't open
+pub fn dgetalias(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let alias = interpreter
+        .dbase
+        .file(channel)
+        .map_or(String::new(), |file| file.alias.clone());
+    Ok(VariableValue::String(alias))
 }
-pub fn drecno(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// TRUE if DSKIP moved before the first record
+pub fn dbof(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter.dbase.file(channel).is_ok_and(|file| file.bof);
+    Ok(VariableValue::Boolean(res))
 }
-pub fn dtype(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// TRUE if the current record has changes that aren't written yet
+pub fn dchanged(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter
+        .dbase
+        .file(channel)
+        .is_ok_and(|file| file.changed);
+    Ok(VariableValue::Boolean(res))
 }
-pub fn fnext(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dnext(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn toddate(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dcloseall(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dopen(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
-}
-pub fn dclose(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the number of decimals of a field
+pub fn ddecimals(
+    interpreter: &Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter
+        .dbase
+        .file(channel)
+        .and_then(|file| file.field(&get_string(&name)))
+        .map_or(0, |field| field.decimals as i32);
+    Ok(VariableValue::Integer(res))
+}
+/// TRUE if the current record is marked as deleted
+pub fn ddeleted(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter
+        .dbase
+        .file(channel)
+        .is_ok_and(|file| file.deleted());
+    Ok(VariableValue::Boolean(res))
+}
+/// TRUE if the record pointer is behind the last record
+pub fn deof(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter.dbase.file(channel).is_ok_and(|file| file.eof);
+    Ok(VariableValue::Boolean(res))
+}
+/// TRUE if the last operation on a dBase channel failed
+pub fn derr(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Boolean(
+        interpreter.dbase.status(channel) < 0,
+    ))
 }
-pub fn dsetalias(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the number of fields of a data file
+pub fn dfields(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let fields = interpreter
+        .dbase
+        .file(channel)
+        .map_or(0, |file| file.fields.len());
+    Ok(VariableValue::Integer(fields as i32))
+}
+/// Returns the length of a field
+pub fn dlength(
+    interpreter: &Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter
+        .dbase
+        .file(channel)
+        .and_then(|file| file.field(&get_string(&name)))
+        .map_or(0, |field| field.len as i32);
+    Ok(VariableValue::Integer(res))
+}
+/// Returns the name of field `number` (1 based), "" if there is no such field
+pub fn dname(
+    interpreter: &Interpreter,
+    channel: VariableValue,
+    number: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let number = get_int(&number)?;
+    let name = interpreter
+        .dbase
+        .file(channel)
+        .ok()
+        .and_then(|file| file.fields.get(usize::try_from(number - 1).ok()?))
+        .map_or(String::new(), |field| field.name.clone());
+    Ok(VariableValue::String(name))
+}
+/// Returns the number of records including the deleted ones
+pub fn dreccount(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let count = interpreter
+        .dbase
+        .file_mut(channel)
+        .and_then(|file| file.record_count())
+        .unwrap_or(0);
+    Ok(VariableValue::Integer(count as i32))
+}
+/// Returns the current record number, the record count + 1 at EOF
+pub fn drecno(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let recno = interpreter.dbase.file(channel).map_or(0, |file| file.recno);
+    Ok(VariableValue::Integer(recno as i32))
+}
+/// Returns the type of a field: "C", "N", "D" or "L"
+pub fn dtype(
+    interpreter: &Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let res = interpreter
+        .dbase
+        .file(channel)
+        .and_then(|file| file.field(&get_string(&name)))
+        .map_or(String::new(), |field| field.field_type.to_string());
+    Ok(VariableValue::String(res))
 }
-pub fn dpack(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn fnext(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("FNEXT")))
 }
-pub fn dlockf(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the first unused dBase channel, -1 if all are used
+pub fn dnext(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.dbase.next_free())
 }
-pub fn dlock(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn toddate(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("TODDATE")))
 }
-pub fn dlockr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dcloseall(interpreter: &mut Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.dbase.close_all())
 }
-pub fn dunlock(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Opens a data file, returns the status: 0 on success
+pub fn dopen(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+    exclusive: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let exclusive = is_true(&exclusive)?;
+    let status =
+        interpreter
+            .dbase
+            .open(&mut *interpreter.io, channel, &get_string(&name), exclusive);
+    Ok(VariableValue::Integer(status))
+}
+pub fn dclose(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.close(channel)))
+}
+pub fn dsetalias(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    alias: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let status = interpreter.dbase.set_alias(channel, &get_string(&alias));
+    Ok(VariableValue::Integer(status))
+}
+pub fn dpack(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.pack(channel)))
+}
+/// Locks a data file, returns the status: 0 on success
+pub fn dlockf(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.lock_file(channel)))
+}
+/// Locks the current record, returns the status: 0 on success
+pub fn dlock(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.lock(channel)))
+}
+pub fn dlockr(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    recno: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.lock_records(channel, &[get_int(&recno)?]),
+    ))
 }
-pub fn dnopen(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dunlock(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.unlock(channel)))
 }
-pub fn dnclose(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Opens an index file, returns the status: 0 on success
+pub fn dnopen(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let status = interpreter
+        .dbase
+        .open_index(&mut *interpreter.io, channel, &get_string(&name));
+    Ok(VariableValue::Integer(status))
+}
+pub fn dnclose(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let status = interpreter.dbase.close_index(channel, &get_string(&name));
+    Ok(VariableValue::Integer(status))
+}
+pub fn dncloseall(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.close_indexes(channel),
+    ))
 }
-pub fn dncloseall(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dnew(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.new_record(channel),
+    ))
 }
-pub fn dnew(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dadd(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.add(channel)))
 }
-pub fn dadd(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dappend(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.append_blank(channel),
+    ))
 }
-pub fn dappend(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dtop(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.top(channel)))
 }
-pub fn dtop(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dgo(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    recno: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.go(channel, get_int(&recno)?),
+    ))
 }
-pub fn dgo(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dbottom(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.bottom(channel)))
 }
-pub fn dbottom(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Skips records, returns 0, 3 (EOF), 4 (BOF) or an error status
+pub fn dskip(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    count: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.skip(channel, get_int(&count)?),
+    ))
 }
-pub fn dskip(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn dblank(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.blank(channel)))
 }
-pub fn dblank(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn ddelete(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.set_deleted(channel, true),
+    ))
 }
-pub fn ddelete(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn drecall(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.set_deleted(channel, false),
+    ))
 }
-pub fn drecall(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// The selected tag of a channel, "" if the records are in record order
+pub fn dtag(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let tag = interpreter
+        .dbase
+        .file(channel)
+        .map_or(String::new(), |file| file.tag_name().to_string());
+    Ok(VariableValue::String(tag))
+}
+/// Searches a key in the selected tag, returns 0 on error, 1 if found,
+/// 2 if positioned on the following record & 3 at the end of file
+pub fn dseek(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    value: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let date_format = interpreter.icb_data.date_format;
+    let status = interpreter.dbase.seek(channel, &value, date_format);
+    Ok(VariableValue::Integer(match status {
+        dbase::R4_SUCCESS => 1,
+        dbase::R4_AFTER => 2,
+        dbase::R4_EOF => 3,
+        _ => 0,
+    }))
+}
+pub fn dfblank(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(
+        interpreter.dbase.blank_field(channel, &get_string(&name)),
+    ))
 }
-pub fn dtag(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns a field of the current record: C fields are STRINGs, N fields INTEGERs or REALs,
+/// D fields DATEs & L fields BOOLEANs. "" if the field doesn't exist.
+pub fn dget(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(interpreter
+        .dbase
+        .get(channel, &get_string(&name))
+        .unwrap_or(VariableValue::String(String::new())))
+}
+pub fn dput(
+    interpreter: &mut Interpreter,
+    channel: VariableValue,
+    name: VariableValue,
+    value: VariableValue,
+) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let date_format = interpreter.icb_data.date_format;
+    let status = interpreter
+        .dbase
+        .put(channel, &get_string(&name), &value, date_format);
+    Ok(VariableValue::Integer(status))
+}
+/// Copies a field to a field of another channel, returns the status of the target channel
+pub fn dfcopy(
+    interpreter: &mut Interpreter,
+    from: VariableValue,
+    from_name: VariableValue,
+    to: VariableValue,
+    to_name: VariableValue,
+) -> Res<VariableValue> {
+    let from = get_channel(&from)?;
+    let to = get_channel(&to)?;
+    let status = match interpreter.dbase.get(from, &get_string(&from_name)) {
+        Some(value) => {
+            let date_format = interpreter.icb_data.date_format;
+            interpreter
+                .dbase
+                .put(to, &get_string(&to_name), &value, date_format)
+        }
+        None => interpreter.dbase.status(from),
+    };
+    Ok(VariableValue::Integer(status))
 }
-pub fn dseek(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the channel of the data file with an alias, -1 if there is none
+pub fn dselect(interpreter: &Interpreter, alias: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Integer(
+        interpreter.dbase.select(&get_string(&alias)),
+    ))
 }
-pub fn dfblank(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the status of the last operation on a dBase channel, 0 if it succeeded
+pub fn dchkstat(interpreter: &Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    Ok(VariableValue::Integer(interpreter.dbase.status(channel)))
 }
-pub fn dget(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+pub fn pcbaccount(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("PCBACCOUNT")))
 }
-pub fn dput(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn pcbaccstat(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("PCBACCSTAT")))
 }
-pub fn dfcopy(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the description of a dBase status code
+pub fn derrmsg(code: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::String(
+        dbase::error_message(get_int(&code)?).to_string(),
+    ))
 }
-pub fn dselect(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn account(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("ACCOUNT")))
 }
-pub fn dchkstat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn scanmsghdr(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("SCANMSGHDR")))
 }
-
-pub fn pcbaccount(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn checkrip(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CHECKRIP")))
 }
-pub fn pcbaccstat(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn ripver(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("RIPVER")))
 }
-pub fn derrmsg(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn qwklimits(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("QWKLIMITS")))
 }
-pub fn account(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Starts a directory search & returns the first file matching `spec` ("C:\PCB\*.PPE")
+/// # Returns
+/// The file name without path, "" if no file matches
+pub fn findfirst(interpreter: &mut Interpreter, spec: VariableValue) -> Res<VariableValue> {
+    let spec = get_string(&spec);
+    let (dir, pattern) = split_file_name(&spec);
+    let pattern = if pattern.is_empty() { "*.*" } else { pattern };
+    interpreter.file_search = match interpreter.io.list_files(dir) {
+        Ok(files) => files
+            .into_iter()
+            .filter(|file| match_wildcard(pattern, file))
+            .collect(),
+        Err(err) => {
+            log::warn!("FINDFIRST {}: {}", spec, err);
+            VecDeque::new()
+        }
+    };
+    Ok(findnext(interpreter))
 }
-pub fn scanmsghdr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the next file of the search started with FINDFIRST, "" if there are no more files
+pub fn findnext(interpreter: &mut Interpreter) -> VariableValue {
+    VariableValue::String(interpreter.file_search.pop_front().unwrap_or_default())
 }
-pub fn checkrip(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn uselmrs(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("USELMRS")))
 }
-pub fn ripver(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn confinfo(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("CONFINFO")))
 }
-pub fn qwklimits(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Waits for a key
+/// # Arguments
+///  * `ticks` - maximum wait time in clock ticks (~1/18.2s), 0 waits forever
+/// # Returns
+///  `VariableValue::String` - the key or "" if no key was pressed in time
+pub fn tinkey(interpreter: &mut Interpreter, ticks: VariableValue) -> Res<VariableValue> {
+    let ticks = get_int(&ticks)?;
+    let timeout = if ticks > 0 {
+        Some(Duration::from_millis((ticks as f32 * 1000.0 / 18.2) as u64))
+    } else {
+        None
+    };
+    Ok(VariableValue::String(read_key(interpreter, timeout)?))
 }
-pub fn findfirst(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the current drive & directory ("C:\PCB")
+pub fn cwd(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::String(interpreter.io.cwd())
 }
-pub fn findnext(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn instrr(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("INSTRR")))
 }
-pub fn uselmrs(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn fdordaka(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("FDORDAKA")))
 }
-pub fn confinfo(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn fdordorg(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("FDORDORG")))
 }
-pub fn tinkey(interpreter: &mut Interpreter) -> Res<VariableValue> {
-    inkey(interpreter)
+pub fn fdordarea(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("FDORDAREA")))
 }
-pub fn cwd(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+pub fn fdoqrd(_x: VariableValue) -> Res<VariableValue> {
+    Err(Box::new(IcyError::FunctionNotImplemented("FDOQRD")))
 }
-pub fn instrr(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the current drive, 0 = A:, 1 = B:, 2 = C: ...
+pub fn getdrive(interpreter: &Interpreter) -> VariableValue {
+    VariableValue::Integer(interpreter.io.get_drive() as i32)
 }
-pub fn fdordaka(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Changes the current drive (0 = A:, 1 = B:, 2 = C: ...)
+/// # Returns
+/// 0 on success, -1 if the drive doesn't exist
+pub fn setdrive(interpreter: &mut Interpreter, drive: VariableValue) -> Res<VariableValue> {
+    let ok = usize::try_from(get_int(&drive)?).is_ok_and(|drive| interpreter.io.set_drive(drive));
+    Ok(VariableValue::Integer(if ok { 0 } else { -1 }))
+}
+/// Converts a BASIC (Microsoft binary format) single precision real to IEEE.
+/// The argument holds the 4 raw bytes of the BASIC real, like read from a binary record.
+pub fn bs2i(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Real(
+        mbf_single_to_ieee(raw_single(&x)?) as f64
+    ))
 }
-pub fn fdordorg(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts a BASIC (Microsoft binary format) double precision real to IEEE.
+/// The argument holds the 8 raw bytes of the BASIC real, like read from a binary record.
+pub fn bd2i(x: VariableValue) -> Res<VariableValue> {
+    Ok(VariableValue::Real(mbf_double_to_ieee(raw_double(&x)?)))
 }
-pub fn fdordarea(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts an IEEE real to BASIC single precision, the result holds the 4 raw bytes
+pub fn i2bs(x: VariableValue) -> Res<VariableValue> {
+    let bits = ieee_to_mbf_single(get_real(&x)? as f32);
+    Ok(VariableValue::Real(f32::from_bits(bits) as f64))
 }
-pub fn fdoqrd(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Converts an IEEE real to BASIC double precision, the result holds the 8 raw bytes
+pub fn i2bd(x: VariableValue) -> Res<VariableValue> {
+    let bits = ieee_to_mbf_double(get_real(&x)?);
+    Ok(VariableValue::Real(f64::from_bits(bits)))
 }
-pub fn getdrive(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Raw bits of a 4 byte BASIC real, integer values are taken as the bit pattern.
+fn raw_single(x: &VariableValue) -> Res<u32> {
+    match x {
+        VariableValue::Money(f) | VariableValue::Real(f) => Ok((*f as f32).to_bits()),
+        _ => Ok(get_i64(x)? as u32),
+    }
 }
-pub fn setdrive(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// Raw bits of an 8 byte BASIC real, integer values are taken as the bit pattern.
+fn raw_double(x: &VariableValue) -> Res<u64> {
+    match x {
+        VariableValue::Money(f) | VariableValue::Real(f) => Ok(f.to_bits()),
+        _ => Ok(get_i64(x)? as u64),
+    }
 }
-pub fn bs2i(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+// MBF layout: exponent byte (bias 129, 0 = zero), sign bit, mantissa with implicit leading 1.
+fn mbf_single_to_ieee(mbf: u32) -> f32 {
+    let exponent = mbf >> 24;
+    if exponent <= 2 {
+        return 0.0;
+    }
+    let sign = (mbf >> 23) & 1;
+    let mantissa = mbf & 0x7F_FFFF;
+    f32::from_bits(sign << 31 | (exponent - 2) << 23 | mantissa)
 }
-pub fn bd2i(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+fn ieee_to_mbf_single(value: f32) -> u32 {
+    let bits = value.to_bits();
+    let exponent = (bits >> 23) & 0xFF;
+    if exponent == 0 {
+        return 0;
+    }
+    let sign = bits >> 31;
+    let exponent = exponent + 2;
+    if exponent > 0xFF {
+        // too large for MBF, saturate
+        return 0xFF << 24 | sign << 23 | 0x7F_FFFF;
+    }
+    exponent << 24 | sign << 23 | (bits & 0x7F_FFFF)
 }
-pub fn i2bs(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+fn mbf_double_to_ieee(mbf: u64) -> f64 {
+    let exponent = mbf >> 56;
+    if exponent == 0 {
+        return 0.0;
+    }
+    let sign = (mbf >> 55) & 1;
+    // 55 bit mantissa, IEEE has 52
+    let mantissa = (mbf & 0x7F_FFFF_FFFF_FFFF) >> 3;
+    f64::from_bits(sign << 63 | (exponent + 1022 - 128) << 52 | mantissa)
+}
+
+fn ieee_to_mbf_double(value: f64) -> u64 {
+    let bits = value.to_bits();
+    let exponent = (bits >> 52) & 0x7FF;
+    // MBF has an 8 bit exponent, everything out of range is 0
+    if !(1022 - 128 + 1..=1022 + 127).contains(&exponent) {
+        return 0;
+    }
+    let sign = bits >> 63;
+    (exponent + 128 - 1022) << 56 | sign << 55 | (bits & 0xF_FFFF_FFFF_FFFF) << 3
 }
-pub fn i2bd(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+/// Returns the current position of the file pointer of a channel, 0 if the channel isn't open
+pub fn ftell(interpreter: &mut Interpreter, channel: VariableValue) -> Res<VariableValue> {
+    let channel = get_channel(&channel)?;
+    let pos = interpreter.io.ftell(channel);
+    Ok(VariableValue::Integer(pos.max(0) as i32))
 }
-pub fn ftell(_x: VariableValue) -> VariableValue {
-    panic!("TODO")
+
+/// A file or dBase channel number, valid channels are 0-7.
+fn get_channel(channel: &VariableValue) -> Res<usize> {
+    let channel = get_int(channel)?;
+    if !(0..=7).contains(&channel) {
+        return Err(Box::new(IcyError::FileChannelOutOfBounds(channel)));
+    }
+    Ok(channel as usize)
 }
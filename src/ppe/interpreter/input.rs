@@ -0,0 +1,247 @@
+use crate::{Interpreter, Res};
+
+use super::constants::*;
+use super::keys::get_char_timeout;
+
+/// Splits a command line into tokens the way PCBoard does for command stacking.
+/// Tokens are separated by spaces or semicolons, empty tokens are skipped and
+/// double quotes group a token that contains separators.
+pub fn tokenize(str: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut cur = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+    for ch in str.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            ' ' | ';' if !in_quotes => {
+                if !cur.is_empty() || quoted {
+                    res.push(std::mem::take(&mut cur));
+                }
+                quoted = false;
+            }
+            _ => cur.push(ch),
+        }
+    }
+    if !cur.is_empty() || quoted {
+        res.push(cur);
+    }
+    res
+}
+
+/// Reassembles tokens to a stacked command line ("J;3;R;N").
+pub fn join_tokens(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|t| {
+            if t.is_empty() || t.contains([' ', ';']) {
+                format!("\"{t}\"")
+            } else {
+                t.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+/// Simple line editor for the INPUT* statements.
+/// # Arguments
+///  * `prompt` - printed before the field
+///  * `color` - color of the input field
+///  * `len` - maximum length of the input
+///  * `valid` - characters that are allowed, an empty string allows everything
+///  * `flags` - INPUTSTR flags (`ECHODOTS`, `FIELDLEN`, `UPCASE`, `NEWLINE`, `LFBEFORE`, `LFAFTER`, …)
+/// # Returns
+///  The entered string, no line ending included
+/// # Remarks
+/// If commands are stacked the next token is used instead of asking the user.
+/// With `STACKED` the input may contain stacked commands, the first one is returned
+/// and the rest is pushed on the token stack.
+pub fn input_field(
+    interpreter: &mut Interpreter,
+    prompt: &str,
+    color: u8,
+    len: usize,
+    valid: &str,
+    flags: i32,
+) -> Res<String> {
+    if flags & LFBEFORE != 0 {
+        interpreter.ctx.print("\n")?;
+    }
+    interpreter.ctx.print(prompt)?;
+    interpreter.ctx.set_color(color);
+
+    if !interpreter.cur_tokens.is_empty() {
+        let token: String = interpreter.cur_tokens.remove(0).chars().take(len).collect();
+        let token = if flags & UPCASE != 0 {
+            token.to_uppercase()
+        } else {
+            token
+        };
+        if flags & ECHODOTS != 0 {
+            interpreter.ctx.print(&".".repeat(token.len()))?;
+        } else {
+            interpreter.ctx.print(&token)?;
+        }
+        finish_field(interpreter, flags)?;
+        return Ok(token);
+    }
+
+    if flags & FIELDLEN != 0 {
+        interpreter.ctx.print("(")?;
+        interpreter.ctx.print(&" ".repeat(len))?;
+        interpreter.ctx.print(")")?;
+        interpreter.ctx.print(&"\x08".repeat(len + 1))?;
+    }
+
+    let mut output = String::new();
+    loop {
+        let Some(ch) = get_char_timeout(interpreter, None)? else {
+            continue;
+        };
+        match ch {
+            '\r' | '\n' => break,
+            '\x08' | '\x7F' => {
+                if output.pop().is_some() {
+                    interpreter.ctx.print("\x08 \x08")?;
+                }
+            }
+            _ => {
+                let ch = if flags & UPCASE != 0 {
+                    ch.to_ascii_uppercase()
+                } else {
+                    ch
+                };
+                if ch < ' ' || (ch as u32 > 127 && flags & HIGHASCII == 0) {
+                    continue;
+                }
+                if output.len() >= len || (!valid.is_empty() && !valid.contains(ch)) {
+                    if flags & BELL != 0 {
+                        interpreter.ctx.print("\x07")?;
+                    }
+                    continue;
+                }
+                output.push(ch);
+                if flags & ECHODOTS != 0 {
+                    interpreter.ctx.print(".")?;
+                } else {
+                    interpreter.ctx.print(&ch.to_string())?;
+                }
+            }
+        }
+    }
+
+    finish_field(interpreter, flags)?;
+
+    if flags & STACKED != 0 && output.contains([' ', ';']) {
+        let mut tokens = tokenize(&output);
+        if !tokens.is_empty() {
+            let first = tokens.remove(0);
+            interpreter.cur_tokens = tokens;
+            return Ok(first);
+        }
+    }
+    Ok(output)
+}
+
+fn finish_field(interpreter: &mut Interpreter, flags: i32) -> Res<()> {
+    if flags & NEWLINE != 0 {
+        interpreter.ctx.print("\n")?;
+    }
+    if flags & LFAFTER != 0 {
+        interpreter.ctx.print("\n")?;
+    }
+    Ok(())
+}
+
+/// Strips spaces and dashes from a credit card number.
+fn cc_digits(number: &str) -> String {
+    number.chars().filter(|c| !matches!(c, ' ' | '-')).collect()
+}
+
+/// Checks a credit card number with the Luhn algorithm.
+pub fn is_valid_cc(number: &str) -> bool {
+    let digits = cc_digits(number);
+    if digits.len() < 13 || digits.len() > 19 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .enumerate()
+        .map(|(i, c)| {
+            let d = c.to_digit(10).unwrap();
+            if i % 2 == 1 {
+                let d = d * 2;
+                if d > 9 {
+                    d - 9
+                } else {
+                    d
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Returns the issuer of a credit card number ("UNKNOWN" if it can't be detected).
+pub fn cc_type(number: &str) -> &'static str {
+    let digits = cc_digits(number);
+    if !is_valid_cc(&digits) {
+        return "UNKNOWN";
+    }
+    let prefix = |len: usize| digits[..len].parse::<u32>().unwrap_or(0);
+    match (prefix(2), digits.len()) {
+        (34 | 37, 15) => "AMERICAN EXPRESS",
+        (51..=55, 16) => "MASTERCARD",
+        (36 | 38, 14) => "DINERS CLUB",
+        (30, 14) => {
+            if (300..=305).contains(&prefix(3)) {
+                "DINERS CLUB"
+            } else {
+                "UNKNOWN"
+            }
+        }
+        (35, 16) => "JCB",
+        _ => {
+            if digits.starts_with('4') && (digits.len() == 13 || digits.len() == 16) {
+                "VISA"
+            } else if prefix(4) == 6011 && digits.len() == 16 {
+                "DISCOVER"
+            } else if (prefix(4) == 2014 || prefix(4) == 2149) && digits.len() == 15 {
+                "ENROUTE"
+            } else {
+                "UNKNOWN"
+            }
+        }
+    }
+}
+
+/// Formats a credit card number in the usual groups (4-4-4-4, American Express 4-6-5).
+pub fn format_cc(number: &str) -> String {
+    let digits = cc_digits(number);
+    let groups: &[usize] = match digits.len() {
+        15 => &[4, 6, 5],
+        14 => &[4, 6, 4],
+        _ => &[4, 4, 4, 4, 4],
+    };
+    let mut res = String::new();
+    let mut rest = digits.as_str();
+    for len in groups {
+        if rest.is_empty() {
+            break;
+        }
+        let (group, tail) = rest.split_at((*len).min(rest.len()));
+        if !res.is_empty() {
+            res.push(' ');
+        }
+        res.push_str(group);
+        rest = tail;
+    }
+    res
+}
@@ -1,16 +1,49 @@
 use std::{
+    cell::{Ref, RefCell, RefMut},
     collections::HashMap,
     fs::{self, File, OpenOptions},
-    io::{BufRead, BufReader, Result, Seek, SeekFrom, Write},
-    os::unix::prelude::MetadataExt,
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write},
+    os::unix::{fs::FileExt, prelude::MetadataExt},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
     time::SystemTime,
 };
 
-use substring::Substring;
+use super::share::{access, lock_range, share_conflict, unlock_range, ShareLock};
+use crate::dos_path::resolve_case;
 
-const O_RD: i32 = 0;
-const O_RW: i32 = 2;
-const O_WR: i32 = 1;
+pub const ATTR_READONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+
+pub const O_RD: i32 = 0;
+pub const O_WR: i32 = 1;
+pub const O_RW: i32 = 2;
+
+/// share modes: deny none, deny read, deny write, deny both
+pub const S_DN: i32 = 0;
+pub const S_DR: i32 = 1;
+pub const S_DW: i32 = 2;
+pub const S_DB: i32 = 3;
+
+pub const SEEK_SET: i32 = 0;
+pub const SEEK_CUR: i32 = 1;
+pub const SEEK_END: i32 = 2;
+
+/// Bytes of a string in a file. Strings hold one CP437 character per char, like CHR() creates
+/// them & the terminal prints them, so every channel writes one byte per char.
+/// Chars beyond 255 can't be stored & become '?'.
+pub fn to_cp437(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// String of CP437 bytes read from a file, one char per byte.
+pub fn from_cp437(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
 
 pub trait PCBoardIO {
     /// Open a file for append access
@@ -80,31 +113,89 @@ pub trait PCBoardIO {
 
     fn fclose(&mut self, channel: usize);
 
+    /// Closes all open channels
+    fn fclose_all(&mut self);
+
+    /// Moves the file position, `origin` is `SEEK_SET`, `SEEK_CUR` or `SEEK_END`
+    fn fseek(&mut self, channel: usize, offset: i64, origin: i32);
+
+    /// Current file position, -1 if the channel isn't open
+    fn ftell(&mut self, channel: usize) -> i64;
+
+    /// Reads up to `len` bytes, a short read at the end of the file sets the error flag
+    fn fread(&mut self, channel: usize, len: usize) -> Vec<u8>;
+
+    /// Writes `data` at the current file position
+    fn fwrite(&mut self, channel: usize, data: &[u8]);
+
+    fn fflush(&mut self, channel: usize);
+
     fn file_exists(&self, file: &str) -> bool;
 
+    /// Reads a whole file, used by DISPFILE, READLINE & KBDFILE
+    fn read_file(&self, file: &str) -> std::io::Result<Vec<u8>>;
+
     fn delete(&mut self, file: &str) -> std::io::Result<()>;
     fn rename(&mut self, old: &str, new: &str) -> std::io::Result<()>;
     fn copy(&mut self, from: &str, to: &str) -> std::io::Result<()>;
 
-    /// .
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// // Example template not implemented for trait functions
-    /// ```
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if .
+    /// Last modification of a file
     fn get_file_date(&self, file: &str) -> Result<SystemTime>;
     fn get_file_size(&self, file: &str) -> u64;
+
+    /// DOS attributes of a file, see the `ATTR_*` constants
+    fn get_file_attributes(&self, file: &str) -> Result<u8>;
+
+    /// Names of the files (not directories) in `dir`, sorted.
+    fn list_files(&self, dir: &str) -> Result<Vec<String>>;
+
+    /// Current drive, 0 = A:, 1 = B:, 2 = C: ...
+    fn get_drive(&self) -> usize;
+
+    /// Changes the current drive, returns false if the drive doesn't exist.
+    fn set_drive(&mut self, drive: usize) -> bool;
+
+    /// Current directory of the current drive in DOS format ("C:\PCB")
+    fn cwd(&self) -> String;
+
+    /// Changes the current directory, a drive letter changes the directory of that drive.
+    fn chdir(&mut self, dir: &str) -> std::io::Result<()>;
+
+    fn mkdir(&mut self, dir: &str) -> std::io::Result<()>;
+
+    /// Free bytes on `drive` ("C:"), an empty string is the current drive.
+    fn drive_space(&self, drive: &str) -> std::io::Result<u64>;
+
+    /// Opens a file for random access, used by the dBase functions.
+    /// `create` creates or truncates the file, `exclusive` denies other opens (S_DB).
+    fn open_data(&mut self, file: &str, create: bool, exclusive: bool)
+        -> Result<Box<dyn DataFile>>;
+}
+
+/// A file opened with `PCBoardIO::open_data`.
+pub trait DataFile {
+    /// Reads up to `len` bytes at `pos`, less at the end of the file.
+    fn read_at(&mut self, pos: u64, len: usize) -> Result<Vec<u8>>;
+
+    fn write_at(&mut self, pos: u64, data: &[u8]) -> Result<()>;
+
+    fn size(&self) -> Result<u64>;
+
+    fn set_size(&mut self, size: u64) -> Result<()>;
+
+    /// Locks `len` bytes at `pos`, returns false if another handle has a lock in that range.
+    /// Locks of one handle don't conflict with each other.
+    fn lock(&mut self, pos: u64, len: u64) -> Result<bool>;
+
+    fn unlock(&mut self, pos: u64, len: u64) -> Result<()>;
 }
 
+/// An open file. Reads are buffered, the buffer is dropped before writes & seeks so text & binary
+/// reads and writes can be mixed on one handle.
 struct FileChannel {
-    file: Option<Box<File>>,
-    reader: Option<BufReader<File>>,
-    _content: Vec<u8>,
+    file: Option<BufReader<File>>,
+    /// access & share mode, released on close
+    _share: Option<ShareLock>,
     err: bool,
 }
 
@@ -112,23 +203,79 @@ impl FileChannel {
     fn new() -> Self {
         FileChannel {
             file: None,
-            reader: None,
-            _content: Vec::new(),
+            _share: None,
             err: false,
         }
     }
+
+    fn open(file: Result<(File, ShareLock)>) -> Self {
+        match file {
+            Ok((handle, share)) => FileChannel {
+                file: Some(BufReader::new(handle)),
+                _share: Some(share),
+                err: false,
+            },
+            Err(err) => {
+                log::trace!("can't open file: {}", err);
+                FileChannel {
+                    file: None,
+                    _share: None,
+                    err: true,
+                }
+            }
+        }
+    }
+
+    /// The file positioned at the current read position.
+    fn writer(&mut self) -> Result<&mut File> {
+        let Some(reader) = &mut self.file else {
+            return Err(Error::new(ErrorKind::NotFound, "channel not open"));
+        };
+        // seeking discards the read buffer
+        let pos = reader.stream_position()?;
+        reader.seek(SeekFrom::Start(pos))?;
+        Ok(reader.get_mut())
+    }
+
+    /// Runs `op` on the open file & sets the error flag accordingly.
+    fn with_file<T: Default>(&mut self, op: impl FnOnce(&mut BufReader<File>) -> Result<T>) -> T {
+        let result = match &mut self.file {
+            Some(reader) => op(reader),
+            None => Err(Error::new(ErrorKind::NotFound, "channel not open")),
+        };
+        match result {
+            Ok(value) => {
+                self.err = false;
+                value
+            }
+            Err(err) => {
+                log::trace!("file error: {}", err);
+                self.err = true;
+                T::default()
+            }
+        }
+    }
 }
 
+/// File access of PPEs on the disk.
+/// DOS drive letters are mapped to host directories, PPEs may not leave the drives.
 pub struct DiskIO {
-    path: String, // use that as root
+    /// host directory of each drive letter
+    drives: HashMap<char, PathBuf>,
+    /// current directory of each drive, relative to the drive root
+    cur_dirs: HashMap<char, Vec<String>>,
+    cur_drive: char,
     channels: [FileChannel; 8],
 }
 
 impl DiskIO {
+    /// `path` is the board directory, it's drive C: and the current drive.
     #[must_use]
     pub fn new(path: &str) -> Self {
-        DiskIO {
-            path: path.to_string(),
+        let mut io = DiskIO {
+            drives: HashMap::new(),
+            cur_dirs: HashMap::new(),
+            cur_drive: 'C',
             channels: [
                 FileChannel::new(),
                 FileChannel::new(),
@@ -139,126 +286,194 @@ impl DiskIO {
                 FileChannel::new(),
                 FileChannel::new(),
             ],
+        };
+        io.map_drive('C', path);
+        io
+    }
+
+    /// Maps drive `letter` to the host directory `dir`.
+    pub fn map_drive(&mut self, letter: char, dir: &str) {
+        let letter = letter.to_ascii_uppercase();
+        self.drives
+            .insert(letter, canonical_path(&resolve_case(Path::new(dir))));
+        self.cur_dirs.insert(letter, Vec::new());
+    }
+
+    /// Splits a DOS path in the drive & the directory components relative to the drive root.
+    /// Returns `None` if `..` leaves the drive.
+    fn parse_dos_path(&self, file: &str) -> Option<(char, Vec<String>)> {
+        let name = file.replace('\\', "/");
+        let (drive, path) = match name.as_bytes() {
+            [drive, b':', ..] if drive.is_ascii_alphabetic() => {
+                (drive.to_ascii_uppercase() as char, &name[2..])
+            }
+            _ => (self.cur_drive, name.as_str()),
+        };
+        let mut components = if path.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cur_dirs.get(&drive).cloned().unwrap_or_default()
+        };
+        for component in path.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop()?;
+                }
+                _ => components.push(component.to_string()),
+            }
         }
+        Some((drive, components))
     }
 
-    fn resolve_file_name(&self, file: &str) -> String {
-        let s: String = file
-            .chars()
-            .map(|x| match x {
-                '\\' => '/',
-                _ => x,
-            })
-            .collect();
+    /// Maps a PPE file name to a path on disk.
+    /// Accesses outside the drives are blocked & logged.
+    fn resolve_file_name(&self, file: &str) -> Result<PathBuf> {
+        let Some((drive, components)) = self.parse_dos_path(file) else {
+            return Err(blocked(file, file));
+        };
+        let Some(root) = self.drives.get(&drive) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("invalid drive {}:", drive),
+            ));
+        };
+        let path = components.iter().fold(root.clone(), |p, c| p.join(c));
+        let path = canonical_path(&resolve_case(&path));
+        if self.drives.values().any(|root| path.starts_with(root)) {
+            Ok(path)
+        } else {
+            Err(blocked(file, &path.display().to_string()))
+        }
+    }
 
-        if file.starts_with("C:\\") {
-            let mut result = self.path.to_string();
-            result.push('/');
-            result.push_str(s.substring(2, file.len() - 2));
-            return result;
+    /// Opens a file with DOS access & share modes. The share modes are checked first, so a denied
+    /// FCREATE/FAPPEND doesn't create or truncate the file.
+    fn open_file(
+        &self,
+        file: &str,
+        options: &OpenOptions,
+        truncate: bool,
+        am: i32,
+        sm: i32,
+    ) -> Result<(File, ShareLock)> {
+        let path = self.resolve_file_name(file)?;
+        log::trace!("open file: {} am {am} sm {sm}", path.display());
+        if path.is_dir() {
+            return Err(Error::other(format!("{} is a directory", file)));
+        }
+        // a file that doesn't exist yet has no handles to conflict with
+        let share = if path.exists() {
+            Some(ShareLock::acquire(&path, am, sm)?)
+        } else {
+            None
+        };
+        let handle = options.open(&path)?;
+        let share = match share {
+            Some(share) => share,
+            None => ShareLock::acquire(&path, am, sm)?,
+        };
+        if truncate {
+            handle.set_len(0)?;
         }
-        s
+        Ok((handle, share))
     }
 }
 
-impl PCBoardIO for DiskIO {
-    fn fappend(&mut self, channel: usize, file: &str, _am: i32, _sm: i32) {
-        let file = OpenOptions::new()
-            .append(true)
-            .open(self.resolve_file_name(file));
-        match file {
-            Ok(handle) => {
-                self.channels[channel] = FileChannel {
-                    file: Some(Box::new(handle)),
-                    reader: None,
-                    _content: Vec::new(),
-                    err: false,
-                };
-            }
-            _ => {
-                self.channels[channel] = FileChannel {
-                    file: None,
-                    reader: None,
-                    _content: Vec::new(),
-                    err: true,
-                };
-            }
-        }
+fn blocked(file: &str, path: &str) -> Error {
+    log::warn!("PPE access to {} ({}) blocked", file, path);
+    Error::new(
+        ErrorKind::PermissionDenied,
+        format!("access to {} denied", file),
+    )
+}
+
+/// Free bytes on the file system of `path`.
+fn free_space(path: &Path) -> Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: path is a valid C string & stat a properly sized buffer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::last_os_error());
     }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
 
-    fn fcreate(&mut self, channel: usize, file: &str, _am: i32, _sm: i32) {
-        let file = File::create(self.resolve_file_name(file));
-        match file {
-            Ok(handle) => {
-                self.channels[channel] = FileChannel {
-                    file: Some(Box::new(handle)),
-                    reader: None,
-                    _content: Vec::new(),
-                    err: false,
-                };
+/// Removes `.` & `..` and resolves symbolic links of the part of the path that exists.
+fn canonical_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
             }
-            _ => {
-                self.channels[channel] = FileChannel {
-                    file: None,
-                    reader: None,
-                    _content: Vec::new(),
-                    err: true,
-                };
+            _ => normalized.push(component),
+        }
+    }
+    let mut existing = normalized.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest.iter().rev().fold(canonical, |path, c| path.join(c));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
             }
+            _ => return normalized,
         }
     }
+}
+
+impl PCBoardIO for DiskIO {
+    fn fappend(&mut self, channel: usize, file: &str, am: i32, sm: i32) {
+        let mut options = OpenOptions::new();
+        options.read(am != O_WR).write(true).create(true);
+        let file = self
+            .open_file(file, &options, false, am, sm)
+            .and_then(|(mut handle, share)| {
+                handle.seek(SeekFrom::End(0))?;
+                Ok((handle, share))
+            });
+        self.channels[channel] = FileChannel::open(file);
+    }
+
+    fn fcreate(&mut self, channel: usize, file: &str, am: i32, sm: i32) {
+        let mut options = OpenOptions::new();
+        options.read(am != O_WR).write(true).create(true);
+        let file = self.open_file(file, &options, true, am, sm);
+        self.channels[channel] = FileChannel::open(file);
+    }
 
     fn delete(&mut self, file: &str) -> std::io::Result<()> {
-        let file = self.resolve_file_name(file);
+        let file = self.resolve_file_name(file)?;
         fs::remove_file(file)
     }
 
     fn rename(&mut self, old: &str, new: &str) -> std::io::Result<()> {
-        let old = self.resolve_file_name(old);
-        let new = self.resolve_file_name(new);
+        let old = self.resolve_file_name(old)?;
+        let new = self.resolve_file_name(new)?;
         fs::rename(old, new)
     }
     fn copy(&mut self, from: &str, to: &str) -> std::io::Result<()> {
-        let old = self.resolve_file_name(from);
-        let new = self.resolve_file_name(to);
+        let old = self.resolve_file_name(from)?;
+        let new = self.resolve_file_name(to)?;
         fs::copy(old, new)?;
         Ok(())
     }
 
-    fn fopen(&mut self, channel: usize, file: &str, mode: i32, _sm: i32) {
-        println!(
-            "fopen file: {} -- {} on channel {channel}",
-            file,
-            self.resolve_file_name(file)
-        );
-
-        let file = match mode {
-            O_RD => File::open(self.resolve_file_name(file)),
-            O_WR => File::create(self.resolve_file_name(file)),
-            O_RW => OpenOptions::new()
-                .read(true)
-                .write(true)
-                .open(self.resolve_file_name(file)),
-            _ => File::open(self.resolve_file_name(file)),
+    fn fopen(&mut self, channel: usize, file: &str, am: i32, sm: i32) {
+        let mut options = OpenOptions::new();
+        match am {
+            O_WR => options.write(true).create(true),
+            O_RW => options.read(true).write(true),
+            _ => options.read(true),
         };
-        match file {
-            Ok(handle) => {
-                self.channels[channel] = FileChannel {
-                    file: Some(Box::new(handle)),
-                    reader: None,
-                    _content: Vec::new(),
-                    err: false,
-                };
-            }
-            Err(err) => {
-                self.channels[channel] = FileChannel {
-                    file: None,
-                    reader: None,
-                    _content: Vec::new(),
-                    err: true,
-                };
-            }
-        }
+        let file = self.open_file(file, &options, am == O_WR, am, sm);
+        self.channels[channel] = FileChannel::open(file);
     }
 
     fn ferr(&self, channel: usize) -> bool {
@@ -266,122 +481,515 @@ impl PCBoardIO for DiskIO {
     }
 
     fn fput(&mut self, channel: usize, text: String) {
-        match &mut self.channels[channel].file {
-            Some(f) => {
-                let _ = f.write(text.as_bytes());
-                self.channels[channel].err = false;
+        self.fwrite(channel, &to_cp437(&text));
+    }
+
+    fn fget(&mut self, channel: usize) -> String {
+        self.channels[channel].with_file(|reader| {
+            let mut line = Vec::new();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "end of file"));
             }
-            _ => {
-                log::error!("channel {} not found", channel);
+            Ok(from_cp437(&line).trim_end_matches(['\r', '\n']).to_string())
+        })
+    }
+
+    fn frewind(&mut self, channel: usize) {
+        self.fseek(channel, 0, SEEK_SET);
+    }
+
+    fn fseek(&mut self, channel: usize, offset: i64, origin: i32) {
+        let pos = match origin {
+            SEEK_CUR => SeekFrom::Current(offset),
+            SEEK_END => SeekFrom::End(offset),
+            _ if offset < 0 => {
                 self.channels[channel].err = true;
+                return;
             }
+            _ => SeekFrom::Start(offset as u64),
+        };
+        self.channels[channel].with_file(|reader| reader.seek(pos));
+    }
+
+    fn ftell(&mut self, channel: usize) -> i64 {
+        let channel = &mut self.channels[channel];
+        if channel.file.is_none() {
+            channel.err = true;
+            return -1;
         }
+        channel.with_file(|reader| reader.stream_position()) as i64
     }
 
-    fn fget(&mut self, channel: usize) -> String {
-        if let Some(f) = self.channels[channel].file.take() {
-            self.channels[channel].reader = Some(BufReader::new(*f));
+    fn fread(&mut self, channel: usize, len: usize) -> Vec<u8> {
+        let channel = &mut self.channels[channel];
+        let mut data = Vec::new();
+        let result = match &mut channel.file {
+            Some(reader) => reader.take(len as u64).read_to_end(&mut data),
+            None => Err(Error::new(ErrorKind::NotFound, "channel not open")),
+        };
+        channel.err = result.is_err() || data.len() < len;
+        data
+    }
+
+    fn fwrite(&mut self, channel: usize, data: &[u8]) {
+        let channel = &mut self.channels[channel];
+        let result = channel.writer().and_then(|file| file.write_all(data));
+        if let Err(err) = &result {
+            log::error!("can't write file: {}", err);
         }
-        if let Some(reader) = &mut self.channels[channel].reader {
-            let mut line = String::new();
-            if reader.read_line(&mut line).is_err() {
-                self.channels[channel].err = true;
-                String::new()
-            } else {
-                self.channels[channel].err = false;
-                line.trim_end_matches(|c| c == '\r' || c == '\n')
-                    .to_string()
-            }
+        channel.err = result.is_err();
+    }
+
+    fn fflush(&mut self, channel: usize) {
+        let channel = &mut self.channels[channel];
+        // writes aren't buffered, so this only drops the read buffer
+        let result = channel.writer().and_then(|file| file.flush());
+        channel.err = result.is_err();
+    }
+
+    fn fclose(&mut self, channel: usize) {
+        if self.channels[channel].file.is_some() {
+            self.channels[channel] = FileChannel::new();
         } else {
-            log::error!("no file!");
             self.channels[channel].err = true;
-            String::new()
         }
     }
 
-    fn frewind(&mut self, channel: usize) {
-        match &mut self.channels[channel].file {
-            Some(f) => {
-                f.seek(SeekFrom::Start(0)).expect("seek error");
-                self.channels[channel].err = false;
-            }
-            _ => {
-                self.channels[channel].err = true;
-            }
+    fn fclose_all(&mut self) {
+        self.channels = std::array::from_fn(|_| FileChannel::new());
+    }
+
+    fn file_exists(&self, file: &str) -> bool {
+        self.resolve_file_name(file).and_then(fs::metadata).is_ok()
+    }
+
+    fn read_file(&self, file: &str) -> std::io::Result<Vec<u8>> {
+        fs::read(self.resolve_file_name(file)?)
+    }
+
+    fn get_file_date(&self, file: &str) -> Result<SystemTime> {
+        let metadata = fs::metadata(self.resolve_file_name(file)?)?;
+        metadata.modified()
+    }
+
+    fn get_file_attributes(&self, file: &str) -> Result<u8> {
+        let metadata = fs::metadata(self.resolve_file_name(file)?)?;
+        let mut attributes = if metadata.is_dir() {
+            ATTR_DIRECTORY
+        } else {
+            ATTR_ARCHIVE
+        };
+        if metadata.permissions().readonly() {
+            attributes |= ATTR_READONLY;
         }
+        if file
+            .rsplit(['\\', '/'])
+            .next()
+            .is_some_and(|name| name.starts_with('.'))
+        {
+            attributes |= ATTR_HIDDEN;
+        }
+        Ok(attributes)
     }
 
-    fn fclose(&mut self, channel: usize) {
-        match &mut self.channels[channel].file {
-            Some(_) => {
-                self.channels[channel] = FileChannel {
-                    file: None,
-                    reader: None,
-                    _content: Vec::new(),
-                    err: false,
-                };
+    fn list_files(&self, dir: &str) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(self.resolve_file_name(dir)?)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.file_name().to_string_lossy().to_string());
             }
-            _ => {
-                self.channels[channel].err = true;
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn get_file_size(&self, file: &str) -> u64 {
+        self.resolve_file_name(file)
+            .and_then(fs::metadata)
+            .map_or(0, |metadata| metadata.size())
+    }
+
+    fn get_drive(&self) -> usize {
+        (self.cur_drive as u8 - b'A') as usize
+    }
+
+    fn set_drive(&mut self, drive: usize) -> bool {
+        let letter = (b'A' + drive.min(25) as u8) as char;
+        if drive > 25 || !self.drives.contains_key(&letter) {
+            return false;
+        }
+        self.cur_drive = letter;
+        true
+    }
+
+    fn cwd(&self) -> String {
+        let dirs = self
+            .cur_dirs
+            .get(&self.cur_drive)
+            .cloned()
+            .unwrap_or_default();
+        format!("{}:\\{}", self.cur_drive, dirs.join("\\")).to_uppercase()
+    }
+
+    fn chdir(&mut self, dir: &str) -> std::io::Result<()> {
+        if !self.resolve_file_name(dir)?.is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} is no directory", dir),
+            ));
+        }
+        if let Some((drive, components)) = self.parse_dos_path(dir) {
+            self.cur_dirs.insert(drive, components);
+        }
+        Ok(())
+    }
+
+    fn mkdir(&mut self, dir: &str) -> std::io::Result<()> {
+        fs::create_dir(self.resolve_file_name(dir)?)
+    }
+
+    fn drive_space(&self, drive: &str) -> std::io::Result<u64> {
+        let letter = match drive.chars().next() {
+            Some(letter) if letter.is_ascii_alphabetic() => letter.to_ascii_uppercase(),
+            _ => self.cur_drive,
+        };
+        match self.drives.get(&letter) {
+            Some(root) => free_space(root),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("invalid drive {}:", letter),
+            )),
+        }
+    }
+
+    fn open_data(
+        &mut self,
+        file: &str,
+        create: bool,
+        exclusive: bool,
+    ) -> Result<Box<dyn DataFile>> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(create);
+        let sm = if exclusive { S_DB } else { S_DN };
+        let (file, share) = self.open_file(file, &options, create, O_RW, sm)?;
+        Ok(Box::new(DiskDataFile {
+            file,
+            _share: share,
+        }))
+    }
+}
+
+struct DiskDataFile {
+    file: File,
+    _share: ShareLock,
+}
+
+impl DataFile for DiskDataFile {
+    fn read_at(&mut self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let mut data = vec![0; len];
+        let mut read = 0;
+        while read < len {
+            match self.file.read_at(&mut data[read..], pos + read as u64)? {
+                0 => break,
+                n => read += n,
             }
         }
+        data.truncate(read);
+        Ok(data)
     }
 
-    fn file_exists(&self, file: &str) -> bool {
-        fs::metadata(self.resolve_file_name(file)).is_ok()
+    fn write_at(&mut self, pos: u64, data: &[u8]) -> Result<()> {
+        self.file.write_all_at(data, pos)
     }
 
-    fn get_file_date(&self, file: &str) -> Result<SystemTime> {
-        let metadata = fs::metadata(self.resolve_file_name(file))?;
-        metadata.accessed()
+    fn size(&self) -> Result<u64> {
+        Ok(self.file.metadata()?.len())
     }
 
-    fn get_file_size(&self, file: &str) -> u64 {
-        let metadata = fs::metadata(self.resolve_file_name(file)).unwrap();
-        metadata.size()
+    fn set_size(&mut self, size: u64) -> Result<()> {
+        self.file.set_len(size)
+    }
+
+    fn lock(&mut self, pos: u64, len: u64) -> Result<bool> {
+        lock_range(&self.file, pos, len)
+    }
+
+    fn unlock(&mut self, pos: u64, len: u64) -> Result<()> {
+        unlock_range(&self.file, pos, len)
+    }
+}
+
+/// Time source of `MemoryIO`, file times are taken from it.
+pub type Clock = Box<dyn Fn() -> SystemTime>;
+
+struct MemoryFile {
+    /// file name as it was created
+    name: String,
+    data: Vec<u8>,
+    modified: SystemTime,
+    /// open data handles
+    handles: Vec<MemoryHandle>,
+}
+
+impl MemoryFile {
+    fn new(name: String, data: Vec<u8>, modified: SystemTime) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(MemoryFile {
+            name,
+            data,
+            modified,
+            handles: Vec::new(),
+        }))
     }
 }
 
-struct SimulatedFileChannel {
+/// A data handle of a file, they're opened read/write (O_RW).
+struct MemoryHandle {
+    id: usize,
+    sm: i32,
+    /// locked ranges (pos, len)
+    locks: Vec<(u64, u64)>,
+}
+
+struct MemoryChannel {
+    /// key of the open file
     file: Option<String>,
-    filepos: i32,
+    pos: u64,
+    read: bool,
+    write: bool,
+    /// access & share mode for the share checks
+    am: i32,
+    sm: i32,
     err: bool,
 }
 
-impl SimulatedFileChannel {
+impl MemoryChannel {
     fn new() -> Self {
-        SimulatedFileChannel {
+        MemoryChannel {
             file: None,
-            filepos: 0,
+            pos: 0,
+            read: false,
+            write: false,
+            am: O_RD,
+            sm: S_DN,
             err: false,
         }
     }
+
+    fn failed() -> Self {
+        MemoryChannel {
+            err: true,
+            ..MemoryChannel::new()
+        }
+    }
 }
 
+/// An in-memory drive C: that behaves like `DiskIO`, for tests.
+/// Names are case insensitive & case preserving, files are bytes and their times come from a clock.
 pub struct MemoryIO {
-    channels: [SimulatedFileChannel; 8],
-    pub files: HashMap<String, String>,
+    channels: [MemoryChannel; 8],
+    /// files by upper case path without drive ("PCB\MAIN\PPE.LOG"), data handles share them
+    files: HashMap<String, Rc<RefCell<MemoryFile>>>,
+    /// modification time of the directories by upper case path, the root is ""
+    dirs: HashMap<String, SystemTime>,
+    cur_dir: Vec<String>,
+    clock: Rc<dyn Fn() -> SystemTime>,
+    next_handle: usize,
 }
 
 impl MemoryIO {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_clock(Box::new(SystemTime::now))
+    }
+
+    #[must_use]
+    pub fn with_clock(clock: Clock) -> Self {
+        let mut dirs = HashMap::new();
+        dirs.insert(String::new(), clock());
         MemoryIO {
+            channels: std::array::from_fn(|_| MemoryChannel::new()),
             files: HashMap::new(),
-            channels: [
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-                SimulatedFileChannel::new(),
-            ],
+            dirs,
+            cur_dir: Vec::new(),
+            clock: Rc::from(clock),
+            next_handle: 0,
+        }
+    }
+
+    /// Adds a file, missing directories are created.
+    pub fn add_file(&mut self, file: &str, data: impl Into<Vec<u8>>) {
+        let components = self.components(file).expect("invalid file name");
+        let Some((name, dir)) = components.split_last() else {
+            panic!("invalid file name {file}");
+        };
+        self.add_dirs(dir);
+        self.files.insert(
+            key(&components),
+            MemoryFile::new(name.clone(), data.into(), (self.clock)()),
+        );
+    }
+
+    /// Adds a directory & its parents.
+    pub fn add_dir(&mut self, dir: &str) {
+        let components = self.components(dir).expect("invalid directory name");
+        self.add_dirs(&components);
+    }
+
+    fn add_dirs(&mut self, components: &[String]) {
+        for i in 1..=components.len() {
+            let now = (self.clock)();
+            self.dirs.entry(key(&components[..i])).or_insert(now);
+        }
+    }
+
+    /// Splits a DOS path in its components, relative paths start at the current directory.
+    fn components(&self, file: &str) -> Result<Vec<String>> {
+        let name = file.replace('/', "\\");
+        let path = match name.as_bytes() {
+            [drive, b':', ..] if drive.is_ascii_alphabetic() => {
+                if !drive.eq_ignore_ascii_case(&b'C') {
+                    return Err(Error::new(
+                        ErrorKind::NotFound,
+                        format!("invalid drive {}:", *drive as char),
+                    ));
+                }
+                &name[2..]
+            }
+            _ => name.as_str(),
+        };
+        let mut components = if path.starts_with('\\') {
+            Vec::new()
+        } else {
+            self.cur_dir.clone()
+        };
+        for component in path.split('\\') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    if components.pop().is_none() {
+                        return Err(blocked(file, file));
+                    }
+                }
+                _ => components.push(component.to_string()),
+            }
+        }
+        Ok(components)
+    }
+
+    /// Key & name of a new or existing entry, the directory has to exist.
+    fn entry(&self, file: &str) -> Result<(String, String)> {
+        let components = self.components(file)?;
+        let Some((name, dir)) = components.split_last() else {
+            return Ok((String::new(), String::new()));
+        };
+        if !self.dirs.contains_key(&key(dir)) {
+            return Err(not_found(file));
+        }
+        Ok((key(&components), name.clone()))
+    }
+
+    fn file(&self, file: &str) -> Result<Ref<'_, MemoryFile>> {
+        let (key, _) = self.entry(file)?;
+        self.files
+            .get(&key)
+            .map(|file| file.borrow())
+            .ok_or_else(|| not_found(file))
+    }
+
+    /// Opens a file like `DiskIO`, files that may be created are always writable.
+    fn open(&mut self, channel: usize, file: &str, am: i32, sm: i32, create: bool, truncate: bool) {
+        let (read, write) = if create {
+            (am != O_WR, true)
+        } else {
+            access(am)
+        };
+        self.channels[channel] = match self.open_file(file, am, sm, create, truncate) {
+            Ok(key) => MemoryChannel {
+                file: Some(key),
+                pos: 0,
+                read,
+                write,
+                am,
+                sm,
+                err: false,
+            },
+            Err(err) => {
+                log::trace!("can't open file: {}", err);
+                MemoryChannel::failed()
+            }
+        };
+    }
+
+    fn open_file(
+        &mut self,
+        file: &str,
+        am: i32,
+        sm: i32,
+        create: bool,
+        truncate: bool,
+    ) -> Result<String> {
+        let (key, name) = self.entry(file)?;
+        if self.dirs.contains_key(&key) {
+            return Err(Error::other(format!("{} is a directory", file)));
+        }
+        let now = (self.clock)();
+        if !self.files.contains_key(&key) {
+            if !create {
+                return Err(not_found(file));
+            }
+            self.files
+                .insert(key.clone(), MemoryFile::new(name, Vec::new(), now));
+        }
+        let shared = self.files[&key].clone();
+        let mut memory_file = shared.borrow_mut();
+        if self.channels.iter().any(|channel| {
+            channel.file.as_ref() == Some(&key) && share_conflict(channel.am, channel.sm, am, sm)
+        }) || memory_file
+            .handles
+            .iter()
+            .any(|handle| share_conflict(O_RW, handle.sm, am, sm))
+        {
+            return Err(Error::other(format!("sharing violation on {}", file)));
+        }
+        if truncate {
+            memory_file.data.clear();
+            memory_file.modified = now;
+        }
+        Ok(key)
+    }
+
+    /// The file of an open channel if the channel has the needed access, sets the error flag otherwise.
+    fn channel_file(
+        &mut self,
+        channel: usize,
+        read: bool,
+        write: bool,
+    ) -> Option<(&mut MemoryChannel, RefMut<'_, MemoryFile>)> {
+        let channel = &mut self.channels[channel];
+        let allowed = (!read || channel.read) && (!write || channel.write);
+        match channel.file.as_ref().and_then(|key| self.files.get(key)) {
+            Some(file) if allowed => Some((channel, file.borrow_mut())),
+            _ => {
+                channel.err = true;
+                None
+            }
         }
     }
 }
 
+fn key(components: &[String]) -> String {
+    components.join("\\").to_uppercase()
+}
+
+fn parent(key: &str) -> &str {
+    key.rfind('\\').map_or("", |i| &key[..i])
+}
+
+fn not_found(file: &str) -> Error {
+    Error::new(ErrorKind::NotFound, format!("{} not found", file))
+}
+
 impl Default for MemoryIO {
     fn default() -> Self {
         Self::new()
@@ -389,144 +997,441 @@ impl Default for MemoryIO {
 }
 
 impl PCBoardIO for MemoryIO {
-    fn fappend(&mut self, channel: usize, file: &str, _am: i32, _sm: i32) {
-        let f = file.to_string();
-        let mut filepos = 0;
-
-        if let Some(v) = self.files.get(&f) {
-            filepos = v.len();
-        } else {
-            self.files.insert(f.clone(), String::new());
+    fn fappend(&mut self, channel: usize, file: &str, am: i32, sm: i32) {
+        self.open(channel, file, am, sm, true, false);
+        if let Some((channel, file)) = self.channel_file(channel, false, false) {
+            channel.pos = file.data.len() as u64;
         }
+    }
 
-        self.channels[channel] = SimulatedFileChannel {
-            file: Some(f),
-            filepos: filepos as i32,
-            err: false,
-        };
+    fn fcreate(&mut self, channel: usize, file: &str, am: i32, sm: i32) {
+        self.open(channel, file, am, sm, true, true);
     }
 
-    fn fcreate(&mut self, channel: usize, file: &str, _am: i32, _sm: i32) {
-        let f = file.to_string();
-        self.files.insert(f.clone(), String::new());
+    fn fopen(&mut self, channel: usize, file: &str, am: i32, sm: i32) {
+        self.open(channel, file, am, sm, am == O_WR, am == O_WR);
+    }
 
-        self.channels[channel] = SimulatedFileChannel {
-            file: Some(f),
-            filepos: 0,
-            err: false,
+    fn ferr(&self, channel: usize) -> bool {
+        self.channels[channel].err
+    }
+
+    fn fput(&mut self, channel: usize, text: String) {
+        self.fwrite(channel, &to_cp437(&text));
+    }
+
+    fn fget(&mut self, channel: usize) -> String {
+        let Some((channel, file)) = self.channel_file(channel, true, false) else {
+            return String::new();
         };
+        let start = channel.pos as usize;
+        if start >= file.data.len() {
+            channel.err = true;
+            return String::new();
+        }
+        let end = file.data[start..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(file.data.len(), |i| start + i + 1);
+        channel.pos = end as u64;
+        channel.err = false;
+        from_cp437(&file.data[start..end])
+            .trim_end_matches(['\r', '\n'])
+            .to_string()
     }
 
-    fn fopen(&mut self, channel: usize, file: &str, _am: i32, _sm: i32) {
-        let f = file.to_string();
+    fn frewind(&mut self, channel: usize) {
+        self.fseek(channel, 0, SEEK_SET);
+    }
 
-        if self.files.get(&f).is_none() {
-            self.channels[channel] = SimulatedFileChannel {
-                file: None,
-                filepos: 0,
-                err: true,
-            };
+    fn fseek(&mut self, channel: usize, offset: i64, origin: i32) {
+        let Some((channel, file)) = self.channel_file(channel, false, false) else {
             return;
+        };
+        let base = match origin {
+            SEEK_CUR => channel.pos as i64,
+            SEEK_END => file.data.len() as i64,
+            _ => 0,
+        };
+        let pos = base + offset;
+        channel.err = pos < 0;
+        if pos >= 0 {
+            channel.pos = pos as u64;
+        }
+    }
+
+    fn ftell(&mut self, channel: usize) -> i64 {
+        match self.channel_file(channel, false, false) {
+            Some((channel, _)) => channel.pos as i64,
+            None => -1,
         }
+    }
 
-        self.channels[channel] = SimulatedFileChannel {
-            file: Some(f),
-            filepos: 0,
-            err: false,
+    fn fread(&mut self, channel: usize, len: usize) -> Vec<u8> {
+        let Some((channel, file)) = self.channel_file(channel, true, false) else {
+            return Vec::new();
+        };
+        let start = (channel.pos as usize).min(file.data.len());
+        let data = file.data[start..(start + len).min(file.data.len())].to_vec();
+        channel.pos += data.len() as u64;
+        channel.err = data.len() < len;
+        data
+    }
+
+    fn fwrite(&mut self, channel: usize, data: &[u8]) {
+        let now = (self.clock)();
+        let Some((channel, mut file)) = self.channel_file(channel, false, true) else {
+            return;
         };
+        let pos = channel.pos as usize;
+        if file.data.len() < pos + data.len() {
+            file.data.resize(pos + data.len(), 0);
+        }
+        file.data[pos..pos + data.len()].copy_from_slice(data);
+        file.modified = now;
+        channel.pos += data.len() as u64;
+        channel.err = false;
+    }
+
+    fn fflush(&mut self, channel: usize) {
+        if let Some((channel, _)) = self.channel_file(channel, false, false) {
+            channel.err = false;
+        }
+    }
+
+    fn fclose(&mut self, channel: usize) {
+        if self.channels[channel].file.is_some() {
+            self.channels[channel] = MemoryChannel::new();
+        } else {
+            self.channels[channel].err = true;
+        }
+    }
+
+    fn fclose_all(&mut self) {
+        self.channels = std::array::from_fn(|_| MemoryChannel::new());
+    }
+
+    fn file_exists(&self, file: &str) -> bool {
+        self.entry(file)
+            .is_ok_and(|(key, _)| self.files.contains_key(&key) || self.dirs.contains_key(&key))
+    }
+
+    fn read_file(&self, file: &str) -> std::io::Result<Vec<u8>> {
+        Ok(self.file(file)?.data.clone())
     }
 
     fn delete(&mut self, file: &str) -> std::io::Result<()> {
-        let f = file.to_string();
-        self.files.remove(&f);
-        Ok(())
+        let (key, _) = self.entry(file)?;
+        self.files
+            .remove(&key)
+            .map(|_| ())
+            .ok_or_else(|| not_found(file))
     }
 
     fn rename(&mut self, old: &str, new: &str) -> std::io::Result<()> {
-        if let Some(removed) = self.files.remove(&old.to_string()) {
-            self.files.insert(new.to_string(), removed);
+        let (old_key, _) = self.entry(old)?;
+        let (new_key, new_name) = self.entry(new)?;
+        if self.dirs.contains_key(&new_key) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is a directory", new),
+            ));
+        }
+        if let Some(file) = self.files.remove(&old_key) {
+            file.borrow_mut().name = new_name;
+            self.files.insert(new_key.clone(), file);
+        } else if self.dirs.contains_key(&old_key) && !old_key.is_empty() {
+            let prefix = format!("{}\\", old_key);
+            if new_key.starts_with(&prefix) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("can't move {} into itself", old),
+                ));
+            }
+            let moved = |key: &String| {
+                if *key == old_key {
+                    Some(new_key.clone())
+                } else {
+                    key.strip_prefix(&prefix)
+                        .map(|rest| format!("{}\\{}", new_key, rest))
+                }
+            };
+            for (key, new) in self
+                .dirs
+                .keys()
+                .filter_map(|k| Some((k.clone(), moved(k)?)))
+                .collect::<Vec<_>>()
+            {
+                let modified = self.dirs.remove(&key).unwrap();
+                self.dirs.insert(new, modified);
+            }
+            for (key, new) in self
+                .files
+                .keys()
+                .filter_map(|k| Some((k.clone(), moved(k)?)))
+                .collect::<Vec<_>>()
+            {
+                let file = self.files.remove(&key).unwrap();
+                self.files.insert(new, file);
+            }
+        } else {
+            return Err(not_found(old));
+        }
+        // open files move along
+        for channel in &mut self.channels {
+            if channel.file.as_ref() == Some(&old_key) {
+                channel.file = Some(new_key.clone());
+            }
         }
         Ok(())
     }
 
     fn copy(&mut self, from: &str, to: &str) -> std::io::Result<()> {
-        if let Some(removed) = self.files.get(&from.to_string()) {
-            self.files.insert(to.to_string(), removed.clone());
+        let data = self.file(from)?.data.clone();
+        let (key, name) = self.entry(to)?;
+        if self.dirs.contains_key(&key) {
+            return Err(Error::other(format!("{} is a directory", to)));
         }
+        let modified = (self.clock)();
+        let mut file = self
+            .files
+            .entry(key)
+            .or_insert_with(|| MemoryFile::new(name, Vec::new(), modified))
+            .borrow_mut();
+        file.data = data;
+        file.modified = modified;
         Ok(())
     }
 
-    fn ferr(&self, channel: usize) -> bool {
-        self.channels[channel].err
+    fn get_file_date(&self, file: &str) -> Result<SystemTime> {
+        let (key, _) = self.entry(file)?;
+        match self.files.get(&key) {
+            Some(file) => Ok(file.borrow().modified),
+            None => self.dirs.get(&key).copied().ok_or_else(|| not_found(file)),
+        }
     }
 
-    fn fput(&mut self, channel: usize, text: String) {
-        if let Some(v) = &self.channels[channel].file {
-            let content = self.files.get_mut(v).unwrap();
-            content.insert_str(self.channels[channel].filepos as usize, &text);
-            self.channels[channel].filepos += text.len() as i32;
+    fn get_file_attributes(&self, file: &str) -> Result<u8> {
+        let (key, _) = self.entry(file)?;
+        let mut attributes = if self.files.contains_key(&key) {
+            ATTR_ARCHIVE
+        } else if self.dirs.contains_key(&key) {
+            ATTR_DIRECTORY
         } else {
-            self.channels[channel] = SimulatedFileChannel {
-                file: None,
-                filepos: 0,
-                err: true,
-            };
+            return Err(not_found(file));
+        };
+        if file
+            .rsplit(['\\', '/'])
+            .next()
+            .is_some_and(|name| name.starts_with('.'))
+        {
+            attributes |= ATTR_HIDDEN;
         }
+        Ok(attributes)
     }
 
-    fn fget(&mut self, channel: usize) -> String {
-        if let Some(v) = &mut self.channels[channel].file {
-            let f = self.files.get(v).unwrap();
-            if self.channels[channel].filepos as usize >= f.len() {
-                self.channels[channel].err = true;
-                return "".to_string();
+    fn list_files(&self, dir: &str) -> Result<Vec<String>> {
+        let dir_key = key(&self.components(dir)?);
+        if !self.dirs.contains_key(&dir_key) {
+            return Err(not_found(dir));
+        }
+        let mut files: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(key, _)| parent(key) == dir_key)
+            .map(|(_, file)| file.borrow().name.clone())
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn get_file_size(&self, file: &str) -> u64 {
+        self.file(file).map_or(0, |file| file.data.len() as u64)
+    }
+
+    fn get_drive(&self) -> usize {
+        2
+    }
+
+    fn set_drive(&mut self, drive: usize) -> bool {
+        drive == 2
+    }
+
+    fn cwd(&self) -> String {
+        format!("C:\\{}", self.cur_dir.join("\\")).to_uppercase()
+    }
+
+    fn chdir(&mut self, dir: &str) -> std::io::Result<()> {
+        let components = self.components(dir)?;
+        if !self.dirs.contains_key(&key(&components)) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} is no directory", dir),
+            ));
+        }
+        self.cur_dir = components;
+        Ok(())
+    }
+
+    fn mkdir(&mut self, dir: &str) -> std::io::Result<()> {
+        let (key, _) = self.entry(dir)?;
+        if self.dirs.contains_key(&key) || self.files.contains_key(&key) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", dir),
+            ));
+        }
+        let now = (self.clock)();
+        self.dirs.insert(key, now);
+        Ok(())
+    }
+
+    fn drive_space(&self, drive: &str) -> std::io::Result<u64> {
+        match drive.chars().next() {
+            Some(letter) if letter.is_ascii_alphabetic() && !letter.eq_ignore_ascii_case(&'C') => {
+                Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("invalid drive {}:", letter),
+                ))
             }
-            let result: String = f
-                .chars()
-                .skip(self.channels[channel].filepos as usize)
-                .take_while(|c| *c != '\n')
-                .collect();
-            self.channels[channel].filepos += result.len() as i32 + 1;
-            result
-        } else {
-            self.channels[channel].err = true;
-            String::new()
+            // memory has no size limit
+            _ => Ok(u64::MAX),
         }
     }
 
-    fn frewind(&mut self, channel: usize) {
-        if self.channels[channel].file.is_some() {
-            self.channels[channel].filepos = 0;
-        } else {
-            self.channels[channel] = SimulatedFileChannel {
-                file: None,
-                filepos: 0,
-                err: true,
-            };
+    fn open_data(
+        &mut self,
+        file: &str,
+        create: bool,
+        exclusive: bool,
+    ) -> Result<Box<dyn DataFile>> {
+        let sm = if exclusive { S_DB } else { S_DN };
+        let key = self.open_file(file, O_RW, sm, create, create)?;
+        let id = self.next_handle;
+        self.next_handle += 1;
+        let file = self.files[&key].clone();
+        file.borrow_mut().handles.push(MemoryHandle {
+            id,
+            sm,
+            locks: Vec::new(),
+        });
+        Ok(Box::new(MemoryDataFile {
+            id,
+            file,
+            clock: self.clock.clone(),
+        }))
+    }
+}
+
+struct MemoryDataFile {
+    id: usize,
+    file: Rc<RefCell<MemoryFile>>,
+    clock: Rc<dyn Fn() -> SystemTime>,
+}
+
+impl DataFile for MemoryDataFile {
+    fn read_at(&mut self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let file = self.file.borrow();
+        let start = (pos as usize).min(file.data.len());
+        let end = (start + len).min(file.data.len());
+        Ok(file.data[start..end].to_vec())
+    }
+
+    fn write_at(&mut self, pos: u64, data: &[u8]) -> Result<()> {
+        let mut file = self.file.borrow_mut();
+        let pos = pos as usize;
+        if file.data.len() < pos + data.len() {
+            file.data.resize(pos + data.len(), 0);
         }
+        file.data[pos..pos + data.len()].copy_from_slice(data);
+        file.modified = (self.clock)();
+        Ok(())
     }
 
-    fn fclose(&mut self, channel: usize) {
-        self.channels[channel] = SimulatedFileChannel {
-            file: None,
-            filepos: 0,
-            err: false,
-        };
+    fn size(&self) -> Result<u64> {
+        Ok(self.file.borrow().data.len() as u64)
     }
 
-    fn file_exists(&self, file: &str) -> bool {
-        self.files.contains_key(file)
+    fn set_size(&mut self, size: u64) -> Result<()> {
+        let mut file = self.file.borrow_mut();
+        file.data.resize(size as usize, 0);
+        file.modified = (self.clock)();
+        Ok(())
     }
 
-    fn get_file_date(&self, _file: &str) -> Result<SystemTime> {
-        Ok(SystemTime::UNIX_EPOCH)
+    fn lock(&mut self, pos: u64, len: u64) -> Result<bool> {
+        let mut file = self.file.borrow_mut();
+        let overlaps = |(start, locked): &(u64, u64)| pos < start + locked && *start < pos + len;
+        if file
+            .handles
+            .iter()
+            .any(|handle| handle.id != self.id && handle.locks.iter().any(overlaps))
+        {
+            return Ok(false);
+        }
+        if let Some(handle) = file.handles.iter_mut().find(|handle| handle.id == self.id) {
+            handle.locks.push((pos, len));
+        }
+        Ok(true)
     }
 
-    fn get_file_size(&self, file: &str) -> u64 {
-        if let Some(v) = self.files.get(file) {
-            v.len() as u64
-        } else {
-            0
+    fn unlock(&mut self, pos: u64, len: u64) -> Result<()> {
+        let mut file = self.file.borrow_mut();
+        if let Some(handle) = file.handles.iter_mut().find(|handle| handle.id == self.id) {
+            handle.locks.retain(|lock| *lock != (pos, len));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MemoryDataFile {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.file
+            .borrow_mut()
+            .handles
+            .retain(|handle| handle.id != id);
+    }
+}
+
+/// Splits a DOS file name in the directory (with drive) and the file name.
+pub fn split_file_name(file: &str) -> (&str, &str) {
+    match file.rfind(['\\', '/', ':']) {
+        Some(i) => file.split_at(i + 1),
+        None => ("", file),
+    }
+}
+
+/// Matches a file name against a DOS wildcard pattern, case insensitive.
+/// Like DOS the name & extension are matched separately: `*` matches the rest of the part,
+/// `?` one character or the end of the part. A pattern without a dot only matches names without
+/// an extension while `*.*` matches all names.
+pub fn match_wildcard(pattern: &str, name: &str) -> bool {
+    let (pattern_base, pattern_ext) = split_extension(pattern);
+    let (base, ext) = split_extension(name);
+    match_part(pattern_base, base) && match_part(pattern_ext, ext)
+}
+
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (name, ""),
+    }
+}
+
+fn match_part(pattern: &str, part: &str) -> bool {
+    let mut chars = part.chars();
+    for p in pattern.chars() {
+        match p {
+            '*' => return true,
+            '?' => {
+                chars.next();
+            }
+            _ => match chars.next() {
+                Some(c) if c.eq_ignore_ascii_case(&p) => {}
+                _ => return false,
+            },
         }
     }
+    chars.next().is_none()
 }
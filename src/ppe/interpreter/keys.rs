@@ -0,0 +1,182 @@
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Interpreter, Res};
+
+/// Time to wait for the rest of an escape sequence before treating ESC as a single key.
+const ESC_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// Keystrokes stuffed by a PPE, these are read before the user input.
+#[derive(Default)]
+pub struct KeyboardBuffer {
+    /// KBDSTRING - keys in the (local) keyboard buffer
+    pub keyboard: VecDeque<char>,
+    /// KBDSTUFF - the PPL keyboard buffer
+    pub ppl: VecDeque<char>,
+    /// KBDFILE - keys stuffed from a file
+    pub file: VecDeque<char>,
+}
+
+impl KeyboardBuffer {
+    pub fn pop_front(&mut self) -> Option<char> {
+        self.keyboard
+            .pop_front()
+            .or_else(|| self.ppl.pop_front())
+            .or_else(|| self.file.pop_front())
+    }
+
+    pub fn clear(&mut self) {
+        self.keyboard.clear();
+        self.ppl.clear();
+        self.file.clear();
+    }
+}
+
+/// Waits up to `timeout` for a character, `None` waits forever.
+pub fn get_char_timeout(
+    interpreter: &mut Interpreter,
+    timeout: Option<Duration>,
+) -> Res<Option<char>> {
+    let start = Instant::now();
+    loop {
+        if let Some(ch) = interpreter.get_char()? {
+            return Ok(Some(ch));
+        }
+        if let Some(timeout) = timeout {
+            if start.elapsed() >= timeout {
+                return Ok(None);
+            }
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Reads a key and translates terminal escape sequences to the PPL key names
+/// ("UP", "DOWN", "LEFT", "RIGHT", "HOME", "END", "PGUP", "PGDN", "INS", "DEL", "F1"-"F12").
+/// # Arguments
+///  * `timeout` - time to wait for the first key, `None` waits forever
+/// # Returns
+///  The key or an empty string if no key was pressed in time
+/// # Remarks
+/// DEL (127) is sent by most terminals for backspace, so it's mapped to BS (8).
+/// The DEL key itself sends `ESC [ 3 ~`.
+pub fn read_key(interpreter: &mut Interpreter, timeout: Option<Duration>) -> Res<String> {
+    let Some(ch) = get_char_timeout(interpreter, timeout)? else {
+        return Ok(String::new());
+    };
+    match ch {
+        '\x7F' => Ok("\x08".to_string()),
+        '\x1B' => read_escape_sequence(interpreter),
+        _ => Ok(ch.to_string()),
+    }
+}
+
+fn read_escape_sequence(interpreter: &mut Interpreter) -> Res<String> {
+    let Some(ch) = get_char_timeout(interpreter, Some(ESC_TIMEOUT))? else {
+        return Ok("\x1B".to_string());
+    };
+    match ch {
+        '[' => read_csi(interpreter),
+        'O' => {
+            let Some(ch) = get_char_timeout(interpreter, Some(ESC_TIMEOUT))? else {
+                return Ok("\x1BO".to_string());
+            };
+            Ok(ss3_key(ch).map_or_else(|| format!("\x1BO{ch}"), str::to_string))
+        }
+        _ => Ok(format!("\x1B{ch}")),
+    }
+}
+
+/// `ESC [` sequences (ANSI-BBS, SCO, VT220 & xterm)
+fn read_csi(interpreter: &mut Interpreter) -> Res<String> {
+    let mut params = String::new();
+    loop {
+        let Some(ch) = get_char_timeout(interpreter, Some(ESC_TIMEOUT))? else {
+            return Ok(format!("\x1B[{params}"));
+        };
+        match ch {
+            '0'..='9' | ';' => params.push(ch),
+            '[' if params.is_empty() => {
+                // linux console F1-F5: ESC [ [ A-E
+                let Some(ch) = get_char_timeout(interpreter, Some(ESC_TIMEOUT))? else {
+                    return Ok("\x1B[[".to_string());
+                };
+                return Ok(match ch {
+                    'A'..='E' => format!("F{}", ch as u8 - b'A' + 1),
+                    _ => format!("\x1B[[{ch}"),
+                });
+            }
+            '~' => {
+                // VT220: ESC [ n ~ - modifiers are ignored
+                let n = params.split(';').next().unwrap_or("");
+                return Ok(tilde_key(n).map_or_else(|| format!("\x1B[{params}~"), str::to_string));
+            }
+            _ => {
+                // xterm sends modified keys as ESC [ 1 ; m X - modifiers are ignored
+                return Ok(csi_key(ch).map_or_else(|| format!("\x1B[{params}{ch}"), str::to_string));
+            }
+        }
+    }
+}
+
+fn csi_key(ch: char) -> Option<&'static str> {
+    Some(match ch {
+        'A' => "UP",
+        'B' => "DOWN",
+        'C' => "RIGHT",
+        'D' => "LEFT",
+        'H' => "HOME",
+        'F' | 'K' => "END",
+        // SCO page keys, the ANSI-BBS ESC [ V / ESC [ U are the SCO F10/F9
+        'I' => "PGUP",
+        'G' => "PGDN",
+        '@' | 'L' => "INS",
+        // SCO function keys
+        'M'..='X' => FUNCTION_KEYS[(ch as u8 - b'M') as usize],
+        _ => return None,
+    })
+}
+
+fn ss3_key(ch: char) -> Option<&'static str> {
+    Some(match ch {
+        'A' => "UP",
+        'B' => "DOWN",
+        'C' => "RIGHT",
+        'D' => "LEFT",
+        'H' => "HOME",
+        'F' => "END",
+        'P'..='S' => FUNCTION_KEYS[(ch as u8 - b'P') as usize],
+        _ => return None,
+    })
+}
+
+fn tilde_key(n: &str) -> Option<&'static str> {
+    Some(match n {
+        "1" | "7" => "HOME",
+        "2" => "INS",
+        "3" => "DEL",
+        "4" | "8" => "END",
+        "5" => "PGUP",
+        "6" => "PGDN",
+        "11" => "F1",
+        "12" => "F2",
+        "13" => "F3",
+        "14" => "F4",
+        "15" => "F5",
+        "17" => "F6",
+        "18" => "F7",
+        "19" => "F8",
+        "20" => "F9",
+        "21" => "F10",
+        "23" => "F11",
+        "24" => "F12",
+        _ => return None,
+    })
+}
+
+const FUNCTION_KEYS: [&str; 12] = [
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+];
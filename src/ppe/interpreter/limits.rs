@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+use crate::{errors::IcyError, Interpreter, Res};
+
+/// Bytes of the emulated PPL stack used by each GOSUB/call level, STACKLEFT reports the free bytes.
+pub const STACK_ENTRY_SIZE: usize = 128;
+
+/// The idle time is checked every n statements, looking at the clock for every statement is too slow.
+const IDLE_CHECK_INTERVAL: u64 = 1024;
+
+/// Limits that stop runaway PPEs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionLimits {
+    /// maximum number of statements a PPE may execute, 0 = unlimited
+    pub max_statements: u64,
+    /// maximum nesting of GOSUBs and procedure/function calls
+    pub max_call_depth: usize,
+    /// maximum time a PPE may run without user input, `None` = unlimited
+    pub max_idle_time: Option<Duration>,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            max_statements: 0,
+            max_call_depth: 128,
+            max_idle_time: Some(Duration::from_secs(10 * 60)),
+        }
+    }
+}
+
+/// State of the execution limits of a running PPE.
+pub struct ExecutionState {
+    statement_count: u64,
+    last_input: Instant,
+    /// STACKERR - set when a GOSUB/call exceeded the maximum depth
+    pub stack_error: bool,
+    /// STACKABORT - abort the PPE on stack errors
+    pub stack_abort: bool,
+}
+
+impl Default for ExecutionState {
+    fn default() -> Self {
+        Self {
+            statement_count: 0,
+            last_input: Instant::now(),
+            stack_error: false,
+            stack_abort: true,
+        }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Procedure/function calls and GOSUBs on the stack.
+    pub(crate) fn call_depth(&self) -> usize {
+        self.cur_frame.iter().map(|f| 1 + f.gosub_stack.len()).sum()
+    }
+
+    /// Free bytes of the emulated stack.
+    pub(crate) fn stack_left(&self) -> usize {
+        let max_depth = self.icb_data.ppe_limits.max_call_depth;
+        max_depth.saturating_sub(self.call_depth()) * STACK_ENTRY_SIZE
+    }
+
+    /// Resets the idle timer, called on user input.
+    pub(crate) fn input_received(&mut self) {
+        self.state.last_input = Instant::now();
+    }
+
+    /// Counts the statement and checks the statement & idle time limits.
+    pub(crate) fn check_limits(&mut self) -> Res<()> {
+        let limits = &self.icb_data.ppe_limits;
+        self.state.statement_count += 1;
+        if limits.max_statements > 0 && self.state.statement_count > limits.max_statements {
+            return Err(Box::new(IcyError::StatementLimitExceeded(
+                limits.max_statements,
+            )));
+        }
+        if let Some(max_idle_time) = limits.max_idle_time {
+            if self
+                .state
+                .statement_count
+                .is_multiple_of(IDLE_CHECK_INTERVAL)
+                && self.state.last_input.elapsed() > max_idle_time
+            {
+                return Err(Box::new(IcyError::IdleTimeExceeded(
+                    max_idle_time.as_secs(),
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if another GOSUB/call fits on the stack.
+    /// Returns false if it doesn't and STACKABORT is off, the call needs to be skipped.
+    pub(crate) fn check_stack(&mut self) -> Res<bool> {
+        let max_depth = self.icb_data.ppe_limits.max_call_depth;
+        if self.call_depth() < max_depth {
+            return Ok(true);
+        }
+        self.state.stack_error = true;
+        if self.state.stack_abort {
+            return Err(Box::new(IcyError::CallDepthExceeded(max_depth)));
+        }
+        log::warn!("stack overflow, call skipped");
+        Ok(false)
+    }
+}
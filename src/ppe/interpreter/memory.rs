@@ -0,0 +1,475 @@
+//! Emulated 1 MB real-mode address space for PEEK/POKE/VARSEG/DOINTR PPEs.
+//!
+//! Addresses are far pointers as PPL passes them: the segment in the high word and the
+//! offset in the low word (`MKADDR(seg, off)`). The BIOS data area at 0040:0000 is
+//! initialized like on a PC with an 80x25 color display, variables passed to VARSEG/VAROFF/VARADDR
+//! get an address above the BIOS area and are kept in sync with the memory.
+use chrono::{Datelike, Local, Timelike};
+use ppl_engine::ast::{VariableType, VariableValue};
+
+use crate::{errors::IcyError, from_cp437, to_cp437, Interpreter, Res};
+
+pub const MEMORY_SIZE: usize = 0x10_0000;
+
+const BIOS_DATA_AREA: usize = 0x400;
+const TIMER_TICKS: usize = BIOS_DATA_AREA + 0x6C;
+const KEYBOARD_FLAGS: usize = BIOS_DATA_AREA + 0x17;
+
+/// Variables get addresses from here up to the video memory.
+const VARIABLE_AREA: usize = 0x3_0000;
+const VARIABLE_AREA_END: usize = 0xA_0000;
+
+/// Size of a string variable in memory, PPL strings are ASCIIZ.
+const STRING_SIZE: usize = 256;
+const BIGSTR_SIZE: usize = 2048;
+
+const CARRY_FLAG: u16 = 0x0001;
+const ZERO_FLAG: u16 = 0x0040;
+
+/// Registers for DOINTR and the REG* functions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Registers {
+    pub ax: u16,
+    pub bx: u16,
+    pub cx: u16,
+    pub dx: u16,
+    pub si: u16,
+    pub di: u16,
+    pub flags: u16,
+    pub ds: u16,
+    pub es: u16,
+}
+
+impl Registers {
+    pub fn carry(&self) -> bool {
+        self.flags & CARRY_FLAG != 0
+    }
+
+    fn set_flag(&mut self, flag: u16, set: bool) {
+        if set {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    fn ah(&self) -> u8 {
+        (self.ax >> 8) as u8
+    }
+
+    fn set_al(&mut self, al: u8) {
+        self.ax = self.ax & 0xFF00 | al as u16;
+    }
+}
+
+fn word(hi: u8, lo: u8) -> u16 {
+    u16::from_le_bytes([lo, hi])
+}
+
+fn bcd(value: u32) -> u8 {
+    (((value / 10 % 10) << 4) | (value % 10)) as u8
+}
+
+/// A variable that got an address with VARSEG/VAROFF/VARADDR.
+struct MappedVariable {
+    scope: usize,
+    name: String,
+    var_type: VariableType,
+    address: usize,
+    size: usize,
+}
+
+pub struct DosMemory {
+    data: Vec<u8>,
+    variables: Vec<MappedVariable>,
+    next_variable: usize,
+    pub registers: Registers,
+}
+
+impl Default for DosMemory {
+    fn default() -> Self {
+        let mut memory = Self {
+            data: vec![0; MEMORY_SIZE],
+            variables: Vec::new(),
+            next_variable: VARIABLE_AREA,
+            registers: Registers::default(),
+        };
+        memory.init_bios();
+        memory
+    }
+}
+
+impl DosMemory {
+    fn init_bios(&mut self) {
+        // COM1, COM2, LPT1
+        self.write(BIOS_DATA_AREA, &0x3F8u16.to_le_bytes());
+        self.write(BIOS_DATA_AREA + 0x02, &0x2F8u16.to_le_bytes());
+        self.write(BIOS_DATA_AREA + 0x08, &0x378u16.to_le_bytes());
+        // equipment: 80x25 color, 2 serial ports, 1 printer
+        self.write(BIOS_DATA_AREA + 0x10, &0x4424u16.to_le_bytes());
+        // 640 KB conventional memory
+        self.write(BIOS_DATA_AREA + 0x13, &640u16.to_le_bytes());
+        // video mode 3, 80 columns, 25 rows
+        self.data[BIOS_DATA_AREA + 0x49] = 3;
+        self.write(BIOS_DATA_AREA + 0x4A, &80u16.to_le_bytes());
+        self.data[BIOS_DATA_AREA + 0x84] = 24;
+        // BIOS date & AT model byte
+        self.write(0xF_FFF5, b"01/01/92");
+        self.data[0xF_FFFE] = 0xFC;
+    }
+
+    /// Converts a PPL address (segment:offset) to a linear address, it wraps at 1 MB like an 8086.
+    pub fn linear(address: i64) -> usize {
+        let segment = (address >> 16) as u16 as usize;
+        let offset = address as u16 as usize;
+        (segment * 16 + offset) % MEMORY_SIZE
+    }
+
+    /// Converts a linear address to a PPL address (segment:offset)
+    pub fn far_pointer(linear: usize) -> i64 {
+        ((linear >> 4) << 16 | linear & 0xF) as i64
+    }
+
+    pub fn read(&self, linear: usize, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| self.data[(linear + i) % MEMORY_SIZE])
+            .collect()
+    }
+
+    pub fn write(&mut self, linear: usize, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.data[(linear + i) % MEMORY_SIZE] = *b;
+        }
+    }
+
+    /// BIOS timer ticks since midnight, 18.2 per second.
+    fn timer_ticks() -> u32 {
+        let now = Local::now();
+        let ms =
+            now.num_seconds_from_midnight() as u64 * 1000 + now.timestamp_subsec_millis() as u64;
+        (ms * 1_193_180 / 65_536 / 1000) as u32
+    }
+
+    fn variables_in(&self, linear: usize, len: usize) -> Vec<usize> {
+        (0..self.variables.len())
+            .filter(|i| {
+                let var = &self.variables[*i];
+                var.address < linear + len && linear < var.address + var.size
+            })
+            .collect()
+    }
+}
+
+/// Size of a variable in DOS memory.
+fn variable_size(var_type: VariableType) -> usize {
+    match var_type {
+        VariableType::Boolean | VariableType::Byte | VariableType::SByte => 1,
+        VariableType::Word | VariableType::SWord | VariableType::Date | VariableType::EDate => 2,
+        VariableType::Double => 8,
+        VariableType::String => STRING_SIZE,
+        VariableType::BigStr => BIGSTR_SIZE,
+        _ => 4,
+    }
+}
+
+/// Memory representation of a variable, little endian like on DOS.
+fn encode(var_type: VariableType, value: &VariableValue) -> Vec<u8> {
+    let size = variable_size(var_type);
+    let mut bytes = match value {
+        VariableValue::String(str) => {
+            let mut bytes: Vec<u8> = str.chars().map(|c| c as u32 as u8).collect();
+            bytes.truncate(size - 1);
+            bytes
+        }
+        VariableValue::Money(f) | VariableValue::Real(f) => match var_type {
+            VariableType::Double => f.to_le_bytes().to_vec(),
+            // money is stored in cents
+            VariableType::Money => ((f * 100.0).round() as i32).to_le_bytes().to_vec(),
+            _ => (*f as f32).to_le_bytes().to_vec(),
+        },
+        _ => crate::get_i64(value).unwrap_or(0).to_le_bytes().to_vec(),
+    };
+    bytes.resize(size, 0);
+    bytes
+}
+
+fn decode(var_type: VariableType, bytes: &[u8]) -> VariableValue {
+    let int = |len: usize| {
+        let mut buf = [0; 8];
+        buf[..len].copy_from_slice(&bytes[..len]);
+        i64::from_le_bytes(buf)
+    };
+    match var_type {
+        VariableType::Boolean => VariableValue::Boolean(bytes[0] != 0),
+        VariableType::Byte => VariableValue::Byte(bytes[0]),
+        VariableType::SByte => VariableValue::SByte(bytes[0] as i8),
+        VariableType::Word => VariableValue::Word(int(2) as u16),
+        VariableType::SWord => VariableValue::SWord(int(2) as i16),
+        VariableType::Date => VariableValue::Date(int(2) as u16),
+        VariableType::EDate => VariableValue::EDate(int(2) as u16),
+        VariableType::Unsigned => VariableValue::Unsigned(int(4) as u32),
+        VariableType::Time => VariableValue::Time(int(4) as i32),
+        VariableType::Money => VariableValue::Money(int(4) as i32 as f64 / 100.0),
+        VariableType::Real => {
+            VariableValue::Real(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64)
+        }
+        VariableType::Double => VariableValue::Real(f64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ])),
+        VariableType::String | VariableType::BigStr => VariableValue::String(
+            bytes
+                .iter()
+                .take_while(|b| **b != 0)
+                .map(|b| *b as char)
+                .collect(),
+        ),
+        _ => VariableValue::Integer(int(4) as i32),
+    }
+}
+
+/// Type of a value that isn't a variable, like an expression passed to FWRITE.
+pub(crate) fn value_type(value: &VariableValue) -> VariableType {
+    match value {
+        VariableValue::Boolean(_) => VariableType::Boolean,
+        VariableValue::Unsigned(_) => VariableType::Unsigned,
+        VariableValue::Word(_) => VariableType::Word,
+        VariableValue::SWord(_) => VariableType::SWord,
+        VariableValue::Byte(_) => VariableType::Byte,
+        VariableValue::SByte(_) => VariableType::SByte,
+        VariableValue::Money(_) => VariableType::Money,
+        VariableValue::Real(_) => VariableType::Double,
+        VariableValue::String(_) => VariableType::String,
+        VariableValue::Date(_) => VariableType::Date,
+        VariableValue::EDate(_) => VariableType::EDate,
+        VariableValue::Time(_) => VariableType::Time,
+        _ => VariableType::Integer,
+    }
+}
+
+/// File representation of a value for FWRITE, `len` bytes padded with zeros.
+/// Unlike in memory strings aren't limited to the size of a string variable.
+pub(crate) fn to_binary(
+    var_type: Option<VariableType>,
+    value: &VariableValue,
+    len: usize,
+) -> Vec<u8> {
+    let var_type = var_type.unwrap_or_else(|| value_type(value));
+    let mut bytes = match (var_type, value) {
+        (VariableType::String | VariableType::BigStr, _) => to_cp437(&value.to_string()),
+        _ => encode(var_type, value),
+    };
+    bytes.resize(len, 0);
+    bytes
+}
+
+/// Value of a variable after FREAD read `bytes` into it, a short read keeps the other bytes of `old`.
+pub(crate) fn from_binary(
+    var_type: VariableType,
+    old: &VariableValue,
+    bytes: &[u8],
+) -> VariableValue {
+    match var_type {
+        VariableType::String | VariableType::BigStr => {
+            let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            VariableValue::String(from_cp437(&bytes[..len]))
+        }
+        _ => {
+            let mut buf = encode(var_type, old);
+            let len = bytes.len().min(buf.len());
+            buf[..len].copy_from_slice(&bytes[..len]);
+            decode(var_type, &buf)
+        }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Declared type of a variable visible from the current frame.
+    pub(crate) fn variable_type(&self, name: &str) -> Option<VariableType> {
+        let (frame, slot) = self.resolve(name)?;
+        Some(
+            self.symbols
+                .scope(self.cur_frame[frame].scope)
+                .get_type(slot),
+        )
+    }
+
+    /// Returns the linear address of a variable, it's mapped into the DOS memory on first use.
+    pub(crate) fn variable_address(&mut self, name: &str) -> Res<usize> {
+        let Some((frame, slot)) = self.resolve(name) else {
+            return Err(Box::new(IcyError::VariableNotFound(name.to_string())));
+        };
+        let scope = self.cur_frame[frame].scope;
+        let var_type = self.symbols.scope(scope).get_type(slot);
+        if let Some(var) = self
+            .memory
+            .variables
+            .iter()
+            .find(|v| v.scope == scope && v.name == name)
+        {
+            return Ok(var.address);
+        }
+        let size = variable_size(var_type);
+        let address = self.memory.next_variable;
+        if address + size > VARIABLE_AREA_END {
+            return Err(Box::new(IcyError::OutOfMemory));
+        }
+        self.memory.next_variable += size;
+        self.memory.variables.push(MappedVariable {
+            scope,
+            name: name.to_string(),
+            var_type,
+            address,
+            size,
+        });
+        Ok(address)
+    }
+
+    /// Reads the emulated memory, mapped variables & the BIOS clock are updated first.
+    pub(crate) fn peek(&mut self, address: i64, len: usize) -> Vec<u8> {
+        let linear = DosMemory::linear(address);
+        if linear < TIMER_TICKS + 4 && TIMER_TICKS < linear + len {
+            let ticks = DosMemory::timer_ticks();
+            self.memory.write(TIMER_TICKS, &ticks.to_le_bytes());
+        }
+        for i in self.memory.variables_in(linear, len) {
+            let var = &self.memory.variables[i];
+            if let Some(value) = self.lookup_mapped(var.scope, &var.name) {
+                let bytes = encode(var.var_type, &value);
+                let address = var.address;
+                self.memory.write(address, &bytes);
+            }
+        }
+        self.memory.read(linear, len)
+    }
+
+    /// Writes the emulated memory, mapped variables in the range are changed accordingly.
+    pub(crate) fn poke(&mut self, address: i64, bytes: &[u8]) -> Res<()> {
+        let linear = DosMemory::linear(address);
+        // refresh the variables first, a partial write keeps the other bytes
+        self.peek(address, bytes.len());
+        self.memory.write(linear, bytes);
+        for i in self.memory.variables_in(linear, bytes.len()) {
+            let var = &self.memory.variables[i];
+            let value = decode(var.var_type, &self.memory.read(var.address, var.size));
+            if self.lookup_mapped(var.scope, &var.name).is_some() {
+                let name = var.name.clone();
+                self.set_variable(&name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Value of a mapped variable if it is visible from the current frame.
+    fn lookup_mapped(&self, scope: usize, name: &str) -> Option<VariableValue> {
+        let (frame, _) = self.resolve(name)?;
+        if self.cur_frame[frame].scope != scope {
+            return None;
+        }
+        self.get_variable(name).cloned()
+    }
+
+    /// Executes a software interrupt with the current registers.
+    /// Unsupported interrupts & functions set the carry flag.
+    pub(crate) fn dointr(&mut self, int: u8) {
+        let mut regs = self.memory.registers;
+        let supported = match int {
+            0x21 => self.int21(&mut regs),
+            0x16 => self.int16(&mut regs),
+            0x1A => Self::int1a(&mut regs),
+            _ => false,
+        };
+        if !supported {
+            log::warn!(
+                "DOINTR: unsupported interrupt {:02X}h AX={:04X}h",
+                int,
+                regs.ax
+            );
+        }
+        regs.set_flag(CARRY_FLAG, !supported);
+        self.memory.registers = regs;
+    }
+
+    /// DOS services
+    fn int21(&mut self, regs: &mut Registers) -> bool {
+        let now = Local::now();
+        match regs.ah() {
+            // check standard input status
+            0x0B => regs.set_al(if self.key_available() { 0xFF } else { 0 }),
+            // get current drive
+            0x19 => regs.set_al(self.io.get_drive() as u8),
+            // get date
+            0x2A => {
+                regs.cx = now.year() as u16;
+                regs.dx = word(now.month() as u8, now.day() as u8);
+                regs.set_al(now.weekday().num_days_from_sunday() as u8);
+            }
+            // get time
+            0x2C => {
+                regs.cx = word(now.hour() as u8, now.minute() as u8);
+                let hundredths = (now.timestamp_subsec_millis() / 10) as u8;
+                regs.dx = word(now.second() as u8, hundredths);
+            }
+            // get DOS version, 6.22
+            0x30 => {
+                regs.ax = word(22, 6);
+                regs.bx = 0;
+                regs.cx = 0;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// keyboard services
+    fn int16(&mut self, regs: &mut Registers) -> bool {
+        match regs.ah() {
+            // check for keystroke, ZF is set if there is none
+            0x01 | 0x11 => {
+                let available = self.key_available();
+                regs.set_flag(ZERO_FLAG, !available);
+                if !available {
+                    regs.ax = 0;
+                }
+            }
+            // shift flags
+            0x02 | 0x12 => regs.set_al(self.memory.data[KEYBOARD_FLAGS]),
+            _ => return false,
+        }
+        true
+    }
+
+    /// clock services
+    fn int1a(regs: &mut Registers) -> bool {
+        let now = Local::now();
+        match regs.ah() {
+            // timer ticks since midnight
+            0x00 => {
+                let ticks = DosMemory::timer_ticks();
+                regs.cx = (ticks >> 16) as u16;
+                regs.dx = ticks as u16;
+                regs.set_al(0);
+            }
+            // real time clock time (BCD)
+            0x02 => {
+                regs.cx = word(bcd(now.hour()), bcd(now.minute()));
+                regs.dx = word(bcd(now.second()), 0);
+            }
+            // real time clock date (BCD)
+            0x04 => {
+                let year = now.year() as u32;
+                regs.cx = word(bcd(year / 100), bcd(year % 100));
+                regs.dx = word(bcd(now.month()), bcd(now.day()));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn key_available(&mut self) -> bool {
+        let keys = &self.keyboard;
+        !keys.keyboard.is_empty()
+            || !keys.ppl.is_empty()
+            || !keys.file.is_empty()
+            || self.ctx.inbytes() > 0
+    }
+}
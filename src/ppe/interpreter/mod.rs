@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::string::String;
 
 pub mod expressions;
@@ -11,6 +11,8 @@ use crate::data::UserRecord;
 use crate::Res;
 use crate::VT;
 
+use self::errors::IcyError;
+
 pub use self::expressions::*;
 
 pub mod statements;
@@ -19,7 +21,24 @@ pub use self::statements::*;
 pub mod io;
 pub use self::io::*;
 
+pub mod input;
+pub mod keys;
+use self::keys::KeyboardBuffer;
+
+pub mod dbase;
+use self::dbase::Dbase;
+pub mod debugger;
+use self::debugger::Debugger;
 pub mod errors;
+pub mod limits;
+pub use self::limits::ExecutionLimits;
+use self::limits::ExecutionState;
+pub mod memory;
+use self::memory::DosMemory;
+pub mod ndx;
+pub mod share;
+pub mod symbols;
+use self::symbols::{SymbolTable, VarSlot, GLOBAL_SCOPE};
 mod tests;
 
 pub trait ExecutionContext {
@@ -33,31 +52,62 @@ pub trait ExecutionContext {
     fn inbytes(&mut self) -> i32;
     fn set_color(&mut self, color: u8);
 
-    /// simulate user input for later processing
-    fn send_to_com(&mut self, data: &str) -> Res<()>;
+    /// discards pending user input
+    fn flush_input(&mut self) -> Res<()>;
 }
 
 pub struct StackFrame {
-    values: HashMap<String, VariableValue>,
+    /// scope in the symbol table, it resolves the variable slots and labels
+    scope: usize,
+    /// variables by slot, `None` if the variable isn't set in this frame
+    values: Vec<Option<VariableValue>>,
 
     gosub_stack: Vec<usize>,
     cur_ptr: usize,
-    label_table: HashMap<String, usize>,
+    /// RETURN without GOSUB left the procedure/PPE
+    returned: bool,
 }
 
-pub fn calc_table(blk: &Block) -> HashMap<String, usize> {
-    let mut res = HashMap::new();
-    for i in 0..blk.statements.len() {
-        if let Statement::Label(label) = &blk.statements[i] {
-            res.insert(label.clone(), i);
+impl StackFrame {
+    pub fn new(scope: usize, slots: usize) -> Self {
+        Self {
+            scope,
+            values: vec![None; slots],
+            gosub_stack: Vec::new(),
+            cur_ptr: 0,
+            returned: false,
+        }
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&VariableValue> {
+        self.values.get(slot)?.as_ref()
+    }
+
+    fn get_mut(&mut self, slot: usize) -> Option<&mut VariableValue> {
+        self.values.get_mut(slot)?.as_mut()
+    }
+
+    /// The statement & GOSUB depth, a jump or RETURN changes it.
+    fn position(&self) -> (usize, usize, bool) {
+        (self.cur_ptr, self.gosub_stack.len(), self.returned)
+    }
+
+    /// The frame has statements left to run.
+    fn is_running(&self, statements: usize) -> bool {
+        !self.returned && self.cur_ptr < statements
+    }
+
+    fn set(&mut self, slot: usize, value: VariableValue) {
+        if slot >= self.values.len() {
+            self.values.resize(slot + 1, None);
         }
+        self.values[slot] = Some(value);
     }
-    res
 }
 pub struct Interpreter<'a> {
     prg: &'a Program,
     ctx: &'a mut dyn ExecutionContext,
-    // lookup: HashMap<&'a Block, i32>,
+    symbols: SymbolTable,
     cur_frame: Vec<StackFrame>,
     io: &'a mut dyn PCBoardIO,
     pub is_running: bool,
@@ -68,36 +118,163 @@ pub struct Interpreter<'a> {
     pub pcb_node: Option<Node>,
 
     pub cur_tokens: Vec<String>, //  stack_frames: Vec<StackFrame>
+
+    /// files left of the FINDFIRST/FINDNEXT search
+    pub file_search: VecDeque<String>,
+
+    /// FDEFIN/FDEFOUT - channels of the FD* statements
+    pub default_in: usize,
+    pub default_out: usize,
+
+    pub keyboard: KeyboardBuffer,
+
+    /// set by BREAK/CONTINUE until the enclosing loop handles it
+    loop_control: LoopControl,
+
+    /// statement count, idle timer & stack error state for the execution limits
+    state: ExecutionState,
+
+    /// PEEK/POKE memory & DOINTR registers
+    memory: DosMemory,
+
+    /// data files of the D* statements & functions
+    dbase: Dbase,
+
+    /// DBGLEVEL - statements are traced if > 0
+    pub debug_level: i32,
+    debugger: Option<Debugger>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LoopControl {
+    None,
+    Break,
+    Continue,
 }
 
 impl<'a> Interpreter<'a> {
+    /// Returns the next stuffed key or the next key from the user, doesn't block.
+    pub fn get_char(&mut self) -> Res<Option<char>> {
+        if let Some(ch) = self.keyboard.pop_front() {
+            return Ok(Some(ch));
+        }
+        let ch = self.ctx.get_char()?;
+        if ch.is_some() {
+            self.input_received();
+        }
+        Ok(ch)
+    }
+
+    fn frame(&self) -> Res<&StackFrame> {
+        self.cur_frame
+            .last()
+            .ok_or_else(|| Box::new(IcyError::NoStackFrame).into())
+    }
+
+    fn frame_mut(&mut self) -> Res<&mut StackFrame> {
+        self.cur_frame
+            .last_mut()
+            .ok_or_else(|| Box::new(IcyError::NoStackFrame).into())
+    }
+
+    /// Resolves a variable to its frame & slot. Identifiers of the program were resolved when it
+    /// was loaded, other names (debugger, mapped memory) are looked up in the current scope and
+    /// then in the globals.
+    fn resolve(&self, name: &str) -> Option<(usize, usize)> {
+        let cur = self.cur_frame.len().checked_sub(1)?;
+        match self.symbols.variable(name) {
+            Some(VarSlot::Global(slot)) => Some((0, slot)),
+            Some(VarSlot::Local(slot)) => Some((cur, slot)),
+            None => {
+                let scope = self.cur_frame[cur].scope;
+                if let Some(slot) = self.symbols.scope(scope).get_slot(name) {
+                    return Some((cur, slot));
+                }
+                let slot = self.symbols.scope(GLOBAL_SCOPE).get_slot(name)?;
+                Some((0, slot))
+            }
+        }
+    }
+
+    /// Looks up a variable in the current frame and then in the global frame.
+    pub(crate) fn get_variable(&self, name: &str) -> Option<&VariableValue> {
+        let (frame, slot) = self.resolve(name)?;
+        self.cur_frame[frame].get(slot)
+    }
+
+    /// Looks up a variable in the current frame and then in the global frame.
+    pub(crate) fn get_variable_mut(&mut self, name: &str) -> Res<Option<&mut VariableValue>> {
+        let Some((frame, slot)) = self.resolve(name) else {
+            return Ok(None);
+        };
+        Ok(self.cur_frame[frame].get_mut(slot))
+    }
+
+    /// Assigns a variable, converted to its declared type.
+    pub(crate) fn set_variable(&mut self, name: &str, value: VariableValue) -> Res<()> {
+        let Some((frame, slot)) = self.resolve(name) else {
+            return Err(Box::new(IcyError::VariableNotFound(name.to_string())));
+        };
+        self.set_slot(frame, slot, value);
+        Ok(())
+    }
+
+    /// Assigns the variable in `slot` of a frame, converted to its declared type.
+    fn set_slot(&mut self, frame: usize, slot: usize, value: VariableValue) {
+        let var_type = self
+            .symbols
+            .scope(self.cur_frame[frame].scope)
+            .get_type(slot);
+        let value = self.convert_value(var_type, value);
+        self.cur_frame[frame].set(slot, value);
+    }
+
+    /// INC/DEC - adds `delta` to a variable.
+    fn add_to_variable(&mut self, name: &str, delta: i32) -> Res<()> {
+        let Some((frame, slot)) = self.resolve(name) else {
+            return Err(Box::new(IcyError::VariableNotFound(name.to_string())));
+        };
+        let Some(value) = self.cur_frame[frame].get(slot) else {
+            return Err(Box::new(IcyError::VariableNotFound(name.to_string())));
+        };
+        let value = value.clone() + VariableValue::Integer(delta);
+        self.set_slot(frame, slot, value);
+        Ok(())
+    }
+
+    /// Converts a value for an assignment, dates & times assigned to strings are formatted.
+    fn convert_value(&self, var_type: VariableType, value: VariableValue) -> VariableValue {
+        match (var_type, &value) {
+            (VariableType::Unknown, _) => value,
+            (
+                VariableType::String | VariableType::BigStr,
+                VariableValue::Date(_) | VariableValue::EDate(_) | VariableValue::Time(_),
+            ) => VariableValue::String(get_display_string(self, &value)),
+            _ => convert_to(var_type, &value),
+        }
+    }
+
+    /// Sets a variable of the main program.
+    fn set_global(&mut self, name: &str, value: VariableValue) {
+        let slot = self
+            .symbols
+            .scope_mut(GLOBAL_SCOPE)
+            .add_slot(name, VariableType::Unknown);
+        self.cur_frame[0].set(slot, value);
+    }
+
     fn set_user_variables(&mut self, cur_user: &UserRecord) {
-        self.cur_frame[0].values.insert(
-            "self".to_string(),
-            VariableValue::Integer(cur_user.page_len),
-        );
-        self.cur_frame[0].values.insert(
-            "U_PWD".to_string(),
-            VariableValue::String(cur_user.password.clone()),
-        );
-        self.cur_frame[0].values.insert(
-            "U_PWDEXP".to_string(),
-            VariableValue::Date(0000), // TODO
+        self.set_global("self", VariableValue::Integer(cur_user.page_len));
+        self.set_global("U_PWD", VariableValue::String(cur_user.password.clone()));
+        self.set_global(
+            "U_PWDEXP",
+            VariableValue::Date(cur_user.password_expire_date),
         );
-        self.cur_frame[0].values.insert(
-            "U_SCROLL".to_string(),
-            VariableValue::Boolean(cur_user.scroll_flag),
-        );
-        self.cur_frame[0].values.insert(
-            "U_SEC".to_string(),
-            VariableValue::Integer(cur_user.security_level),
-        );
-        self.cur_frame[0].values.insert(
-            "U_CITY".to_string(),
-            VariableValue::String(cur_user.city.clone()),
-        );
-        self.cur_frame[0].values.insert(
-            "U_ADDR".to_string(),
+        self.set_global("U_SCROLL", VariableValue::Boolean(cur_user.scroll_flag));
+        self.set_global("U_SEC", VariableValue::Integer(cur_user.security_level));
+        self.set_global("U_CITY", VariableValue::String(cur_user.city.clone()));
+        self.set_global(
+            "U_ADDR",
             VariableValue::Dim1(
                 VariableType::String,
                 vec![
@@ -113,350 +290,593 @@ impl<'a> Interpreter<'a> {
     }
 }
 
-pub fn create_array(
-    interpreter: &mut Interpreter,
-    var_type: VariableType,
-    var_info: &VarInfo,
-) -> Res<VariableValue> {
-    match var_info {
-        VarInfo::Var0(_) => panic!(""),
-        VarInfo::Var1(_, vec) => {
-            let dim = get_int(&evaluate_exp(interpreter, vec)?)?;
-            let mut v = Vec::new();
-            v.resize(dim as usize, VariableValue::Integer(0));
-            Ok(VariableValue::Dim1(var_type, v))
-        }
-        VarInfo::Var2(_, _, _) => Ok(VariableValue::Dim2(var_type, Vec::new())),
-        VarInfo::Var3(_, _, _, _) => Ok(VariableValue::Dim3(var_type, Vec::new())),
+/// Returns the initial value of a variable of type `var_type`.
+pub fn default_value(var_type: VariableType) -> Res<VariableValue> {
+    Ok(match var_type {
+        VariableType::Integer => VariableValue::Integer(0),
+        VariableType::String => VariableValue::String("".to_string()),
+        VariableType::Boolean => VariableValue::Boolean(false),
+        VariableType::Date => VariableValue::Date(0),
+
+        VariableType::Unsigned => VariableValue::Unsigned(0),
+        VariableType::EDate => VariableValue::EDate(0),
+        VariableType::Money => VariableValue::Money(0.0),
+        VariableType::Real => VariableValue::Real(0.0),
+        VariableType::Time => VariableValue::Time(0),
+        VariableType::Byte => VariableValue::Byte(0),
+        VariableType::Word => VariableValue::Word(0),
+        VariableType::SByte => VariableValue::SByte(0),
+        VariableType::SWord => VariableValue::SWord(0),
+        VariableType::BigStr => VariableValue::String("".to_string()),
+        VariableType::Double => VariableValue::Real(0.0),
+        // DDATE has no value of its own, assigned DDATEs are converted to dates as well
+        VariableType::DDate => VariableValue::Date(0),
+        VariableType::Function | VariableType::Procedure | VariableType::Unknown => {
+            return Err(Box::new(IcyError::UnsupportedVariableType(format!(
+                "{:?}",
+                var_type
+            ))));
+        }
+    })
+}
+
+/// Evaluates the array dimensions/indices of a variable reference.
+pub fn get_indices(interpreter: &mut Interpreter, var_info: &VarInfo) -> Res<Vec<i32>> {
+    let exprs = match var_info {
+        VarInfo::Var0(_) => vec![],
+        VarInfo::Var1(_, d1) => vec![d1],
+        VarInfo::Var2(_, d1, d2) => vec![d1, d2],
+        VarInfo::Var3(_, d1, d2, d3) => vec![d1, d2, d3],
+    };
+    let mut res = Vec::new();
+    for expr in exprs {
+        res.push(get_int(&evaluate_exp(interpreter, expr)?)?);
+    }
+    Ok(res)
+}
+
+/// Creates an array, PPL arrays are 0 based and the declared dimension is the upper bound.
+pub fn create_array(var_type: VariableType, dims: &[i32]) -> Res<VariableValue> {
+    let value = default_value(var_type)?;
+    let size = |i: usize| dims[i].max(0) as usize + 1;
+    match dims.len() {
+        1 => Ok(VariableValue::Dim1(var_type, vec![value; size(0)])),
+        2 => Ok(VariableValue::Dim2(
+            var_type,
+            vec![vec![value; size(1)]; size(0)],
+        )),
+        3 => Ok(VariableValue::Dim3(
+            var_type,
+            vec![vec![vec![value; size(2)]; size(1)]; size(0)],
+        )),
+        _ => Err(Box::new(IcyError::NotSupported)),
     }
 }
 
-pub fn set_array_value(
-    arr: &mut VariableValue,
-    var_info: &VarInfo,
-    val: VariableValue,
-    dim1: usize,
-) {
-    match var_info {
-        VarInfo::Var0(_) => panic!(""),
-        VarInfo::Var1(_, _) => {
-            if let VariableValue::Dim1(_, data) = arr {
-                data[dim1] = val;
+/// Resizes an array to the new dimensions, the contents are preserved.
+pub fn redim_array(arr: &mut VariableValue, dims: &[i32]) -> Res<()> {
+    let size = |i: usize| dims[i].max(0) as usize + 1;
+    match (arr, dims.len()) {
+        (VariableValue::Dim1(var_type, data), 1) => {
+            data.resize(size(0), default_value(*var_type)?);
+        }
+        (VariableValue::Dim2(var_type, data), 2) => {
+            let value = default_value(*var_type)?;
+            data.resize(size(0), Vec::new());
+            for row in data {
+                row.resize(size(1), value.clone());
             }
         }
-        VarInfo::Var2(_, _, _) => todo!(),
-        VarInfo::Var3(_, _, _, _) => todo!(),
+        (VariableValue::Dim3(var_type, data), 3) => {
+            let value = default_value(*var_type)?;
+            data.resize(size(0), Vec::new());
+            for plane in data {
+                plane.resize(size(1), Vec::new());
+                for row in plane {
+                    row.resize(size(2), value.clone());
+                }
+            }
+        }
+        (arr, _) => {
+            return Err(Box::new(IcyError::ArrayExpected(format!("{:?}", arr))));
+        }
     }
+    Ok(())
 }
 
-pub fn get_first_index(var_info: &VarInfo) -> &Expression {
-    match var_info {
-        VarInfo::Var1(_, v) => v,
-        VarInfo::Var2(_, v, _) => v,
-        VarInfo::Var3(_, v, _, _) => v,
-        _ => panic!(""),
+fn check_index(index: i32, len: usize) -> Res<usize> {
+    if index < 0 || index as usize >= len {
+        return Err(Box::new(IcyError::ArrayIndexOutOfBounds(index, len)));
     }
+    Ok(index as usize)
 }
 
+pub fn get_array_value(arr: &VariableValue, indices: &[i32]) -> Res<VariableValue> {
+    match (arr, indices) {
+        (VariableValue::Dim1(_, data), [i]) => Ok(data[check_index(*i, data.len())?].clone()),
+        (VariableValue::Dim2(_, data), [i, j]) => {
+            let row = &data[check_index(*i, data.len())?];
+            Ok(row[check_index(*j, row.len())?].clone())
+        }
+        (VariableValue::Dim3(_, data), [i, j, k]) => {
+            let plane = &data[check_index(*i, data.len())?];
+            let row = &plane[check_index(*j, plane.len())?];
+            Ok(row[check_index(*k, row.len())?].clone())
+        }
+        _ => Err(Box::new(IcyError::ArrayExpected(format!("{:?}", arr)))),
+    }
+}
+
+pub fn set_array_value(arr: &mut VariableValue, indices: &[i32], val: VariableValue) -> Res<()> {
+    match (arr, indices) {
+        (VariableValue::Dim1(var_type, data), [i]) => {
+            let i = check_index(*i, data.len())?;
+            data[i] = convert_to(*var_type, &val);
+        }
+        (VariableValue::Dim2(var_type, data), [i, j]) => {
+            let i = check_index(*i, data.len())?;
+            let j = check_index(*j, data[i].len())?;
+            data[i][j] = convert_to(*var_type, &val);
+        }
+        (VariableValue::Dim3(var_type, data), [i, j, k]) => {
+            let i = check_index(*i, data.len())?;
+            let j = check_index(*j, data[i].len())?;
+            let k = check_index(*k, data[i][j].len())?;
+            data[i][j][k] = convert_to(*var_type, &val);
+        }
+        (arr, _) => return Err(Box::new(IcyError::ArrayExpected(format!("{:?}", arr)))),
+    }
+    Ok(())
+}
+
+/// Executes a statement, errors are reported with the statement they happened in.
 fn execute_statement(interpreter: &mut Interpreter, stmt: &Statement) -> Res<()> {
+    run_statement(interpreter, stmt).map_err(|err| runtime_error(interpreter.prg, stmt, err))
+}
+
+/// Wraps an error in a `RuntimeError` of `stmt`, errors of nested statements already are.
+fn runtime_error(
+    prg: &Program,
+    stmt: &Statement,
+    err: Box<dyn std::error::Error>,
+) -> Box<dyn std::error::Error> {
+    if let Some(IcyError::RuntimeError { .. }) = err.downcast_ref::<IcyError>() {
+        return err;
+    }
+    Box::new(IcyError::RuntimeError {
+        file: prg.file_name.display().to_string(),
+        statement: format!("{:?}", stmt),
+        error: err.to_string(),
+    })
+}
+
+fn run_statement(interpreter: &mut Interpreter, stmt: &Statement) -> Res<()> {
+    interpreter.check_limits()?;
     match stmt {
         Statement::Let(variable, expr) => {
             let value: VariableValue = evaluate_exp(interpreter, expr)?;
-            let var_name = variable.get_name().clone();
-            let var_type = interpreter.prg.get_var_type(&var_name);
-
-            if let Some(var_info) = interpreter.prg.get_var_info(&var_name) {
-                if var_info.is_array() {
-                    let dim1 = &evaluate_exp(interpreter, get_first_index(variable))?;
-                    let val = match interpreter
-                        .cur_frame
-                        .last_mut()
-                        .unwrap()
-                        .values
-                        .get_mut(&var_name)
-                    {
-                        Some(val) => val,
-                        None => {
-                            let arr = create_array(interpreter, var_type, var_info)?;
-                            interpreter
-                                .cur_frame
-                                .last_mut()
-                                .unwrap()
-                                .values
-                                .insert(var_name.clone(), arr);
-                            interpreter
-                                .cur_frame
-                                .last_mut()
-                                .unwrap()
-                                .values
-                                .get_mut(&var_name)
-                                .unwrap()
-                        }
-                    };
-
-                    set_array_value(val, var_info, value, get_int(dim1)? as usize - 1);
-                } else {
-                    interpreter
-                        .cur_frame
-                        .last_mut()
-                        .unwrap()
-                        .values
-                        .insert(var_name, convert_to(var_type, &value));
-                }
+            let var_name = variable.get_name();
+            if variable.is_array() {
+                let indices = get_indices(interpreter, variable)?;
+                let Some(arr) = interpreter.get_variable_mut(var_name)? else {
+                    return Err(Box::new(IcyError::VariableNotFound(var_name.clone())));
+                };
+                set_array_value(arr, &indices, value)?;
             } else {
-                interpreter
-                    .cur_frame
-                    .last_mut()
-                    .unwrap()
-                    .values
-                    .insert(var_name, value);
+                interpreter.set_variable(var_name, value)?;
             }
         }
         Statement::Goto(label) => {
-            if let Some(frame) = interpreter.cur_frame.last_mut() {
-                let Some(label_ptr) = frame.label_table.get(label) else {
-                    panic!("label not found {}", label);
-                };
-                frame.cur_ptr = *label_ptr;
-            }
+            let scope = interpreter.frame()?.scope;
+            let Some(&label_ptr) = interpreter.symbols.scope(scope).label_table.get(label) else {
+                return Err(Box::new(IcyError::LabelNotFound(label.clone())));
+            };
+            let frame = interpreter.frame_mut()?;
+            frame.cur_ptr = label_ptr;
         }
 
         Statement::Gosub(label) => {
-            if let Some(frame) = interpreter.cur_frame.last_mut() {
-                let Some(label_ptr) = frame.label_table.get(label) else {
-                    panic!("label not found {}", label);
-                };
-                frame.gosub_stack.push(frame.cur_ptr);
-                frame.cur_ptr = *label_ptr;
+            if !interpreter.check_stack()? {
+                return Ok(());
             }
+            let scope = interpreter.frame()?.scope;
+            let Some(&label_ptr) = interpreter.symbols.scope(scope).label_table.get(label) else {
+                return Err(Box::new(IcyError::LabelNotFound(label.clone())));
+            };
+            let frame = interpreter.frame_mut()?;
+            frame.gosub_stack.push(frame.cur_ptr);
+            frame.cur_ptr = label_ptr;
+            execute_subroutine(interpreter)?;
         }
         Statement::Return => {
-            //let table = &interpreter.label_tables[interpreter.cur_frame.last().unwrap().label_table as usize];
-            interpreter.cur_frame.last_mut().unwrap().cur_ptr = interpreter
-                .cur_frame
-                .last_mut()
-                .unwrap()
-                .gosub_stack
-                .pop()
-                .unwrap();
+            //let table = &interpreter.label_tables[interpreter.frame()?.label_table as usize];
+            let frame = interpreter.frame_mut()?;
+            // RETURN without GOSUB leaves the procedure/PPE like PCBoard does
+            match frame.gosub_stack.pop() {
+                Some(ptr) => frame.cur_ptr = ptr,
+                None => frame.returned = true,
+            }
         }
 
         Statement::Call(def, params) => {
             call_predefined_procedure(interpreter, def, params)?;
         }
         Statement::ProcedureCall(name, parameters) => {
-            let mut found = false;
-            for f in &interpreter.prg.procedure_implementations {
-                if let Declaration::Procedure(pname, params) = &f.declaration {
-                    if name != pname {
-                        continue;
-                    }
-                    let label_table = calc_table(&f.block);
-                    let mut prg_frame = StackFrame {
-                        values: HashMap::new(),
-                        gosub_stack: Vec::new(),
-                        cur_ptr: 0,
-                        label_table,
-                    };
-
-                    for i in 0..parameters.len() {
-                        if let Declaration::Variable(var_type, infos) = &params[i] {
-                            let value = evaluate_exp(interpreter, &parameters[i])?;
-                            println!(
-                                "Insert {} to :{}",
-                                infos[0].get_name(),
-                                convert_to(*var_type, &value)
-                            );
-                            prg_frame
-                                .values
-                                .insert(infos[0].get_name().clone(), convert_to(*var_type, &value));
-                        } else {
-                            panic!("invalid parameter declaration {:?}", params[i]);
-                        }
-                    }
-                    println!("push frame!");
-                    interpreter.cur_frame.push(prg_frame);
-
-                    while interpreter.cur_frame.last().unwrap().cur_ptr < f.block.statements.len() {
-                        let stmt =
-                            &f.block.statements[interpreter.cur_frame.last().unwrap().cur_ptr];
-                        execute_statement(interpreter, stmt)?;
-                        interpreter.cur_frame.last_mut().unwrap().cur_ptr += 1;
-                    }
-                    interpreter.cur_frame.pop();
-                    println!("pop frame!");
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                panic!("procedure not found {}", name);
-            }
+            let Some((index, scope)) = interpreter.symbols.get_procedure(name) else {
+                return Err(Box::new(IcyError::ProcedureNotFound(name.clone())));
+            };
+            let f = &interpreter.prg.procedure_implementations[index];
+            let Declaration::Procedure(_, params) = &f.declaration else {
+                return Err(Box::new(IcyError::ProcedureNotFound(name.clone())));
+            };
+            call_implementation(interpreter, f, scope, params, parameters)?;
         }
 
         Statement::End => {
-            interpreter.cur_frame.last_mut().unwrap().cur_ptr = usize::MAX - 1;
+            interpreter.is_running = false;
         }
         Statement::If(cond, statement) => {
-            let value = evaluate_exp(interpreter, cond)?;
-            if let VariableValue::Integer(x) = value {
-                if x == PPL_TRUE {
-                    execute_statement(interpreter, statement)?;
-                }
-            } else if let VariableValue::Boolean(x) = value {
-                if x {
-                    execute_statement(interpreter, statement)?;
-                }
-            } else {
-                panic!("no bool value {:?}", value);
+            if is_true(&evaluate_exp(interpreter, cond)?)? {
+                execute_statement(interpreter, statement)?;
             }
         }
 
-        Statement::Inc(expr) => {
-            let new_value = evaluate_exp(interpreter, &Expression::Identifier(expr.clone()))?
-                + VariableValue::Integer(1);
-            interpreter
-                .cur_frame
-                .last_mut()
-                .unwrap()
-                .values
-                .insert(expr.to_string(), new_value);
+        Statement::Inc(name) => {
+            interpreter.add_to_variable(name, 1)?;
         }
 
-        Statement::Dec(expr) => {
-            let new_value = evaluate_exp(interpreter, &Expression::Identifier(expr.clone()))?
-                + VariableValue::Integer(-1);
-            interpreter
-                .cur_frame
-                .last_mut()
-                .unwrap()
-                .values
-                .insert(expr.to_string(), new_value);
+        Statement::Dec(name) => {
+            interpreter.add_to_variable(name, -1)?;
         }
 
-        /* unsupported - the compiler does not generate them and ast transformation should remove them */
+        /* structured statements - the compiler does not generate them but the parser does */
         Statement::Continue => {
-            panic!("unsupported statement Continue")
+            interpreter.loop_control = LoopControl::Continue;
         }
         Statement::Break => {
-            panic!("unsupported statement Break")
+            interpreter.loop_control = LoopControl::Break;
         }
-        Statement::For(_, _, _, _, _) => {
-            panic!("unsupported statement For")
+        Statement::For(var, from, to, step, block) => {
+            let Expression::Identifier(var_name) = var.as_ref() else {
+                return Err(Box::new(IcyError::VariableNotFound(format!("{:?}", var))));
+            };
+            let mut value = evaluate_exp(interpreter, from)?;
+            loop {
+                interpreter.set_variable(var_name, value)?;
+
+                let step = match step {
+                    Some(step) => get_int(&evaluate_exp(interpreter, step)?)?,
+                    None => 1,
+                };
+                let end = evaluate_exp(interpreter, to)?;
+                let cur = evaluate_exp(interpreter, var)?;
+                let done = if step < 0 { cur < end } else { cur > end };
+                if done || !execute_loop_body(interpreter, block)? {
+                    break;
+                }
+                value = evaluate_exp(interpreter, var)? + VariableValue::Integer(step);
+            }
         }
-        Statement::DoWhile(_, _) => {
-            panic!("unsupported statement DoWhile")
+        Statement::DoWhile(cond, block) => {
+            while is_true(&evaluate_exp(interpreter, cond)?)? {
+                if !execute_loop_body(interpreter, block)? {
+                    break;
+                }
+            }
         }
-        Statement::IfThen(_, _, _, _) => {
-            panic!("unsupported statement IfThen")
+        Statement::While(cond, statement) => {
+            while is_true(&evaluate_exp(interpreter, cond)?)? {
+                if !execute_loop_body(interpreter, std::slice::from_ref(statement.as_ref()))? {
+                    break;
+                }
+            }
         }
-        Statement::Block(_) => {
-            panic!("unsupported statement Begin…End block")
+        Statement::IfThen(cond, block, else_if_blocks, else_block) => {
+            if is_true(&evaluate_exp(interpreter, cond)?)? {
+                execute_block(interpreter, block)?;
+            } else {
+                let mut executed = false;
+                for (cond, block) in else_if_blocks {
+                    if is_true(&evaluate_exp(interpreter, cond)?)? {
+                        execute_block(interpreter, block)?;
+                        executed = true;
+                        break;
+                    }
+                }
+                if !executed {
+                    if let Some(block) = else_block {
+                        execute_block(interpreter, block)?;
+                    }
+                }
+            }
+        }
+        Statement::Block(block) => {
+            execute_block(interpreter, block)?;
         }
 
         // nop statements
         Statement::Label(_) | Statement::Comment(_) => { /* skip */ }
 
         _ => {
-            panic!("unsupported statement {:?}", stmt);
+            return Err(Box::new(IcyError::UnsupportedStatement(format!(
+                "{:?}",
+                stmt
+            ))));
+        }
+    }
+    Ok(())
+}
+
+/// Frame & slot a VAR parameter gets written back to after the call.
+enum VarTarget {
+    Variable(usize, usize),
+    ArrayElement(usize, usize, Vec<i32>),
+}
+
+/// Calls a user defined procedure or function and returns its stack frame.
+/// Parameters are passed by value, VAR parameters are written back to the caller's variables.
+pub fn call_implementation(
+    interpreter: &mut Interpreter,
+    f: &Implementation,
+    scope: usize,
+    params: &[Declaration],
+    arguments: &[Expression],
+) -> Res<StackFrame> {
+    if !interpreter.check_stack()? {
+        // the call is skipped, functions return the default value
+        return Ok(StackFrame::new(scope, 0));
+    }
+    let mut prg_frame = StackFrame::new(scope, interpreter.symbols.scope(scope).len());
+
+    // parameters occupy the first slots of the scope
+    let mut var_targets = Vec::new();
+    for (slot, (param, argument)) in params.iter().zip(arguments).enumerate() {
+        let var_type = match param {
+            Declaration::Variable(var_type, _) => var_type,
+            Declaration::VarParameter(var_type, _) => {
+                let target = match argument {
+                    Expression::Identifier(name) => interpreter
+                        .resolve(name)
+                        .map(|(frame, slot)| VarTarget::Variable(frame, slot)),
+                    Expression::FunctionCall(name, indices) => {
+                        let mut res = Vec::new();
+                        for expr in indices {
+                            res.push(get_int(&evaluate_exp(interpreter, expr)?)?);
+                        }
+                        interpreter
+                            .resolve(name)
+                            .map(|(frame, slot)| VarTarget::ArrayElement(frame, slot, res))
+                    }
+                    _ => None,
+                };
+                if let Some(target) = target {
+                    var_targets.push((slot, target));
+                }
+                var_type
+            }
+            _ => {
+                return Err(Box::new(IcyError::InvalidParameterDeclaration(format!(
+                    "{:?}",
+                    param
+                ))));
+            }
+        };
+        let value = evaluate_exp(interpreter, argument)?;
+        prg_frame.set(slot, convert_to(*var_type, &value));
+    }
+
+    interpreter.cur_frame.push(prg_frame);
+    let mut result = init_locals(interpreter, f, scope);
+    while result.is_ok()
+        && interpreter.is_running
+        && interpreter.frame()?.is_running(f.block.statements.len())
+    {
+        let stmt = &f.block.statements[interpreter.frame()?.cur_ptr];
+        result = execute_top_level_statement(interpreter, stmt);
+        if result.is_err() {
+            break;
+        }
+        interpreter.frame_mut()?.cur_ptr += 1;
+    }
+    let Some(prg_frame) = interpreter.cur_frame.pop() else {
+        return Err(Box::new(IcyError::NoStackFrame));
+    };
+    result?;
+
+    for (slot, target) in var_targets {
+        let Some(value) = prg_frame.get(slot).cloned() else {
+            continue;
+        };
+        match target {
+            VarTarget::Variable(frame, slot) => interpreter.set_slot(frame, slot, value),
+            VarTarget::ArrayElement(frame, slot, indices) => {
+                let Some(arr) = interpreter.cur_frame[frame].get_mut(slot) else {
+                    continue;
+                };
+                set_array_value(arr, &indices, value)?;
+            }
+        }
+    }
+    Ok(prg_frame)
+}
+
+/// Creates the declared local variables of a procedure/function in the current frame,
+/// every call gets fresh ones. Array dimensions may use the parameters.
+fn init_locals(interpreter: &mut Interpreter, f: &Implementation, scope: usize) -> Res<()> {
+    for decl in &f.variable_declarations {
+        if let Declaration::Variable(var_type, infos) = decl {
+            for var_info in infos {
+                let value = if var_info.is_array() {
+                    let dims = get_indices(interpreter, var_info)?;
+                    create_array(*var_type, &dims)?
+                } else {
+                    default_value(*var_type)?
+                };
+                if let Some(slot) = interpreter
+                    .symbols
+                    .scope(scope)
+                    .get_slot(var_info.get_name())
+                {
+                    interpreter.frame_mut()?.set(slot, value);
+                }
+            }
         }
     }
     Ok(())
 }
 
+/// Executes a statement of the main program or a procedure/function body,
+/// these are the statements the debugger can stop at.
+fn execute_top_level_statement(interpreter: &mut Interpreter, stmt: &Statement) -> Res<()> {
+    if debugger::before_statement(interpreter, stmt)? {
+        execute_statement(interpreter, stmt)?;
+    }
+    Ok(())
+}
+
+/// PPL truth value of a condition
+pub(crate) fn is_true(value: &VariableValue) -> Res<bool> {
+    match value {
+        VariableValue::Integer(x) => Ok(*x == PPL_TRUE),
+        VariableValue::Boolean(x) => Ok(*x),
+        _ => Err(Box::new(IcyError::BoolExpected(format!("{:?}", value)))),
+    }
+}
+
+/// Executes a GOSUB subroutine up to its RETURN. It runs in place, so a GOSUB inside a block
+/// continues with the next statement of the block. GOTO stays in the subroutine, END stops the PPE.
+fn execute_subroutine(interpreter: &mut Interpreter) -> Res<()> {
+    let frame = interpreter.frame()?;
+    let depth = frame.gosub_stack.len();
+    let block = &interpreter
+        .symbols
+        .block(interpreter.prg, frame.scope)
+        .statements;
+    while interpreter.is_running && interpreter.frame()?.is_running(block.len()) {
+        execute_top_level_statement(interpreter, &block[interpreter.frame()?.cur_ptr])?;
+        let frame = interpreter.frame_mut()?;
+        if frame.gosub_stack.len() < depth {
+            break;
+        }
+        frame.cur_ptr += 1;
+    }
+    Ok(())
+}
+
+/// Executes the statements of a nested block (IF…ENDIF, loop bodies, BEGIN…END).
+/// Stops early on BREAK/CONTINUE or when a statement changed the control flow (GOTO, RETURN, END).
+fn execute_block(interpreter: &mut Interpreter, block: &[Statement]) -> Res<()> {
+    let position = interpreter.frame()?.position();
+    for stmt in block {
+        execute_statement(interpreter, stmt)?;
+        if !interpreter.is_running
+            || interpreter.loop_control != LoopControl::None
+            || interpreter.frame()?.position() != position
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Executes one loop iteration, returns false if the loop needs to be left.
+fn execute_loop_body(interpreter: &mut Interpreter, block: &[Statement]) -> Res<bool> {
+    let position = interpreter.frame()?.position();
+    execute_block(interpreter, block)?;
+    let control = interpreter.loop_control;
+    interpreter.loop_control = LoopControl::None;
+    Ok(control != LoopControl::Break
+        && interpreter.is_running
+        && interpreter.frame()?.position() == position)
+}
+
 pub fn run(
     prg: &Program,
     ctx: &mut dyn ExecutionContext,
     io: &mut dyn PCBoardIO,
     pcb_data: &IcyBoardData,
 ) -> Res<bool> {
-    let label_table = calc_table(&prg.main_block);
-    let mut cur_frame = StackFrame {
-        values: HashMap::new(),
-        gosub_stack: Vec::new(),
-        cur_ptr: 0,
-        label_table,
-    };
+    run_program(prg, ctx, io, pcb_data, None)
+}
 
-    for decl in &prg.declarations {
-        if let Declaration::Variable(var_type, name) = decl {
-            let var = match var_type {
-                VariableType::Integer => VariableValue::Integer(0),
-                VariableType::String => VariableValue::String("".to_string()),
-                VariableType::Boolean => VariableValue::Boolean(false),
-                VariableType::Date => VariableValue::Date(0),
-
-                VariableType::Unsigned => VariableValue::Unsigned(0),
-                VariableType::EDate => VariableValue::Date(0),
-                VariableType::Money => VariableValue::Money(0.0),
-                VariableType::Real => VariableValue::Real(0.0),
-                VariableType::Time => VariableValue::Time(0),
-                VariableType::Byte => VariableValue::Byte(0),
-                VariableType::Word => VariableValue::Word(0),
-                VariableType::SByte => VariableValue::SByte(0),
-                VariableType::SWord => VariableValue::SWord(0),
-                VariableType::BigStr => VariableValue::String("".to_string()),
-                VariableType::Double => VariableValue::Real(0.0),
-                VariableType::Function => todo!(),
-                VariableType::Procedure => todo!(),
-                VariableType::DDate => VariableValue::Date(0),
-                VariableType::Unknown => todo!(),
-            };
-            for name in name {
-                match name {
-                    VarInfo::Var0(name) => {
-                        cur_frame.values.insert(name.clone(), var.clone());
-                    }
-                    VarInfo::Var1(name, _expression) => {
-                        cur_frame.values.insert(
-                            name.clone(),
-                            VariableValue::Dim1(*var_type, vec![var.clone(); 50]),
-                        );
-                    }
-                    VarInfo::Var2(name, _expression1, _expression2) => {
-                        cur_frame
-                            .values
-                            .insert(name.clone(), VariableValue::Dim2(*var_type, Vec::new()));
-                    }
-                    VarInfo::Var3(name, _expression1, _expression2, _expression3) => {
-                        cur_frame
-                            .values
-                            .insert(name.clone(), VariableValue::Dim3(*var_type, Vec::new()));
-                    }
-                }
-            }
-        }
-    }
+/// Runs the program under control of a debugger, see [`debugger`] for the protocol.
+pub fn run_with_debugger(
+    prg: &Program,
+    ctx: &mut dyn ExecutionContext,
+    io: &mut dyn PCBoardIO,
+    pcb_data: &IcyBoardData,
+    debugger: Debugger,
+) -> Res<bool> {
+    run_program(prg, ctx, io, pcb_data, Some(debugger))
+}
+
+fn run_program(
+    prg: &Program,
+    ctx: &mut dyn ExecutionContext,
+    io: &mut dyn PCBoardIO,
+    pcb_data: &IcyBoardData,
+    debugger: Option<Debugger>,
+) -> Res<bool> {
+    let symbols = SymbolTable::new(prg);
+    let cur_frame = StackFrame::new(GLOBAL_SCOPE, symbols.scope(GLOBAL_SCOPE).len());
 
     let mut interpreter = Interpreter {
         prg,
         ctx,
-        // lookup: HashMap::new(),
+        symbols,
         cur_frame: vec![cur_frame],
         io,
         is_running: true,
         cur_tokens: Vec::new(),
+        file_search: VecDeque::new(),
+        default_in: 1,
+        default_out: 1,
         icb_data: pcb_data.clone(),
         cur_user: 0,
         current_user: None,
         pcb_node: None,
-        //  stack_frames: vec![]
+        keyboard: KeyboardBuffer::default(),
+        loop_control: LoopControl::None,
+        state: ExecutionState::default(),
+        memory: DosMemory::default(),
+        dbase: Dbase::default(),
+        debug_level: 0,
+        debugger,
     };
+    for decl in &prg.declarations {
+        if let Declaration::Variable(var_type, infos) = decl {
+            for var_info in infos {
+                let value = if var_info.is_array() {
+                    let dims = get_indices(&mut interpreter, var_info)?;
+                    create_array(*var_type, &dims)?
+                } else {
+                    default_value(*var_type)?
+                };
+                interpreter.set_global(var_info.get_name(), value);
+            }
+        }
+    }
     interpreter.set_user_variables(&UserRecord::default());
 
     while interpreter.is_running
-        && interpreter.cur_frame.last().unwrap().cur_ptr < prg.main_block.statements.len()
+        && interpreter
+            .frame()?
+            .is_running(prg.main_block.statements.len())
     {
-        let stmt = &prg.main_block.statements[interpreter.cur_frame.last().unwrap().cur_ptr];
-        match execute_statement(&mut interpreter, stmt) {
-            Ok(_) => {}
-            Err(err) => {
-                println!("error executing {:?} : {}", stmt, err);
-                break;
+        let stmt = &prg.main_block.statements[interpreter.frame()?.cur_ptr];
+        if let Err(err) = execute_top_level_statement(&mut interpreter, stmt) {
+            let err = runtime_error(prg, stmt, err);
+            log::error!("{}", err);
+            if let Some(debugger) = &mut interpreter.debugger {
+                debugger.terminated(Some(&err.to_string()))?;
             }
+            return Err(err);
         }
 
-        interpreter.cur_frame.last_mut().unwrap().cur_ptr += 1;
+        interpreter.frame_mut()?.cur_ptr += 1;
+    }
+    if let Some(debugger) = &mut interpreter.debugger {
+        debugger.terminated(None)?;
     }
     Ok(true)
 }
@@ -0,0 +1,673 @@
+//! dBase III NDX index files for DNCREATE/DNOPEN/DTAG/DSEEK.
+//!
+//! An NDX file is a B-tree of 512 byte pages, page 0 is the header with the root page & the key
+//! expression. A node starts with its key count followed by entries of a child page, a record
+//! number & the key. Interior nodes have one child more than keys, each key is the greatest key of
+//! the child before it. Character keys are compared as bytes, numeric & date keys are doubles
+//! (dates as julian day numbers).
+//!
+//! Entries are ordered by key and record number. Removed keys leave their pages in place, the keys
+//! of the interior nodes stay upper bounds of their children so searches continue in the next
+//! child if one runs empty. Each NDX file is a tag named like the file, MDX files aren't supported.
+use std::{cmp::Ordering, thread, time::Duration};
+
+use ppl_engine::ast::VariableValue;
+
+use super::{
+    dbase::{Field, E4_FIELD_NAME, E4_LOCK, E4_READ, E4_WRITE},
+    get_string, DataFile,
+};
+use crate::pcb_date::{DateFormat, PcbDate};
+
+pub const E4_INDEX: i32 = -310;
+pub const E4_TAG_NAME: i32 = -330;
+
+const PAGE_SIZE: usize = 512;
+const MAX_KEY_LEN: usize = 100;
+const EXPRESSION_LEN: usize = PAGE_SIZE - 24;
+
+/// Byte locked while a node reads or changes the index.
+const LOCK_OFFSET: u64 = 1_000_000_000;
+/// Index locks are held only for a single operation, so other nodes are waited for.
+const LOCK_ATTEMPTS: usize = 50;
+const LOCK_DELAY: Duration = Duration::from_millis(20);
+
+/// Field references of a key expression like "UPPER(NAME)+DTOS(BORN)".
+#[derive(Clone, Debug, PartialEq)]
+enum KeyPart {
+    Field(usize),
+    Upper(usize),
+}
+
+/// An index entry, `child` is 0 in leaf nodes.
+#[derive(Clone, Debug)]
+struct Entry {
+    child: u32,
+    recno: u32,
+    key: Vec<u8>,
+}
+
+struct Node {
+    entries: Vec<Entry>,
+    /// the child after the last key of an interior node
+    last: Option<u32>,
+}
+
+impl Node {
+    fn child(&self, i: usize) -> u32 {
+        match self.entries.get(i) {
+            Some(entry) => entry.child,
+            None => self.last.unwrap_or_default(),
+        }
+    }
+}
+
+/// An open NDX file.
+pub struct Index {
+    file: Box<dyn DataFile>,
+    pub tag: String,
+    parts: Vec<KeyPart>,
+    numeric: bool,
+    key_len: usize,
+    entry_len: usize,
+    exclusive: bool,
+    root: u32,
+    pages: u32,
+}
+
+impl Index {
+    /// Creates an index of the records, which are (record number, record).
+    pub fn create(
+        file: Box<dyn DataFile>,
+        tag: String,
+        expression: &str,
+        fields: &[Field],
+        exclusive: bool,
+        records: &[(u32, Vec<u8>)],
+    ) -> Result<Self, i32> {
+        let (parts, numeric) = parse_expression(expression, fields)?;
+        let mut index = Index::new(file, tag, parts, numeric, fields, exclusive)?;
+        let mut header = vec![0; PAGE_SIZE];
+        header[12..14].copy_from_slice(&(index.key_len as u16).to_le_bytes());
+        header[14..16].copy_from_slice(&(index.max_keys() as u16).to_le_bytes());
+        header[16..18].copy_from_slice(&(numeric as u16).to_le_bytes());
+        header[18..20].copy_from_slice(&(index.entry_len as u16).to_le_bytes());
+        let expression = expression.trim().to_uppercase();
+        let len = expression.len().min(EXPRESSION_LEN - 1);
+        header[24..24 + len].copy_from_slice(&expression.as_bytes()[..len]);
+        index.write(0, &header)?;
+        index.rebuild(fields, records)?;
+        Ok(index)
+    }
+
+    pub fn open(
+        file: Box<dyn DataFile>,
+        tag: String,
+        fields: &[Field],
+        exclusive: bool,
+    ) -> Result<Self, i32> {
+        let mut file = file;
+        let header = file.read_at(0, PAGE_SIZE).map_err(|_| E4_READ)?;
+        if header.len() < PAGE_SIZE {
+            return Err(E4_INDEX);
+        }
+        let expression: String = header[24..]
+            .iter()
+            .take_while(|b| **b != 0)
+            .map(|b| *b as char)
+            .collect();
+        let (parts, numeric) = parse_expression(&expression, fields)?;
+        let index = Index::new(file, tag, parts, numeric, fields, exclusive)?;
+        let key_len = u16::from_le_bytes([header[12], header[13]]) as usize;
+        let key_type = u16::from_le_bytes([header[16], header[17]]);
+        if key_len != index.key_len || key_type != numeric as u16 {
+            return Err(E4_INDEX);
+        }
+        Ok(index)
+    }
+
+    fn new(
+        file: Box<dyn DataFile>,
+        tag: String,
+        parts: Vec<KeyPart>,
+        numeric: bool,
+        fields: &[Field],
+        exclusive: bool,
+    ) -> Result<Self, i32> {
+        let key_len = if numeric {
+            8
+        } else {
+            parts.iter().map(|part| fields[part.field()].len).sum()
+        };
+        if key_len > MAX_KEY_LEN {
+            return Err(E4_INDEX);
+        }
+        Ok(Index {
+            file,
+            tag,
+            parts,
+            numeric,
+            key_len,
+            // the key & two pointers aligned to 4 bytes
+            entry_len: (key_len + 8 + 3) & !3,
+            exclusive,
+            root: 1,
+            pages: 2,
+        })
+    }
+
+    /// Entries that fit in a page, interior nodes need one of them for their last child.
+    fn max_keys(&self) -> usize {
+        (PAGE_SIZE - 4) / self.entry_len
+    }
+
+    /// Key of a record.
+    pub fn key(&self, fields: &[Field], record: &[u8]) -> Vec<u8> {
+        if self.numeric {
+            let field = &fields[self.parts[0].field()];
+            let text: String = field.raw(record).iter().map(|b| *b as char).collect();
+            let number = match field.field_type {
+                'D' => julian_day(text.trim()),
+                _ => text.trim().parse().unwrap_or(0.0),
+            };
+            return number.to_le_bytes().to_vec();
+        }
+        let mut key = Vec::with_capacity(self.key_len);
+        for part in &self.parts {
+            let raw = fields[part.field()].raw(record);
+            match part {
+                KeyPart::Upper(_) => key.extend(raw.iter().map(u8::to_ascii_uppercase)),
+                KeyPart::Field(_) => key.extend_from_slice(raw),
+            }
+        }
+        key
+    }
+
+    /// The key DSEEK searches for & the length of it that has to match.
+    /// Character keys match if they start with the value, like dBase without SET EXACT.
+    pub fn seek_key(
+        &self,
+        fields: &[Field],
+        value: &VariableValue,
+        date_format: DateFormat,
+    ) -> (Vec<u8>, usize) {
+        if self.numeric {
+            let field = &fields[self.parts[0].field()];
+            let mut record = vec![b' '; field.offset() + field.len];
+            field.encode(&mut record, value, date_format);
+            return (self.key(fields, &record), self.key_len);
+        }
+        let text = match value {
+            VariableValue::Date(julian) | VariableValue::EDate(julian) => {
+                format!("{:08}", PcbDate::from_julian(*julian as i32).to_ddate())
+            }
+            _ => get_string(value),
+        };
+        let mut key: Vec<u8> = text.chars().map(|c| c as u32 as u8).collect();
+        key.truncate(self.key_len);
+        let len = key.len();
+        key.resize(self.key_len, 0);
+        (key, len)
+    }
+
+    /// Compares keys, then record numbers.
+    fn compare(&self, key: &[u8], recno: u32, other_key: &[u8], other_recno: u32) -> Ordering {
+        self.compare_keys(key, other_key)
+            .then(recno.cmp(&other_recno))
+    }
+
+    fn compare_keys(&self, key: &[u8], other: &[u8]) -> Ordering {
+        if self.numeric {
+            let number = |key: &[u8]| f64::from_le_bytes(key.try_into().unwrap_or_default());
+            number(key).total_cmp(&number(other))
+        } else {
+            key.cmp(other)
+        }
+    }
+
+    /// Compares the upper bound of the child of an interior entry, files of other programs
+    /// don't set the record numbers there.
+    fn compare_bound(&self, entry: &Entry, key: &[u8], recno: u32) -> Ordering {
+        let bound = if entry.recno == 0 {
+            u32::MAX
+        } else {
+            entry.recno
+        };
+        self.compare(&entry.key, bound, key, recno)
+    }
+
+    /// Runs `op` with the index locked & the header read.
+    fn locked<T>(&mut self, op: impl FnOnce(&mut Self) -> Result<T, i32>) -> Result<T, i32> {
+        if self.exclusive {
+            self.read_header()?;
+            return op(self);
+        }
+        let mut attempts = 0;
+        while !self.file.lock(LOCK_OFFSET, 1).map_err(|_| E4_LOCK)? {
+            attempts += 1;
+            if attempts >= LOCK_ATTEMPTS {
+                return Err(E4_LOCK);
+            }
+            thread::sleep(LOCK_DELAY);
+        }
+        let res = self.read_header().and_then(|_| op(self));
+        self.file.unlock(LOCK_OFFSET, 1).map_err(|_| E4_LOCK)?;
+        res
+    }
+
+    fn read_header(&mut self) -> Result<(), i32> {
+        let header = self.read(0, 8)?;
+        if header.len() < 8 {
+            return Err(E4_INDEX);
+        }
+        self.root = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        self.pages = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<(), i32> {
+        let mut header = self.root.to_le_bytes().to_vec();
+        header.extend(self.pages.to_le_bytes());
+        self.write(0, &header)
+    }
+
+    fn read(&mut self, pos: u64, len: usize) -> Result<Vec<u8>, i32> {
+        self.file.read_at(pos, len).map_err(|err| {
+            log::error!("error reading index {}: {}", self.tag, err);
+            E4_READ
+        })
+    }
+
+    fn write(&mut self, pos: u64, data: &[u8]) -> Result<(), i32> {
+        self.file.write_at(pos, data).map_err(|err| {
+            log::error!("error writing index {}: {}", self.tag, err);
+            E4_WRITE
+        })
+    }
+
+    fn read_node(&mut self, page: u32) -> Result<Node, i32> {
+        if page == 0 || page >= self.pages {
+            return Err(E4_INDEX);
+        }
+        let data = self.read(page as u64 * PAGE_SIZE as u64, PAGE_SIZE)?;
+        if data.len() < PAGE_SIZE {
+            return Err(E4_INDEX);
+        }
+        let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if count > self.max_keys() {
+            return Err(E4_INDEX);
+        }
+        let number = |pos: usize| {
+            data.get(pos..pos + 4)
+                .map_or(0, |p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+        };
+        let entries = (0..count)
+            .map(|i| {
+                let pos = 4 + i * self.entry_len;
+                Entry {
+                    child: number(pos),
+                    recno: number(pos + 4),
+                    key: data[pos + 8..pos + 8 + self.key_len].to_vec(),
+                }
+            })
+            .collect::<Vec<_>>();
+        // leaves have no child pointers
+        let last = match entries.first() {
+            Some(entry) if entry.child == 0 => None,
+            _ => Some(number(4 + count * self.entry_len)).filter(|child| *child != 0),
+        };
+        Ok(Node { entries, last })
+    }
+
+    fn write_node(&mut self, page: u32, node: &Node) -> Result<(), i32> {
+        let mut data = vec![0; PAGE_SIZE];
+        data[..4].copy_from_slice(&(node.entries.len() as u32).to_le_bytes());
+        for (i, entry) in node.entries.iter().enumerate() {
+            let pos = 4 + i * self.entry_len;
+            data[pos..pos + 4].copy_from_slice(&entry.child.to_le_bytes());
+            data[pos + 4..pos + 8].copy_from_slice(&entry.recno.to_le_bytes());
+            data[pos + 8..pos + 8 + self.key_len].copy_from_slice(&entry.key);
+        }
+        if let Some(last) = node.last {
+            let pos = 4 + node.entries.len() * self.entry_len;
+            data[pos..pos + 4].copy_from_slice(&last.to_le_bytes());
+        }
+        self.write(page as u64 * PAGE_SIZE as u64, &data)
+    }
+
+    fn new_page(&mut self) -> Result<u32, i32> {
+        let page = self.pages;
+        self.pages += 1;
+        self.write_header()?;
+        Ok(page)
+    }
+
+    /// Replaces the index with the keys of the records.
+    pub fn rebuild(&mut self, fields: &[Field], records: &[(u32, Vec<u8>)]) -> Result<(), i32> {
+        let mut entries: Vec<Entry> = records
+            .iter()
+            .map(|(recno, record)| Entry {
+                child: 0,
+                recno: *recno,
+                key: self.key(fields, record),
+            })
+            .collect();
+        entries.sort_by(|a, b| self.compare(&a.key, a.recno, &b.key, b.recno));
+        self.locked(|index| {
+            index
+                .file
+                .set_size(PAGE_SIZE as u64)
+                .map_err(|_| E4_WRITE)?;
+            index.root = 1;
+            index.pages = 2;
+            index.write_header()?;
+            index.write_node(
+                1,
+                &Node {
+                    entries: Vec::new(),
+                    last: None,
+                },
+            )?;
+            for entry in entries {
+                index.insert_entry(entry)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn insert(&mut self, key: Vec<u8>, recno: u32) -> Result<(), i32> {
+        let entry = Entry {
+            child: 0,
+            recno,
+            key,
+        };
+        self.locked(|index| index.insert_entry(entry))
+    }
+
+    fn insert_entry(&mut self, entry: Entry) -> Result<(), i32> {
+        let root = self.root;
+        if let Some((separator, right)) = self.insert_into(root, entry)? {
+            let new_root = self.new_page()?;
+            let node = Node {
+                entries: vec![Entry {
+                    child: root,
+                    ..separator
+                }],
+                last: Some(right),
+            };
+            self.write_node(new_root, &node)?;
+            self.root = new_root;
+            self.write_header()?;
+        }
+        Ok(())
+    }
+
+    /// Inserts into the subtree at `page`, returns the separator & the new right page if the page was split.
+    fn insert_into(&mut self, page: u32, entry: Entry) -> Result<Option<(Entry, u32)>, i32> {
+        let mut node = self.read_node(page)?;
+        if node.last.is_none() {
+            let pos = node.entries.partition_point(|e| {
+                self.compare(&e.key, e.recno, &entry.key, entry.recno) == Ordering::Less
+            });
+            node.entries.insert(pos, entry);
+        } else {
+            let i = node.entries.partition_point(|e| {
+                self.compare_bound(e, &entry.key, entry.recno) == Ordering::Less
+            });
+            let child = node.child(i);
+            let Some((separator, right)) = self.insert_into(child, entry)? else {
+                return Ok(None);
+            };
+            let left = Entry { child, ..separator };
+            if i < node.entries.len() {
+                node.entries[i].child = right;
+                node.entries.insert(i, left);
+            } else {
+                node.entries.push(left);
+                node.last = Some(right);
+            }
+        }
+        if node.entries.len() < self.max_keys() {
+            self.write_node(page, &node)?;
+            return Ok(None);
+        }
+        let mid = node.entries.len() / 2;
+        let mut right_entries = node.entries.split_off(mid);
+        let (separator, right) = match node.last {
+            None => {
+                let separator = node.entries[mid - 1].clone();
+                (
+                    separator,
+                    Node {
+                        entries: right_entries,
+                        last: None,
+                    },
+                )
+            }
+            Some(last) => {
+                // the middle key moves up, its child becomes the last child of the left node
+                let separator = right_entries.remove(0);
+                node.last = Some(separator.child);
+                (
+                    separator,
+                    Node {
+                        entries: right_entries,
+                        last: Some(last),
+                    },
+                )
+            }
+        };
+        let right_page = self.new_page()?;
+        self.write_node(right_page, &right)?;
+        self.write_node(page, &node)?;
+        Ok(Some((
+            Entry {
+                child: 0,
+                ..separator
+            },
+            right_page,
+        )))
+    }
+
+    pub fn remove(&mut self, key: &[u8], recno: u32) -> Result<(), i32> {
+        self.locked(|index| {
+            let root = index.root;
+            if !index.remove_from(root, key, recno)? {
+                log::warn!("index {}: key of record {} not found", index.tag, recno);
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_from(&mut self, page: u32, key: &[u8], recno: u32) -> Result<bool, i32> {
+        let mut node = self.read_node(page)?;
+        if node.last.is_none() {
+            let Some(pos) = node
+                .entries
+                .iter()
+                .position(|e| self.compare(&e.key, e.recno, key, recno) == Ordering::Equal)
+            else {
+                return Ok(false);
+            };
+            node.entries.remove(pos);
+            self.write_node(page, &node)?;
+            return Ok(true);
+        }
+        let start = node
+            .entries
+            .partition_point(|e| self.compare_bound(e, key, recno) == Ordering::Less);
+        for i in start..=node.entries.len() {
+            if self.remove_from(node.child(i), key, recno)? {
+                return Ok(true);
+            }
+            if i < node.entries.len()
+                && self.compare_keys(&node.entries[i].key, key) == Ordering::Greater
+            {
+                break;
+            }
+        }
+        Ok(false)
+    }
+
+    /// The first entry, (key, record number)
+    pub fn first(&mut self) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        self.locked(|index| {
+            let root = index.root;
+            index.find_from(root, None, true)
+        })
+    }
+
+    pub fn last(&mut self) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        self.locked(|index| {
+            let root = index.root;
+            index.find_before(root, None)
+        })
+    }
+
+    /// The first entry with a key >= `key`.
+    pub fn seek(&mut self, key: &[u8]) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        self.locked(|index| {
+            let root = index.root;
+            index.find_from(root, Some((key, 0)), true)
+        })
+    }
+
+    /// The entry after (key, recno), the entry itself doesn't need to exist.
+    pub fn next(&mut self, key: &[u8], recno: u32) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        self.locked(|index| {
+            let root = index.root;
+            index.find_from(root, Some((key, recno)), false)
+        })
+    }
+
+    /// The entry before (key, recno).
+    pub fn prev(&mut self, key: &[u8], recno: u32) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        self.locked(|index| {
+            let root = index.root;
+            index.find_before(root, Some((key, recno)))
+        })
+    }
+
+    /// First entry at or after `target`, all entries if there is no target.
+    fn find_from(
+        &mut self,
+        page: u32,
+        target: Option<(&[u8], u32)>,
+        inclusive: bool,
+    ) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        let node = self.read_node(page)?;
+        let after =
+            |order: Ordering| order == Ordering::Greater || inclusive && order == Ordering::Equal;
+        if node.last.is_none() {
+            return Ok(node
+                .entries
+                .into_iter()
+                .find(|e| {
+                    target
+                        .is_none_or(|(key, recno)| after(self.compare(&e.key, e.recno, key, recno)))
+                })
+                .map(|e| (e.key, e.recno)));
+        }
+        let start = target.map_or(0, |(key, recno)| {
+            node.entries
+                .partition_point(|e| !after(self.compare_bound(e, key, recno)))
+        });
+        for i in start..=node.entries.len() {
+            if let Some(found) = self.find_from(node.child(i), target, inclusive)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Last entry before `target`, all entries if there is no target.
+    fn find_before(
+        &mut self,
+        page: u32,
+        target: Option<(&[u8], u32)>,
+    ) -> Result<Option<(Vec<u8>, u32)>, i32> {
+        let node = self.read_node(page)?;
+        if node.last.is_none() {
+            return Ok(node
+                .entries
+                .into_iter()
+                .rev()
+                .find(|e| {
+                    target.is_none_or(|(key, recno)| {
+                        self.compare(&e.key, e.recno, key, recno) == Ordering::Less
+                    })
+                })
+                .map(|e| (e.key, e.recno)));
+        }
+        let end = target.map_or(node.entries.len(), |(key, recno)| {
+            node.entries
+                .partition_point(|e| self.compare_bound(e, key, recno) == Ordering::Less)
+        });
+        for i in (0..=end).rev() {
+            if let Some(found) = self.find_before(node.child(i), target)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl KeyPart {
+    fn field(&self) -> usize {
+        match self {
+            KeyPart::Field(field) | KeyPart::Upper(field) => *field,
+        }
+    }
+}
+
+/// Parses a key expression: field names joined with '+', character fields may be in UPPER(),
+/// date fields in DTOS(). A single N or D field is a numeric key.
+fn parse_expression(expression: &str, fields: &[Field]) -> Result<(Vec<KeyPart>, bool), i32> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .position(|field| field.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or(E4_FIELD_NAME)
+    };
+    let mut parts = Vec::new();
+    let mut plain = true;
+    for part in expression.split('+') {
+        let part = part.trim();
+        let upper = part.to_uppercase();
+        let argument = |function: &str| {
+            upper
+                .strip_prefix(function)
+                .and_then(|rest| rest.trim_start().strip_prefix('('))
+                .and_then(|rest| rest.strip_suffix(')'))
+        };
+        parts.push(if let Some(name) = argument("UPPER") {
+            plain = false;
+            KeyPart::Upper(field(name)?)
+        } else if let Some(name) = argument("DTOS") {
+            plain = false;
+            let i = field(name)?;
+            if fields[i].field_type != 'D' {
+                return Err(E4_INDEX);
+            }
+            KeyPart::Field(i)
+        } else {
+            KeyPart::Field(field(part)?)
+        });
+    }
+    let numeric =
+        plain && parts.len() == 1 && matches!(fields[parts[0].field()].field_type, 'N' | 'D');
+    Ok((parts, numeric))
+}
+
+/// Julian day number of a "yyyymmdd" date as dBase stores dates in numeric keys, 0 if it's blank.
+fn julian_day(ddate: &str) -> f64 {
+    let Ok(ddate) = ddate.parse::<i32>() else {
+        return 0.0;
+    };
+    let date = PcbDate::from_ddate(ddate);
+    let a = (14 - date.month as i64) / 12;
+    let y = date.year as i64 + 4800 - a;
+    let m = date.month as i64 + 12 * a - 3;
+    let day = date.day as i64 + (153 * m + 2) / 5 + 365 * y + y / 4 - y / 100 + y / 400 - 32045;
+    day as f64
+}
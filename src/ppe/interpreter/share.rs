@@ -0,0 +1,134 @@
+//! DOS file sharing for FOPEN/FCREATE/FAPPEND.
+//!
+//! Like SHARE.EXE an open fails if its access mode is denied by an open handle of the file or
+//! if it denies an access mode another handle uses. Access & deny modes are advisory shared locks
+//! on bytes far beyond the end of the file, one byte per mode. On Linux these are open file
+//! description locks, so nodes in the same process see each other like separate processes do.
+//! `MemoryIO` checks its own channels with `share_conflict`.
+//! The dBase record locks are write locks on the data file itself, see `lock_range`.
+use std::{
+    fs::File,
+    io::{Error, Result},
+    os::unix::io::AsRawFd,
+    path::Path,
+};
+
+use super::{O_RD, O_WR, S_DB, S_DR, S_DW};
+
+#[cfg(target_os = "linux")]
+const SET_LOCK: libc::c_int = libc::F_OFD_SETLK;
+#[cfg(target_os = "linux")]
+const GET_LOCK: libc::c_int = libc::F_OFD_GETLK;
+// process wide locks, other nodes in this process don't see them
+#[cfg(not(target_os = "linux"))]
+const SET_LOCK: libc::c_int = libc::F_SETLK;
+#[cfg(not(target_os = "linux"))]
+const GET_LOCK: libc::c_int = libc::F_GETLK;
+
+const LOCK_BASE: i64 = 0x7FFF_FFFF_FFFF_FF00;
+const ACCESS_READ: i64 = LOCK_BASE;
+const ACCESS_WRITE: i64 = LOCK_BASE + 1;
+const DENY_READ: i64 = LOCK_BASE + 2;
+const DENY_WRITE: i64 = LOCK_BASE + 3;
+
+/// (read, write) of an access mode
+pub fn access(am: i32) -> (bool, bool) {
+    match am {
+        O_RD => (true, false),
+        O_WR => (false, true),
+        _ => (true, true),
+    }
+}
+
+/// (deny read, deny write) of a share mode
+fn denied(sm: i32) -> (bool, bool) {
+    match sm {
+        S_DR => (true, false),
+        S_DW => (false, true),
+        S_DB => (true, true),
+        _ => (false, false),
+    }
+}
+
+/// Returns true if a file opened with `am`/`sm` can't be opened again with `new_am`/`new_sm`.
+pub fn share_conflict(am: i32, sm: i32, new_am: i32, new_sm: i32) -> bool {
+    let (read, write) = access(am);
+    let (deny_read, deny_write) = denied(sm);
+    let (new_read, new_write) = access(new_am);
+    let (new_deny_read, new_deny_write) = denied(new_sm);
+    new_read && deny_read
+        || new_write && deny_write
+        || read && new_deny_read
+        || write && new_deny_write
+}
+
+/// The access & deny modes of an open file, they're released when it's dropped.
+pub struct ShareLock {
+    file: File,
+}
+
+impl ShareLock {
+    /// Registers an open of `path`, fails with a sharing violation if it conflicts with another open.
+    pub fn acquire(path: &Path, am: i32, sm: i32) -> Result<Self> {
+        let lock = ShareLock {
+            file: File::open(path)?,
+        };
+        let (read, write) = access(am);
+        let (deny_read, deny_write) = denied(sm);
+        let modes = [
+            (read, ACCESS_READ, DENY_READ),
+            (write, ACCESS_WRITE, DENY_WRITE),
+            (deny_read, DENY_READ, ACCESS_READ),
+            (deny_write, DENY_WRITE, ACCESS_WRITE),
+        ];
+        // lock first & check afterwards, two racing opens may both fail but never both succeed
+        for (used, byte, _) in modes {
+            if used {
+                fcntl(&lock.file, SET_LOCK, libc::F_RDLCK, byte, 1)?;
+            }
+        }
+        for (used, _, conflicting) in modes {
+            if used && fcntl(&lock.file, GET_LOCK, libc::F_WRLCK, conflicting, 1)? != libc::F_UNLCK
+            {
+                return Err(Error::other(format!(
+                    "sharing violation on {}",
+                    path.display()
+                )));
+            }
+        }
+        Ok(lock)
+    }
+}
+
+/// Locks a byte range for writing, returns false if another handle holds a lock in the range.
+pub fn lock_range(file: &File, pos: u64, len: u64) -> Result<bool> {
+    match fcntl(file, SET_LOCK, libc::F_WRLCK, pos as i64, len as i64) {
+        Ok(_) => Ok(true),
+        Err(err) if matches!(err.raw_os_error(), Some(libc::EAGAIN | libc::EACCES)) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn unlock_range(file: &File, pos: u64, len: u64) -> Result<()> {
+    fcntl(file, SET_LOCK, libc::F_UNLCK, pos as i64, len as i64).map(|_| ())
+}
+
+/// Sets or tests a lock on a byte range, returns the lock type F_GETLK reports.
+fn fcntl(
+    file: &File,
+    cmd: libc::c_int,
+    lock_type: libc::c_int,
+    start: i64,
+    len: i64,
+) -> Result<libc::c_int> {
+    let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+    flock.l_type = lock_type as _;
+    flock.l_whence = libc::SEEK_SET as _;
+    flock.l_start = start as _;
+    flock.l_len = len as _;
+    // SAFETY: the file descriptor is open & flock is initialized
+    if unsafe { libc::fcntl(file.as_raw_fd(), cmd, &mut flock) } == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok(flock.l_type as libc::c_int)
+}
@@ -1,4 +1,5 @@
 use crate::evaluate_exp;
+use crate::get_display_string;
 use crate::get_int;
 use crate::get_string;
 use crate::Interpreter;
@@ -34,14 +35,16 @@ pub fn call_predefined_procedure(
         OpCode::PRINT => {
             for expr in params {
                 let value = evaluate_exp(interpreter, expr)?;
-                interpreter.ctx.print(&value.to_string())?;
+                let str = get_display_string(interpreter, &value);
+                interpreter.ctx.print(&str)?;
             }
             Ok(())
         }
         OpCode::PRINTLN => {
             for expr in params {
                 let value = evaluate_exp(interpreter, expr)?;
-                interpreter.ctx.print(&value.to_string())?;
+                let str = get_display_string(interpreter, &value);
+                interpreter.ctx.print(&str)?;
             }
             interpreter.ctx.print("\n")?;
             Ok(())
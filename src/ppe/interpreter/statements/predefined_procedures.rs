@@ -1,7 +1,14 @@
-use std::{fs, thread, time::Duration};
+use std::{thread, time::Duration};
 
 use super::super::errors::IcyError;
-use crate::{evaluate_exp, get_int, get_string, pcb_text, Interpreter, Res};
+use crate::constants::{NEWLINE, UPCASE};
+use crate::input::{self, input_field, is_valid_cc};
+use crate::memory::{from_binary, to_binary, value_type, DosMemory, Registers};
+use crate::pcb_date::{parse_time, PcbDate};
+use crate::{
+    evaluate_exp, get_i64, get_int, get_string, is_true, pcb_text, redim_array, set_array_value,
+    with_int_type, Interpreter, Res,
+};
 use ppl_engine::ast::*;
 
 pub fn cls(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
@@ -17,7 +24,7 @@ pub fn more(interpreter: &mut Interpreter) -> Res<()> {
         .ctx
         .print(&interpreter.icb_data.pcb_text[pcb_text::MOREPROMPT])?;
     loop {
-        if let Some(ch) = interpreter.ctx.get_char()? {
+        if let Some(ch) = interpreter.get_char()? {
             let ch = ch.to_uppercase().to_string();
 
             if ch == interpreter.icb_data.yes_char.to_string()
@@ -35,7 +42,7 @@ pub fn wait(interpreter: &mut Interpreter) -> Res<()> {
         .ctx
         .print(&interpreter.icb_data.pcb_text[pcb_text::PRESSENTER])?;
     loop {
-        if let Some(ch) = interpreter.ctx.get_char()? {
+        if let Some(ch) = interpreter.get_char()? {
             if ch == '\n' || ch == '\r' {
                 break;
             }
@@ -51,17 +58,17 @@ pub fn color(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
 }
 
 pub fn goto(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("GOTO")))
 }
 pub fn confflag(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CONFFLAG")))
 }
 pub fn confunflag(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CONFUNFLAG")))
 }
 
 pub fn dispfile(interpreter: &mut Interpreter, file: String, flags: i32) -> Res<()> {
-    let content = fs::read(&file);
+    let content = interpreter.io.read_file(&file);
     match content {
         Ok(content) => interpreter.ctx.write_raw(&content),
         Err(err) => interpreter
@@ -70,11 +77,48 @@ pub fn dispfile(interpreter: &mut Interpreter, file: String, flags: i32) -> Res<
     }
 }
 
-pub fn input(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// Evaluates a file channel number, valid channels are 0-7.
+fn get_channel(interpreter: &mut Interpreter, expr: &Expression) -> Res<usize> {
+    let channel = get_int(&evaluate_exp(interpreter, expr)?)?;
+    if !(0..=7).contains(&channel) {
+        return Err(Box::new(IcyError::FileChannelOutOfBounds(channel)));
+    }
+    Ok(channel as usize)
+}
+
+/// Stores `value` in the variable or array element given by `var`, converted to the variable type.
+fn set_variable(interpreter: &mut Interpreter, var: &Expression, value: VariableValue) -> Res<()> {
+    let Expression::FunctionCall(name, parameters) = var else {
+        return interpreter.set_variable(var_name(var)?, value);
+    };
+    let mut indices = Vec::new();
+    for p in parameters {
+        indices.push(get_int(&evaluate_exp(interpreter, p)?)?);
+    }
+    let Some(arr) = interpreter.get_variable_mut(name)? else {
+        return Err(Box::new(IcyError::VariableNotFound(name.clone())));
+    };
+    set_array_value(arr, &indices, value)
+}
+
+/// Name of the variable an argument refers to, array elements give the array for type lookups.
+/// It's the name in the program, so the variable slot it was resolved to is used.
+fn var_name(var: &Expression) -> Res<&String> {
+    match var {
+        Expression::Identifier(name) | Expression::FunctionCall(name, _) => Ok(name),
+        _ => Err(Box::new(IcyError::VariableNotFound(format!("{:?}", var)))),
+    }
+}
+
+pub fn input(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let prompt = get_string(&evaluate_exp(interpreter, &params[0])?);
+    // INPUT has no color parameter, PCBoard uses the default color & a 60 char field
+    let value = input_field(interpreter, &prompt, 7, 60, "", 0)?;
+    set_variable(interpreter, &params[1], VariableValue::String(value))?;
+    Ok(())
 }
 pub fn fcreate(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
-    let channel = get_int(&evaluate_exp(interpreter, &params[0])?)? as usize;
+    let channel = get_channel(interpreter, &params[0])?;
     let file = &evaluate_exp(interpreter, &params[1])?.to_string();
     let am = get_int(&evaluate_exp(interpreter, &params[2])?)?;
     let sm = get_int(&evaluate_exp(interpreter, &params[3])?)?;
@@ -83,7 +127,7 @@ pub fn fcreate(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()>
 }
 
 pub fn fopen(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
-    let channel = get_int(&evaluate_exp(interpreter, &params[0])?)? as usize;
+    let channel = get_channel(interpreter, &params[0])?;
     let file = &evaluate_exp(interpreter, &params[1])?.to_string();
     let am = get_int(&evaluate_exp(interpreter, &params[2])?)?;
     let sm = get_int(&evaluate_exp(interpreter, &params[3])?)?;
@@ -92,7 +136,7 @@ pub fn fopen(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
 }
 
 pub fn fappend(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
-    let channel = get_int(&evaluate_exp(interpreter, &params[0])?)? as usize;
+    let channel = get_channel(interpreter, &params[0])?;
     let file = &evaluate_exp(interpreter, &params[1])?.to_string();
     let am = get_int(&evaluate_exp(interpreter, &params[2])?)?;
     let sm = get_int(&evaluate_exp(interpreter, &params[3])?)?;
@@ -114,37 +158,54 @@ pub fn fclose(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
 }
 
 pub fn fget(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
-    let channel = get_int(&evaluate_exp(interpreter, &params[0])?)? as usize;
-    let value = VariableValue::String(interpreter.io.fget(channel));
-    let var_name = get_var_name(&params[1]);
-    let var_type = interpreter.prg.get_var_type(&var_name);
-    interpreter
-        .cur_frame
-        .last_mut()
-        .unwrap()
-        .values
-        .insert(var_name, convert_to(var_type, &value));
-    Ok(())
+    let channel = get_channel(interpreter, &params[0])?;
+    get_line(interpreter, channel, &params[1])
 }
 
 pub fn fput(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
-    let channel = get_int(&evaluate_exp(interpreter, &params[0])?)? as usize;
+    let channel = get_channel(interpreter, &params[0])?;
+    put_values(interpreter, channel, &params[1..])
+}
 
-    for expr in &params[1..] {
-        let value = evaluate_exp(interpreter, expr)?;
-        interpreter.io.fput(channel, value.to_string());
-    }
+pub fn fputln(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    put_values(interpreter, channel, &params[1..])?;
+    interpreter.io.fput(channel, "\n".to_string());
     Ok(())
 }
 
-pub fn fputln(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
-    let channel = get_int(&evaluate_exp(interpreter, &params[0])?)? as usize;
+/// FPUTPAD channel, exp, width - writes a line padded with spaces or truncated to `width`
+pub fn fputpad(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    put_padded(interpreter, channel, &params[1], &params[2])
+}
 
-    for expr in &params[1..] {
+fn get_line(interpreter: &mut Interpreter, channel: usize, var: &Expression) -> Res<()> {
+    let value = VariableValue::String(interpreter.io.fget(channel));
+    set_variable(interpreter, var, value)
+}
+
+fn put_values(interpreter: &mut Interpreter, channel: usize, exprs: &[Expression]) -> Res<()> {
+    for expr in exprs {
         let value = evaluate_exp(interpreter, expr)?;
         interpreter.io.fput(channel, value.to_string());
     }
-    interpreter.io.fput(channel, "\n".to_string());
+    Ok(())
+}
+
+fn put_padded(
+    interpreter: &mut Interpreter,
+    channel: usize,
+    expr: &Expression,
+    width: &Expression,
+) -> Res<()> {
+    let text = evaluate_exp(interpreter, expr)?.to_string();
+    let width = get_int(&evaluate_exp(interpreter, width)?)?.max(0) as usize;
+    let mut line: String = text.chars().take(width).collect();
+    let len = line.chars().count();
+    line.push_str(&" ".repeat(width - len));
+    line.push('\n');
+    interpreter.io.fput(channel, line);
     Ok(())
 }
 
@@ -156,11 +217,8 @@ pub fn startdisp(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
     // TODO?: unused
     Ok(())
 }
-pub fn fputpad(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
-}
 pub fn hangup(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("HANGUP")))
 }
 
 pub fn getuser(interpreter: &mut Interpreter) -> Res<()> {
@@ -178,7 +236,7 @@ pub fn putuser(interpreter: &mut Interpreter) -> Res<()> {
 }
 
 pub fn defcolor(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DEFCOLOR")))
 }
 
 pub fn delete(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
@@ -190,51 +248,151 @@ pub fn delete(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
 }
 
 pub fn deluser(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DELUSER")))
 }
 pub fn adjtime(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADJTIME")))
 }
 pub fn log(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("LOG")))
+}
+
+pub fn inputstr(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let prompt = get_string(&evaluate_exp(interpreter, &params[0])?);
+    let color = get_int(&evaluate_exp(interpreter, &params[2])?)? as u8;
+    let len = get_int(&evaluate_exp(interpreter, &params[3])?)?;
+    let valid = get_string(&evaluate_exp(interpreter, &params[4])?);
+    let flags = get_int(&evaluate_exp(interpreter, &params[5])?)?;
+    let value = input_field(
+        interpreter,
+        &prompt,
+        color,
+        len.max(0) as usize,
+        &valid,
+        flags,
+    )?;
+    set_variable(interpreter, &params[1], VariableValue::String(value))?;
+    Ok(())
 }
 
-pub fn inputstr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO inputstr")
+/// Evaluates prompt & color parameters of the typed INPUT* statements.
+fn get_prompt_color(interpreter: &mut Interpreter, params: &[Expression]) -> Res<(String, u8)> {
+    let prompt = get_string(&evaluate_exp(interpreter, &params[0])?);
+    let color = get_int(&evaluate_exp(interpreter, &params[2])?)? as u8;
+    Ok((prompt, color))
 }
 
-pub fn inputyn(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// Reads input until `parse` accepts it.
+fn input_until_valid<T>(
+    interpreter: &mut Interpreter,
+    params: &[Expression],
+    len: usize,
+    valid: &str,
+    parse: impl Fn(&Interpreter, &str) -> Option<T>,
+) -> Res<T> {
+    let (prompt, color) = get_prompt_color(interpreter, params)?;
+    loop {
+        let input = input_field(interpreter, &prompt, color, len, valid, UPCASE | NEWLINE)?;
+        if let Some(value) = parse(interpreter, &input) {
+            return Ok(value);
+        }
+    }
 }
-pub fn inputmoney(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+pub fn inputyn(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let valid = format!(
+        "{}{}",
+        interpreter.icb_data.yes_char.to_ascii_uppercase(),
+        interpreter.icb_data.no_char.to_ascii_uppercase()
+    );
+    let ch = input_until_valid(interpreter, params, 1, &valid, |_, input| {
+        input.chars().next()
+    })?;
+    let is_yes = ch == interpreter.icb_data.yes_char.to_ascii_uppercase();
+
+    let value = if interpreter.prg.get_var_type(var_name(&params[1])?) == VariableType::Boolean {
+        VariableValue::Boolean(is_yes)
+    } else {
+        VariableValue::String(ch.to_string())
+    };
+    set_variable(interpreter, &params[1], value)?;
+    Ok(())
 }
-pub fn inputint(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+pub fn inputmoney(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let value = input_until_valid(interpreter, params, 13, "0123456789$.,-", |_, input| {
+        input
+            .trim_start_matches('$')
+            .replace(',', "")
+            .parse::<f64>()
+            .ok()
+    })?;
+    set_variable(interpreter, &params[1], VariableValue::Money(value))?;
+    Ok(())
+}
+
+pub fn inputint(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let value = input_until_valid(interpreter, params, 11, "0123456789+-", |_, input| {
+        input.parse::<i32>().ok()
+    })?;
+    set_variable(interpreter, &params[1], VariableValue::Integer(value))?;
+    Ok(())
 }
-pub fn inputcc(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+pub fn inputcc(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let value = input_until_valid(interpreter, params, 23, "0123456789 -", |_, input| {
+        if is_valid_cc(input) {
+            Some(
+                input
+                    .chars()
+                    .filter(char::is_ascii_digit)
+                    .collect::<String>(),
+            )
+        } else {
+            None
+        }
+    })?;
+    set_variable(interpreter, &params[1], VariableValue::String(value))?;
+    Ok(())
 }
-pub fn inputdate(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+pub fn inputdate(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let value = input_until_valid(
+        interpreter,
+        params,
+        8,
+        "0123456789-/.",
+        |interpreter, input| PcbDate::parse(input, interpreter.icb_data.date_format),
+    )?;
+    set_variable(
+        interpreter,
+        &params[1],
+        VariableValue::Date(value.to_julian() as _),
+    )?;
+    Ok(())
 }
-pub fn inputtime(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+pub fn inputtime(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let value = input_until_valid(interpreter, params, 8, "0123456789:", |_, input| {
+        parse_time(input)
+    })?;
+    set_variable(interpreter, &params[1], VariableValue::Time(value as _))?;
+    Ok(())
 }
 pub fn promptstr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PROMPTSTR")))
 }
 pub fn dtron(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DTRON")))
 }
 pub fn dtroff(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DTROFF")))
 }
 pub fn cdchkon(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CDCHKON")))
 }
 pub fn cdchkoff(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CDCHKOFF")))
 }
 
 pub fn delay(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
@@ -247,13 +405,13 @@ pub fn delay(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
 }
 
 pub fn sendmodem(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SENDMODEM")))
 }
 pub fn inc(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("INC")))
 }
 pub fn dec(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DEC")))
 }
 
 pub fn newline(interpreter: &mut Interpreter) -> Res<()> {
@@ -269,62 +427,78 @@ pub fn newlines(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()>
 }
 
 pub fn tokenize(interpreter: &mut Interpreter, str: String) -> Res<()> {
-    let split = str.split(&[' ', ';'][..]).map(|s| s.to_string());
-    interpreter.cur_tokens = split.collect();
+    interpreter.cur_tokens = input::tokenize(&str);
     Ok(())
 }
 
-pub fn gettoken(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn gettoken(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let token = if interpreter.cur_tokens.is_empty() {
+        String::new()
+    } else {
+        interpreter.cur_tokens.remove(0)
+    };
+    set_variable(interpreter, &params[0], VariableValue::String(token))?;
+    Ok(())
 }
 pub fn shell(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SHELL")))
 }
 pub fn disptext(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DISPTEXT")))
 }
 pub fn stop(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("STOP")))
 }
 pub fn inputtext(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("INPUTTEXT")))
 }
 pub fn beep(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("BEEP")))
 }
 pub fn push(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PUSH")))
 }
 pub fn pop(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("POP")))
 }
 pub fn kbdstuff(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
     let value = evaluate_exp(interpreter, &params[0])?;
-    interpreter.ctx.print(&get_string(&value))
+    interpreter.keyboard.ppl.extend(get_string(&value).chars());
+    Ok(())
 }
 pub fn call(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CALL")))
 }
 pub fn join(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("JOIN")))
 }
 pub fn quest(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("QUEST")))
 }
 pub fn blt(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("BLT")))
 }
 pub fn dir(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
-}
-pub fn kbdfile(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DIR")))
+}
+pub fn kbdfile(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let file = get_string(&evaluate_exp(interpreter, &params[0])?);
+    match interpreter.io.read_file(&file) {
+        Ok(content) => {
+            interpreter
+                .keyboard
+                .file
+                .extend(content.iter().map(|b| *b as char));
+        }
+        Err(err) => log::error!("Error reading keyboard file {}: {}", file, err),
+    }
+    Ok(())
 }
 pub fn bye(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("BYE")))
 }
 pub fn goodbye(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("GOODBYE")))
 }
 
 /// Broadcast a single line message to a range of nodes.
@@ -341,24 +515,26 @@ pub fn broadcast(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()
     let hinode = get_int(&evaluate_exp(interpreter, &params[1])?)?;
     let message = get_string(&evaluate_exp(interpreter, &params[2])?);
     // TODO: Broadcast
-    println!(
+    log::trace!(
         "Broadcasting message from {} to {}: {}",
-        lonode, hinode, message
+        lonode,
+        hinode,
+        message
     );
     Ok(())
 }
 
 pub fn waitfor(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("WAITFOR")))
 }
 pub fn kbdchkon(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("KBDCHKON")))
 }
 pub fn kbdchkoff(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("KBDCHKOFF")))
 }
 pub fn optext(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("OPTEXT")))
 }
 pub fn dispstr(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
     let value = evaluate_exp(interpreter, &params[0])?;
@@ -396,23 +572,71 @@ pub fn wrunet(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
     Ok(())
 }
 
-pub fn dointr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
-}
-pub fn varseg(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
-}
-pub fn varoff(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
-}
-pub fn pokeb(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
-}
-pub fn pokew(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DOINTR int, ax, bx, cx, dx, si, di, flags, ds, es
+/// The registers after the interrupt are returned by the REG* functions.
+pub fn dointr(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let mut values = Vec::new();
+    for param in params {
+        values.push(get_i64(&evaluate_exp(interpreter, param)?)?);
+    }
+    let reg = |i: usize| values.get(i).copied().unwrap_or(0) as u16;
+    interpreter.memory.registers = Registers {
+        ax: reg(1),
+        bx: reg(2),
+        cx: reg(3),
+        dx: reg(4),
+        si: reg(5),
+        di: reg(6),
+        flags: reg(7),
+        ds: reg(8),
+        es: reg(9),
+    };
+    interpreter.dointr(reg(0) as u8);
+    Ok(())
 }
-pub fn varaddr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// VARSEG var, seg - stores the segment of `var` in the emulated DOS memory
+pub fn varseg(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let address = variable_address(interpreter, &params[0])?;
+    set_variable(
+        interpreter,
+        &params[1],
+        VariableValue::Integer((address >> 16) as i32),
+    )
+}
+/// VAROFF var, off - stores the offset of `var` in the emulated DOS memory
+pub fn varoff(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let address = variable_address(interpreter, &params[0])?;
+    set_variable(
+        interpreter,
+        &params[1],
+        VariableValue::Integer((address & 0xFFFF) as i32),
+    )
+}
+/// VARADDR var, addr - stores the address (segment:offset) of `var` in the emulated DOS memory
+pub fn varaddr(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let address = variable_address(interpreter, &params[0])?;
+    set_variable(
+        interpreter,
+        &params[1],
+        VariableValue::Integer(address as i32),
+    )
+}
+fn variable_address(interpreter: &mut Interpreter, var: &Expression) -> Res<i64> {
+    let linear = interpreter.variable_address(var_name(var)?)?;
+    Ok(DosMemory::far_pointer(linear))
+}
+/// POKEB addr, value - writes a byte to the emulated DOS memory
+pub fn pokeb(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    poke(interpreter, params, 1)
+}
+/// POKEW addr, value - writes a word to the emulated DOS memory
+pub fn pokew(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    poke(interpreter, params, 2)
+}
+fn poke(interpreter: &mut Interpreter, params: &[Expression], len: usize) -> Res<()> {
+    let address = get_i64(&evaluate_exp(interpreter, &params[0])?)?;
+    let value = get_i64(&evaluate_exp(interpreter, &params[1])?)?;
+    interpreter.poke(address, &value.to_le_bytes()[..len])
 }
 
 pub fn ansipos(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
@@ -422,43 +646,43 @@ pub fn ansipos(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()>
 }
 
 pub fn backup(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("BACKUP")))
 }
 pub fn forward(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FORWARD")))
 }
 pub fn freshline(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FRESHLINE")))
 }
 pub fn wrusys(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("WRUSYS")))
 }
 pub fn rdusys(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("RDUSYS")))
 }
 pub fn newpwd(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("NEWPWD")))
 }
 pub fn opencap(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("OPENCAP")))
 }
 pub fn closecap(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CLOSECAP")))
 }
 pub fn message(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("MESSAGE")))
 }
 pub fn savescrn(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SAVESCRN")))
 }
 pub fn restscrn(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("RESTSCRN")))
 }
 pub fn sound(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SOUND")))
 }
 pub fn chat(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CHAT")))
 }
 
 pub fn sprint(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
@@ -503,77 +727,153 @@ pub fn rename(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
     }
     Ok(())
 }
-pub fn frewind(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn frewind(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.io.frewind(channel);
+    Ok(())
 }
-pub fn pokedw(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// POKEDW addr, value - writes a double word to the emulated DOS memory
+pub fn pokedw(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    poke(interpreter, params, 4)
 }
-pub fn dbglevel(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DBGLEVEL level
+/// Sets the debug level, with a level > 0 the executed statements are logged
+/// and sent to an attached debugger.
+pub fn dbglevel(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.debug_level = get_int(&evaluate_exp(interpreter, &params[0])?)?;
+    Ok(())
 }
 pub fn showon(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SHOWON")))
 }
 pub fn showoff(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SHOWOFF")))
 }
 pub fn pageon(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PAGEON")))
 }
 pub fn pageoff(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PAGEOFF")))
+}
+/// FSEEK channel, bytes, position - moves the file pointer relative to SEEK_SET, SEEK_CUR or SEEK_END
+pub fn fseek(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let offset = get_int(&evaluate_exp(interpreter, &params[1])?)?;
+    let origin = get_int(&evaluate_exp(interpreter, &params[2])?)?;
+    interpreter.io.fseek(channel, offset as i64, origin);
+    Ok(())
 }
-pub fn fseek(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fflush(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.io.fflush(channel);
+    Ok(())
 }
-pub fn fflush(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// FREAD channel, var, size - reads `size` bytes into `var` as it is stored in DOS memory
+pub fn fread(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    read_binary(interpreter, channel, &params[1], &params[2])
 }
-pub fn fread(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// FWRITE channel, exp, size - writes `size` bytes of `exp` as it is stored in DOS memory
+pub fn fwrite(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    write_binary(interpreter, channel, &params[1], &params[2])
 }
-pub fn fwrite(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+fn read_binary(
+    interpreter: &mut Interpreter,
+    channel: usize,
+    var: &Expression,
+    size: &Expression,
+) -> Res<()> {
+    let size = get_int(&evaluate_exp(interpreter, size)?)?;
+    let bytes = interpreter.io.fread(channel, size.max(0) as usize);
+    let old = evaluate_exp(interpreter, var)?;
+    let var_type = interpreter
+        .variable_type(var_name(var)?)
+        .filter(|var_type| *var_type != VariableType::Unknown)
+        .unwrap_or_else(|| value_type(&old));
+    set_variable(interpreter, var, from_binary(var_type, &old, &bytes))
 }
-pub fn fdefin(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+fn write_binary(
+    interpreter: &mut Interpreter,
+    channel: usize,
+    expr: &Expression,
+    size: &Expression,
+) -> Res<()> {
+    let value = evaluate_exp(interpreter, expr)?;
+    let size = get_int(&evaluate_exp(interpreter, size)?)?;
+    let var_type = match expr {
+        Expression::Identifier(name) => interpreter
+            .variable_type(name)
+            .filter(|var_type| *var_type != VariableType::Unknown),
+        _ => None,
+    };
+    let bytes = to_binary(var_type, &value, size.max(0) as usize);
+    interpreter.io.fwrite(channel, &bytes);
+    Ok(())
 }
-pub fn fdefout(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// FDEFIN channel - sets the input channel of FDGET & FDREAD
+pub fn fdefin(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.default_in = get_channel(interpreter, &params[0])?;
+    Ok(())
 }
-pub fn fdget(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// FDEFOUT channel - sets the output channel of FDPUT, FDPUTLN, FDPUTPAD & FDWRITE
+pub fn fdefout(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.default_out = get_channel(interpreter, &params[0])?;
+    Ok(())
 }
-pub fn fdput(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fdget(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = interpreter.default_in;
+    get_line(interpreter, channel, &params[0])
 }
-pub fn fdputln(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fdput(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = interpreter.default_out;
+    put_values(interpreter, channel, params)
 }
-pub fn fdputpad(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fdputln(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = interpreter.default_out;
+    put_values(interpreter, channel, params)?;
+    interpreter.io.fput(channel, "\n".to_string());
+    Ok(())
 }
-pub fn fdread(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fdputpad(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = interpreter.default_out;
+    put_padded(interpreter, channel, &params[0], &params[1])
 }
-pub fn fdwrite(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fdread(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = interpreter.default_in;
+    read_binary(interpreter, channel, &params[0], &params[1])
+}
+pub fn fdwrite(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = interpreter.default_out;
+    write_binary(interpreter, channel, &params[0], &params[1])
 }
 pub fn adjbytes(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADJBYTES")))
 }
-pub fn kbdstring(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn kbdstring(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let value = get_string(&evaluate_exp(interpreter, &params[0])?);
+    interpreter.ctx.print(&value)?;
+    interpreter.keyboard.keyboard.extend(value.chars());
+    Ok(())
 }
 pub fn alias(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ALIAS")))
 }
-pub fn redim(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn redim(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let var_name = var_name(&params[0])?;
+    let mut dims = Vec::new();
+    for expr in &params[1..] {
+        dims.push(get_int(&evaluate_exp(interpreter, expr)?)?);
+    }
+    let Some(arr) = interpreter.get_variable_mut(var_name)? else {
+        return Err(Box::new(IcyError::VariableNotFound(var_name.clone())));
+    };
+    redim_array(arr, &dims)
 }
 pub fn append(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("APPEND")))
 }
 pub fn copy(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
     let old = &evaluate_exp(interpreter, &params[0])?.to_string();
@@ -583,29 +883,28 @@ pub fn copy(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
     }
     Ok(())
 }
-pub fn kbdflush(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    // TODO?
+pub fn kbdflush(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.keyboard.clear();
     Ok(())
 }
-pub fn mdmflush(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    // TODO?
-    Ok(())
+pub fn mdmflush(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.ctx.flush_input()
 }
-pub fn keyflush(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    // TODO?
-    Ok(())
+pub fn keyflush(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.keyboard.clear();
+    interpreter.ctx.flush_input()
 }
 pub fn lastin(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("LASTIN")))
 }
 pub fn flag(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FLAG")))
 }
 pub fn download(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DOWNLOAD")))
 }
 pub fn wrusysdoor(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("WRUSYSDOOR")))
 }
 
 pub fn getaltuser(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
@@ -616,267 +915,420 @@ pub fn getaltuser(interpreter: &mut Interpreter, params: &[Expression]) -> Res<(
 }
 
 pub fn adjdbytes(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADJDBYTES")))
 }
 pub fn adjtbytes(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADJTBYTES")))
 }
 pub fn adjtfiles(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADJTFILES")))
 }
 pub fn lang(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("LANG")))
 }
 pub fn sort(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SORT")))
 }
 pub fn mousereg(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("MOUSEREG")))
 }
 pub fn scrfile(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SCRFILE")))
 }
 pub fn searchinit(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SEARCHINIT")))
 }
 pub fn searchfind(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SEARCHFIND")))
 }
 pub fn searchstop(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SEARCHSTOP")))
 }
 pub fn prfound(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PRFOUND")))
 }
 pub fn prfoundln(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PRFOUNDLN")))
 }
 pub fn tpaget(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPAGET")))
 }
 pub fn tpaput(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPAPUT")))
 }
 pub fn tpacget(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPACGET")))
 }
 pub fn tpacput(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPACPUT")))
 }
 pub fn tparead(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPAREAD")))
 }
 pub fn tpawrite(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPAWRITE")))
 }
 pub fn tpacread(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPACREAD")))
 }
 pub fn tpacwrite(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("TPACWRITE")))
 }
-pub fn bitset(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// BITSET var, bit - sets bit `bit` (0 = least significant) of `var`
+pub fn bitset(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    change_bit(interpreter, params, true)
 }
-pub fn bitclear(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// BITCLEAR var, bit - clears bit `bit` (0 = least significant) of `var`
+pub fn bitclear(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    change_bit(interpreter, params, false)
+}
+fn change_bit(interpreter: &mut Interpreter, params: &[Expression], set: bool) -> Res<()> {
+    let value = evaluate_exp(interpreter, &params[0])?;
+    let bit = get_int(&evaluate_exp(interpreter, &params[1])?)?;
+    if !(0..64).contains(&bit) {
+        return Ok(());
+    }
+    let bits = get_i64(&value)?;
+    let bits = if set {
+        bits | (1 << bit)
+    } else {
+        bits & !(1 << bit)
+    };
+    set_variable(interpreter, &params[0], with_int_type(&value, bits))
 }
 pub fn brag(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("BRAG")))
 }
 pub fn frealtuser(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FREALTUSER")))
 }
 pub fn setlmr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SETLMR")))
 }
 pub fn setenv(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SETENV")))
 }
-pub fn fcloseall(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn fcloseall(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.io.fclose_all();
+    Ok(())
 }
 pub fn declare(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("DECLARE")))
 }
 pub fn function(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FUNCTION")))
 }
 pub fn procedure(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PROCEDURE")))
 }
 pub fn pcall(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("PCALL")))
 }
 pub fn fpclr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FPCLR")))
 }
 pub fn begin(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("BEGIN")))
 }
 pub fn fend(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FEND")))
 }
-pub fn stackabort(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// STACKABORT bool
+/// If FALSE a GOSUB/call that exceeds the stack is skipped and STACKERR() returns TRUE
+/// instead of aborting the PPE.
+pub fn stackabort(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.state.stack_abort = is_true(&evaluate_exp(interpreter, &params[0])?)?;
+    Ok(())
 }
-pub fn dcreate(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DCREATE channel, name, exclusive, fieldinfo - creates & opens a data file,
+/// `fieldinfo` is a STRING array of "NAME,TYPE,LEN,DEC" field definitions
+pub fn dcreate(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    let exclusive = is_true(&evaluate_exp(interpreter, &params[2])?)?;
+    let fields = get_string_list(&evaluate_exp(interpreter, &params[3])?);
+    interpreter
+        .dbase
+        .create(&mut *interpreter.io, channel, &name, exclusive, &fields);
+    Ok(())
 }
-pub fn dopen(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+/// The strings of an array, a single value is a list with one string.
+fn get_string_list(value: &VariableValue) -> Vec<String> {
+    match value {
+        VariableValue::Dim1(_, values) => values.iter().map(get_string).collect(),
+        _ => vec![get_string(value)],
+    }
 }
-pub fn dclose(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+
+pub fn dopen(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    let exclusive = is_true(&evaluate_exp(interpreter, &params[2])?)?;
+    interpreter
+        .dbase
+        .open(&mut *interpreter.io, channel, &name, exclusive);
+    Ok(())
 }
-pub fn dsetalias(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dclose(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.close(channel);
+    Ok(())
 }
-pub fn dpack(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dsetalias(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let alias = get_string(&evaluate_exp(interpreter, &params[1])?);
+    interpreter.dbase.set_alias(channel, &alias);
+    Ok(())
 }
-pub fn dcloseall(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dpack(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.pack(channel);
+    Ok(())
 }
-pub fn dlock(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dcloseall(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    interpreter.dbase.close_all();
+    Ok(())
 }
-pub fn dlockr(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DLOCK channel - locks the current record
+pub fn dlock(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.lock(channel);
+    Ok(())
 }
-pub fn dlockg(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dlockr(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let recno = get_int(&evaluate_exp(interpreter, &params[1])?)?;
+    interpreter.dbase.lock_records(channel, &[recno]);
+    Ok(())
 }
-pub fn dunlock(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DLOCKG channel, records, count - locks the first `count` record numbers of an array
+pub fn dlockg(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let records = evaluate_exp(interpreter, &params[1])?;
+    let count = get_int(&evaluate_exp(interpreter, &params[2])?)?.max(0) as usize;
+    let records = match records {
+        VariableValue::Dim1(_, values) => values
+            .iter()
+            .take(count)
+            .map(get_int)
+            .collect::<Res<Vec<i32>>>()?,
+        value => vec![get_int(&value)?],
+    };
+    interpreter.dbase.lock_records(channel, &records);
+    Ok(())
 }
-pub fn dncreate(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dunlock(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.unlock(channel);
+    Ok(())
 }
-pub fn dnopen(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DNCREATE channel, name, expression - creates an index, its tag is the file name
+pub fn dncreate(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    let expression = get_string(&evaluate_exp(interpreter, &params[2])?);
+    interpreter
+        .dbase
+        .create_index(&mut *interpreter.io, channel, &name, &expression);
+    Ok(())
 }
-pub fn dnclose(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dnopen(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    interpreter
+        .dbase
+        .open_index(&mut *interpreter.io, channel, &name);
+    Ok(())
 }
-pub fn dncloseall(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dnclose(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    interpreter.dbase.close_index(channel, &name);
+    Ok(())
 }
-pub fn dnew(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dncloseall(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.close_indexes(channel);
+    Ok(())
 }
-pub fn dadd(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DTAG channel, name - selects the index order, "" selects the record order
+pub fn dtag(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    interpreter.dbase.select_tag(channel, &name);
+    Ok(())
 }
-pub fn dappend(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DSEEK channel, value - positions on the first record with a key >= value in the selected tag
+pub fn dseek(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let value = evaluate_exp(interpreter, &params[1])?;
+    let date_format = interpreter.icb_data.date_format;
+    interpreter.dbase.seek(channel, &value, date_format);
+    Ok(())
 }
-pub fn dtop(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dnew(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.new_record(channel);
+    Ok(())
 }
-pub fn dgo(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dadd(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.add(channel);
+    Ok(())
 }
-pub fn dbottom(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dappend(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.append_blank(channel);
+    Ok(())
 }
-pub fn dskip(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dtop(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.top(channel);
+    Ok(())
 }
-pub fn dblank(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dgo(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let recno = get_int(&evaluate_exp(interpreter, &params[1])?)?;
+    interpreter.dbase.go(channel, recno);
+    Ok(())
 }
-pub fn ddelete(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dbottom(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.bottom(channel);
+    Ok(())
+}
+pub fn dskip(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let count = get_int(&evaluate_exp(interpreter, &params[1])?)?;
+    interpreter.dbase.skip(channel, count);
+    Ok(())
 }
-pub fn drecall(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dblank(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.blank(channel);
+    Ok(())
 }
-pub fn dtag(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn ddelete(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.set_deleted(channel, true);
+    Ok(())
 }
-pub fn dseek(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn drecall(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    interpreter.dbase.set_deleted(channel, false);
+    Ok(())
 }
-pub fn dfblank(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dfblank(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    interpreter.dbase.blank_field(channel, &name);
+    Ok(())
 }
-pub fn dget(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DGET channel, name, var - reads a field of the current record into a variable
+pub fn dget(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    if let Some(value) = interpreter.dbase.get(channel, &name) {
+        set_variable(interpreter, &params[2], value)?;
+    }
+    Ok(())
 }
-pub fn dput(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn dput(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let channel = get_channel(interpreter, &params[0])?;
+    let name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    let value = evaluate_exp(interpreter, &params[2])?;
+    let date_format = interpreter.icb_data.date_format;
+    interpreter.dbase.put(channel, &name, &value, date_format);
+    Ok(())
 }
-pub fn dfcopy(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+/// DFCOPY channel1, name1, channel2, name2 - copies a field to a field of another channel
+pub fn dfcopy(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let from = get_channel(interpreter, &params[0])?;
+    let from_name = get_string(&evaluate_exp(interpreter, &params[1])?);
+    let to = get_channel(interpreter, &params[2])?;
+    let to_name = get_string(&evaluate_exp(interpreter, &params[3])?);
+    if let Some(value) = interpreter.dbase.get(from, &from_name) {
+        let date_format = interpreter.icb_data.date_format;
+        interpreter.dbase.put(to, &to_name, &value, date_format);
+    }
+    Ok(())
 }
 
 pub fn eval(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("EVAL")))
 }
 pub fn account(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ACCOUNT")))
 }
 pub fn recordusage(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("RECORDUSAGE")))
 }
 pub fn msgtofile(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("MSGTOFILE")))
 }
 pub fn qwklimits(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("QWKLIMITS")))
 }
 pub fn command(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("COMMAND")))
 }
 pub fn uselmrs(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("USELMRS")))
 }
 pub fn confinfo(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("CONFINFO")))
 }
 pub fn adjtubytes(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADJTUBYTES")))
 }
 pub fn grafmode(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("GRAFMODE")))
 }
 pub fn adduser(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("ADDUSER")))
 }
 pub fn killmsg(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("KILLMSG")))
 }
-pub fn chdir(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn chdir(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let dir = get_string(&evaluate_exp(interpreter, &params[0])?);
+    if let Err(err) = interpreter.io.chdir(&dir) {
+        log::error!("Error changing directory to {}: {}", dir, err);
+    }
+    Ok(())
 }
-pub fn mkdir(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+pub fn mkdir(interpreter: &mut Interpreter, params: &[Expression]) -> Res<()> {
+    let dir = get_string(&evaluate_exp(interpreter, &params[0])?);
+    if let Err(err) = interpreter.io.mkdir(&dir) {
+        log::error!("Error creating directory {}: {}", dir, err);
+    }
+    Ok(())
 }
 pub fn redir(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("REDIR")))
 }
 pub fn fdowraka(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOWRAKA")))
 }
 pub fn fdoaddaka(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOADDAKA")))
 }
 pub fn fdowrorg(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOWRORG")))
 }
 pub fn fdoaddorg(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOADDORG")))
 }
 pub fn fdoqmod(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOQMOD")))
 }
 pub fn fdoqadd(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOQADD")))
 }
 pub fn fdoqdel(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("FDOQDEL")))
 }
 pub fn sounddelay(interpreter: &Interpreter, params: &[Expression]) -> Res<()> {
-    panic!("TODO")
+    Err(Box::new(IcyError::StatementNotImplemented("SOUNDDELAY")))
 }
@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use ppl_engine::ast::{
+    Block, Declaration, Expression, Implementation, Program, Statement, VarInfo, VariableType,
+};
+
+/// Scope of the main program, it holds the global variables.
+pub const GLOBAL_SCOPE: usize = 0;
+
+pub fn calc_table(blk: &Block) -> HashMap<String, usize> {
+    let mut res = HashMap::new();
+    for i in 0..blk.statements.len() {
+        if let Statement::Label(label) = &blk.statements[i] {
+            res.insert(label.clone(), i);
+        }
+    }
+    res
+}
+
+/// Variable slots and labels of the main program or of a procedure/function.
+#[derive(Default)]
+pub struct Scope {
+    /// name of the procedure/function, "MAIN" for the main program
+    pub name: String,
+    slots: HashMap<String, usize>,
+    types: Vec<VariableType>,
+    pub label_table: HashMap<String, usize>,
+}
+
+impl Scope {
+    fn new(name: &str, block: &Block) -> Self {
+        Self {
+            name: name.to_string(),
+            slots: HashMap::new(),
+            types: Vec::new(),
+            label_table: calc_table(block),
+        }
+    }
+
+    pub fn get_slot(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).copied()
+    }
+
+    /// Type of the variable in `slot`, variables that weren't declared are `Unknown`.
+    pub fn get_type(&self, slot: usize) -> VariableType {
+        self.types[slot]
+    }
+
+    /// Returns the slot of `name`, a new slot is added if the variable is not yet known.
+    pub fn add_slot(&mut self, name: &str, var_type: VariableType) -> usize {
+        if let Some(slot) = self.get_slot(name) {
+            return slot;
+        }
+        let slot = self.types.len();
+        self.slots.insert(name.to_string(), slot);
+        self.types.push(var_type);
+        slot
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+/// Where a variable of the program lives, in the global frame or in the frame of the
+/// procedure/function it's used in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VarSlot {
+    Global(usize),
+    Local(usize),
+}
+
+/// Hasher for the addresses of identifiers, they're spread with a multiplication.
+#[derive(Default)]
+pub struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.write_u8(*b);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_usize(i as usize);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.0 = (self.0 ^ i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+/// Variables & labels of a program resolved to slots once before it runs.
+/// Scope 0 is the main program, followed by the procedures and then the functions.
+/// The parameters of a procedure/function occupy the first slots of its scope, followed by its
+/// declared local variables.
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+    procedures: HashMap<String, usize>,
+    functions: HashMap<String, usize>,
+    function_offset: usize,
+    /// the slots of the identifiers in the program by the address of their name, the program
+    /// isn't changed while it runs so each occurrence is resolved once
+    variables: HashMap<*const u8, VarSlot, BuildHasherDefault<AddressHasher>>,
+}
+
+impl SymbolTable {
+    pub fn new(prg: &Program) -> Self {
+        let mut globals = Scope::new("MAIN", &prg.main_block);
+        for decl in &prg.declarations {
+            if let Declaration::Variable(var_type, infos) = decl {
+                for var_info in infos {
+                    globals.add_slot(var_info.get_name(), *var_type);
+                }
+            }
+        }
+        let mut scopes = vec![globals];
+        let function_offset = 1 + prg.procedure_implementations.len();
+        let mut procedures = HashMap::new();
+        let mut functions = HashMap::new();
+
+        for (i, f) in prg.procedure_implementations.iter().enumerate() {
+            if let Declaration::Procedure(name, params) = &f.declaration {
+                procedures.insert(name.clone(), i);
+                scopes.push(Self::create_scope(name, f, params));
+            } else {
+                scopes.push(Scope::new("", &f.block));
+            }
+        }
+        for (i, f) in prg.function_implementations.iter().enumerate() {
+            if let Declaration::Function(name, params, return_type) = &f.declaration {
+                functions.insert(name.clone(), i);
+                let mut scope = Self::create_scope(name, f, params);
+                // the return value is assigned to the function name
+                scope.add_slot(name, *return_type);
+                scopes.push(scope);
+            } else {
+                scopes.push(Scope::new("", &f.block));
+            }
+        }
+
+        let mut table = Self {
+            scopes,
+            procedures,
+            functions,
+            function_offset,
+            variables: HashMap::default(),
+        };
+        table.resolve_declarations(GLOBAL_SCOPE, &prg.declarations);
+        table.resolve_block(GLOBAL_SCOPE, &prg.main_block.statements);
+        for (i, f) in prg.procedure_implementations.iter().enumerate() {
+            table.resolve_declarations(1 + i, &f.variable_declarations);
+            table.resolve_block(1 + i, &f.block.statements);
+        }
+        for (i, f) in prg.function_implementations.iter().enumerate() {
+            table.resolve_declarations(function_offset + i, &f.variable_declarations);
+            table.resolve_block(function_offset + i, &f.block.statements);
+        }
+        table
+    }
+
+    fn create_scope(name: &str, f: &Implementation, params: &[Declaration]) -> Scope {
+        let mut scope = Scope::new(name, &f.block);
+        for param in params {
+            if let Declaration::Variable(var_type, infos)
+            | Declaration::VarParameter(var_type, infos) = param
+            {
+                scope.add_slot(infos[0].get_name(), *var_type);
+            }
+        }
+        // the declared locals follow the parameters
+        for decl in &f.variable_declarations {
+            if let Declaration::Variable(var_type, infos) = decl {
+                for var_info in infos {
+                    scope.add_slot(var_info.get_name(), *var_type);
+                }
+            }
+        }
+        scope
+    }
+
+    /// Slot of an identifier of the program, `name` has to be the name in the program itself.
+    pub fn variable(&self, name: &str) -> Option<VarSlot> {
+        self.variables.get(&name.as_ptr()).copied()
+    }
+
+    /// Resolves an identifier of `scope`. Declared locals & parameters shadow the globals,
+    /// variables that aren't declared are predefined globals like U_SEC.
+    fn resolve_variable(&mut self, scope: usize, name: &str) {
+        let slot = match self.scopes[scope].get_slot(name) {
+            Some(slot) if scope != GLOBAL_SCOPE => VarSlot::Local(slot),
+            _ => VarSlot::Global(self.scopes[GLOBAL_SCOPE].add_slot(name, VariableType::Unknown)),
+        };
+        self.variables.insert(name.as_ptr(), slot);
+    }
+
+    fn resolve_var_info(&mut self, scope: usize, var_info: &VarInfo) {
+        self.resolve_variable(scope, var_info.get_name());
+        match var_info {
+            VarInfo::Var0(_) => {}
+            VarInfo::Var1(_, d1) => self.resolve_expression(scope, d1),
+            VarInfo::Var2(_, d1, d2) => {
+                self.resolve_expression(scope, d1);
+                self.resolve_expression(scope, d2);
+            }
+            VarInfo::Var3(_, d1, d2, d3) => {
+                self.resolve_expression(scope, d1);
+                self.resolve_expression(scope, d2);
+                self.resolve_expression(scope, d3);
+            }
+        }
+    }
+
+    fn resolve_declarations(&mut self, scope: usize, declarations: &[Declaration]) {
+        for decl in declarations {
+            if let Declaration::Variable(_, infos) = decl {
+                for var_info in infos {
+                    self.resolve_var_info(scope, var_info);
+                }
+            }
+        }
+    }
+
+    fn resolve_block(&mut self, scope: usize, statements: &[Statement]) {
+        for stmt in statements {
+            self.resolve_statement(scope, stmt);
+        }
+    }
+
+    fn resolve_statement(&mut self, scope: usize, stmt: &Statement) {
+        match stmt {
+            Statement::Block(block) => self.resolve_block(scope, block),
+            Statement::DoWhile(cond, block) => {
+                self.resolve_expression(scope, cond);
+                self.resolve_block(scope, block);
+            }
+            Statement::If(cond, stmt) | Statement::While(cond, stmt) => {
+                self.resolve_expression(scope, cond);
+                self.resolve_statement(scope, stmt);
+            }
+            Statement::IfThen(cond, block, else_if_blocks, else_block) => {
+                self.resolve_expression(scope, cond);
+                self.resolve_block(scope, block);
+                for (cond, block) in else_if_blocks {
+                    self.resolve_expression(scope, cond);
+                    self.resolve_block(scope, block);
+                }
+                if let Some(block) = else_block {
+                    self.resolve_block(scope, block);
+                }
+            }
+            Statement::For(var, from, to, step, block) => {
+                self.resolve_expression(scope, var);
+                self.resolve_expression(scope, from);
+                self.resolve_expression(scope, to);
+                if let Some(step) = step {
+                    self.resolve_expression(scope, step);
+                }
+                self.resolve_block(scope, block);
+            }
+            Statement::Let(var_info, expr) => {
+                self.resolve_var_info(scope, var_info);
+                self.resolve_expression(scope, expr);
+            }
+            Statement::Call(_, params) | Statement::ProcedureCall(_, params) => {
+                for expr in params {
+                    self.resolve_expression(scope, expr);
+                }
+            }
+            Statement::Inc(name) | Statement::Dec(name) => self.resolve_variable(scope, name),
+            _ => {}
+        }
+    }
+
+    fn resolve_expression(&mut self, scope: usize, expr: &Expression) {
+        match expr {
+            Expression::Identifier(name) => self.resolve_variable(scope, name),
+            Expression::Const(_) => {}
+            Expression::Parens(expr)
+            | Expression::Not(expr)
+            | Expression::Minus(expr)
+            | Expression::Plus(expr) => self.resolve_expression(scope, expr),
+            Expression::FunctionCall(name, params) => {
+                // without a function of that name it's an array element
+                if !self.functions.contains_key(name) {
+                    self.resolve_variable(scope, name);
+                }
+                for expr in params {
+                    self.resolve_expression(scope, expr);
+                }
+            }
+            Expression::PredefinedFunctionCall(_, params) => {
+                for expr in params {
+                    self.resolve_expression(scope, expr);
+                }
+            }
+            Expression::BinaryExpression(_, l_value, r_value) => {
+                self.resolve_expression(scope, l_value);
+                self.resolve_expression(scope, r_value);
+            }
+        }
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn scope(&self, scope: usize) -> &Scope {
+        &self.scopes[scope]
+    }
+
+    pub fn scope_mut(&mut self, scope: usize) -> &mut Scope {
+        &mut self.scopes[scope]
+    }
+
+    /// The statements of the main program or the procedure/function of a scope.
+    pub fn block<'p>(&self, prg: &'p Program, scope: usize) -> &'p Block {
+        if scope == GLOBAL_SCOPE {
+            &prg.main_block
+        } else if scope < self.function_offset {
+            &prg.procedure_implementations[scope - 1].block
+        } else {
+            &prg.function_implementations[scope - self.function_offset].block
+        }
+    }
+
+    /// Returns the index of the procedure implementation and its scope.
+    pub fn get_procedure(&self, name: &str) -> Option<(usize, usize)> {
+        self.procedures.get(name).map(|i| (*i, 1 + *i))
+    }
+
+    /// Returns the index of the function implementation and its scope.
+    pub fn get_function(&self, name: &str) -> Option<(usize, usize)> {
+        self.functions
+            .get(name)
+            .map(|i| (*i, self.function_offset + *i))
+    }
+}
@@ -1,19 +1,39 @@
 #[cfg(test)]
 mod interpreter_tests {
-    use crate::{data::IcyBoardData, VT};
+    use std::{
+        cell::RefCell,
+        collections::VecDeque,
+        io::{Cursor, Write},
+        ops::Deref,
+        path::{Path, PathBuf},
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use crate::{data::IcyBoardData, pcb_date::DateFormat, VT};
 
     use ppl_engine::parser::parse_program;
 
-    use crate::{run, ExecutionContext, MemoryIO, PCBoardIO, Res};
+    use crate::{
+        debugger::Debugger, run, run_with_debugger, DiskIO, ExecutionContext, ExecutionLimits,
+        MemoryIO, PCBoardIO, Res,
+    };
 
     struct TestContext {
         output: String,
+        input: VecDeque<char>,
+        /// set while the input is polled without a key being available
+        waiting_since: Option<Instant>,
         vt: VT,
     }
+
+    const INPUT_EXHAUSTED_TIMEOUT: Duration = Duration::from_secs(2);
     impl TestContext {
         pub fn new() -> Self {
             Self {
                 output: String::new(),
+                input: VecDeque::new(),
+                waiting_since: None,
                 vt: VT::new(),
             }
         }
@@ -27,7 +47,16 @@ mod interpreter_tests {
             Ok(())
         }
         fn get_char(&mut self) -> Res<Option<char>> {
-            todo!()
+            if let Some(ch) = self.input.pop_front() {
+                self.waiting_since = None;
+                return Ok(Some(ch));
+            }
+            // INKEY & key timeouts poll the empty input, a wait for a key that never comes fails
+            let since = *self.waiting_since.get_or_insert_with(Instant::now);
+            if since.elapsed() > INPUT_EXHAUSTED_TIMEOUT {
+                return Err("the test input is exhausted".into());
+            }
+            Ok(None)
         }
 
         fn print(&mut self, str: &str) -> Res<()> {
@@ -39,8 +68,9 @@ mod interpreter_tests {
             Ok(())
         }
 
-        fn send_to_com(&mut self, _data: &str) -> Res<()> {
-            todo!()
+        fn flush_input(&mut self) -> Res<()> {
+            self.input.clear();
+            Ok(())
         }
 
         fn read(&mut self) -> Res<String> {
@@ -68,17 +98,128 @@ PRINT B
             "1,0,1",
         ); // This is no error. Bools were printed as numbers
     }
-    /*
-        #[test]
-        fn test_real() {
-            check_output(r#"
-    REAL v
-    v = 0.653
-    v = v + 10
-    PRINT v
-    "#, "10.653"); // This is no error. Bools were printed as numbers
-        }
-    */
+    #[test]
+    fn test_real() {
+        check_output(
+            r#"
+REAL v
+v = 0.653
+v = v + 10
+PRINT v
+"#,
+            "10.653",
+        );
+    }
+
+    #[test]
+    fn test_money() {
+        check_output(
+            r#"
+MONEY m
+m = 1.5
+PRINT m, ","
+m = m * 3 + 0.255
+PRINT m, ",", -m, ",", m / 0
+"#,
+            "$1.50,$4.76,-$4.76,$0.00",
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        check_output(
+            r#"
+PRINT TOBYTE(256 + 42), ",", TOSBYTE(200), ",", TOWORD(-1), ",", TOSWORD(40000), ","
+PRINT TOUNSIGNED(-1), ",", TOINTEGER("4294967295"), ",", TOINTEGER(3.99), ","
+PRINT TOBOOLEAN(5), TOBOOLEAN(0), ",", TOMONEY(2.456), ",", TOREAL("1.25") * 2, ","
+PRINT TOBIGSTR(42) + "!", ",", TODATE("03-01-24"), ",", TOTIME("12:30"), ",", TOEDATE(0)
+"#,
+            "42,-56,65535,-25536,4294967295,-1,3,10,$2.46,2.5,42!,03-01-24,12:30:00,01-01-1900",
+        );
+    }
+
+    #[test]
+    fn test_bit_functions() {
+        check_output(
+            r#"
+BYTE b
+WORD w
+INTEGER i
+b = 0F0h
+w = 1
+PRINT BAND(b, 3Ch), ",", BOR(b, 0Fh), ",", BXOR(b, 0FFh), ",", BNOT(b), ",", BNOT(w), ","
+i = 0
+PRINT BNOT(i), ",", ISBITSET(b, 4), ISBITSET(b, 3), ",", B2W(34h, 12h), ","
+BITSET b, 0
+BITCLEAR b, 7
+BITSET w, 15
+BITSET i, 31
+PRINT b, ",", w, ",", i
+"#,
+            "48,255,15,15,65534,-1,10,4660,113,32769,-2147483648",
+        );
+    }
+
+    #[test]
+    fn test_basic_reals() {
+        // MBF 1.0 = 00 00 00 81, 10.0 = 00 00 20 84, -0.5 = 00 00 80 80
+        check_output(
+            r#"
+PRINT BS2I(81000000h), ",", BS2I(84200000h), ",", BS2I(80800000h), ",", BS2I(0), ","
+PRINT BS2I(I2BS(123.5)), ",", BD2I(I2BD(-1234.25))
+"#,
+            "1,10,-0.5,0,123.5,-1234.25",
+        );
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        check_output(
+            r#"
+INTEGER seg, off, addr, i
+WORD w
+VARSEG w, seg
+VAROFF w, off
+VARADDR i, addr
+w = 1234h
+PRINT PEEKB(MKADDR(seg, off)), ",", PEEKW(MKADDR(seg, off)), ","
+POKEW MKADDR(seg, off), 4321
+POKEB addr, 0FFh
+POKEB addr + 1, 1
+PRINT w, ",", i, ",", PEEKW(MKADDR(40h, 13h)), ",", PEEKB(MKADDR(0, 449h)), ","
+POKEDW MKADDR(1000h, 0), -2
+PRINT PEEKDW(MKADDR(1000h, 0)), ",", PEEKB(MKADDR(0FFFFh, 10h))
+"#,
+            "52,4660,4321,511,640,3,-2,0",
+        );
+    }
+
+    #[test]
+    fn test_dointr() {
+        check_output(
+            r#"
+DOINTR 21h, 3000h, 0, 0, 0, 0, 0, 0, 0, 0
+PRINT REGAL(), ".", REGAH(), ",", REGCF(), ","
+DOINTR 21h, 2A00h, 0, 0, 0, 0, 0, 0, 0, 0
+PRINT REGCX() > 2000, REGDH() >= 1, REGDH() <= 12, ","
+DOINTR 16h, 0100h, 0, 0, 0, 0, 0, 0, 0, 0
+PRINT BAND(REGF(), 40h), ","
+DOINTR 13h, 0, 0, 0, 0, 0, 0, 0, 0, 0
+PRINT REGCF()
+"#,
+            "6.22,0,111,64,1",
+        );
+    }
+
+    #[test]
+    fn test_fmtreal() {
+        check_output(
+            r#"
+PRINT "[", FMTREAL(3.14159, 8, 2), "][", FMTREAL(-2, 0, 3), "]"
+"#,
+            "[    3.14][-2.000]",
+        );
+    }
     #[test]
     fn test_byte_overflow() {
         check_output(
@@ -250,6 +391,19 @@ PRINT B
         assert_eq!(out, ctx.output);
     }
 
+    fn check_input(prg: &str, input: &str, out: &str) {
+        let mut ctx = TestContext::new();
+        ctx.input = input.chars().collect();
+        let mut io = MemoryIO::new();
+        let data = IcyBoardData {
+            yes_char: 'Y',
+            no_char: 'N',
+            ..Default::default()
+        };
+        run(&parse_program(prg), &mut ctx, &mut io, &data).unwrap();
+        assert_eq!(out, ctx.output);
+    }
+
     #[test]
     fn test_println() {
         let mut ctx = TestContext::new();
@@ -395,6 +549,7 @@ FPUT 1, "Hello World"
 FCLOSE 1
 "#;
         let mut io = MemoryIO::new();
+        io.add_dir(r"C:\PCB\MAIN");
         let mut ctx = TestContext::new();
         run(
             &parse_program(prg),
@@ -403,9 +558,8 @@ FCLOSE 1
             &IcyBoardData::default(),
         )
         .unwrap();
-        assert!(io.files.contains_key(r"C:\PCB\MAIN\PPE.LOG"));
-        let content = io.files.get(r"C:\PCB\MAIN\PPE.LOG").unwrap();
-        assert!(content == "Hello World");
+        let content = io.read_file(r"C:\PCB\MAIN\PPE.LOG").unwrap();
+        assert_eq!(b"Hello World", &content[..]);
     }
 
     #[test]
@@ -418,6 +572,7 @@ FCLOSE 1
         FCLOSE 1
                 "#;
         let mut io = MemoryIO::new();
+        io.add_dir(r"C:\PCB\MAIN");
         let mut ctx = TestContext::new();
         run(
             &parse_program(prg),
@@ -426,7 +581,7 @@ FCLOSE 1
             &IcyBoardData::default(),
         )
         .unwrap();
-        assert!(io.files.contains_key(r"C:\PCB\MAIN\PPE.LOG"));
+        assert!(io.file_exists(r"C:\PCB\MAIN\PPE.LOG"));
     }
 
     #[test]
@@ -457,6 +612,43 @@ FCLOSE 1
         check_output_withio(prg, &mut io, "Line  1: Hello\nLine  2: World\nLine  3: !\n");
     }
 
+    #[test]
+    fn test_default_channels() {
+        let prg = r#"
+STRING s
+INTEGER i
+FCREATE 2, "OUT.TXT", O_RW, S_DN
+FDEFOUT 2
+FDPUT "a", 1
+FDPUTLN "b"
+FDPUTPAD "name", 6
+FPUTPAD 2, "truncated", 5
+i = 42
+FDWRITE i, 2
+FCLOSEALL
+FOPEN 3, "OUT.TXT", O_RD, S_DN
+FDEFIN 3
+FDGET s
+PRINT s, ","
+FDGET s
+PRINT s, "|,"
+FGET 3, s
+PRINT s, ","
+i = 0
+FDREAD i, 2
+PRINT i, FERR(3)
+FCLOSEALL
+FGET 3, s
+PRINT FERR(3)
+"#;
+        let mut io = MemoryIO::new();
+        check_output_withio(prg, &mut io, "a1b,name  |,trunc,4201");
+        assert_eq!(
+            b"a1b\nname  \ntrunc\n*\0",
+            &io.read_file("OUT.TXT").unwrap()[..]
+        );
+    }
+
     #[test]
     fn test_procedure() {
         check_output(
@@ -489,6 +681,325 @@ ENDFUNC
         ); // This is no error. Bools were printed as numbers
     }
 
+    #[test]
+    fn test_var_parameters() {
+        check_output(
+            r#"
+DECLARE PROCEDURE Swap(VAR INTEGER a, VAR INTEGER b)
+INTEGER x, y, arr(2)
+x = 1
+y = 2
+arr(1) = 3
+Swap(x, y)
+Swap(y, arr(1))
+PRINT x, y, arr(1)
+
+PROCEDURE Swap(VAR INTEGER a, VAR INTEGER b)
+    INTEGER t
+    t = a
+    a = b
+    b = t
+ENDPROC
+"#,
+            "231",
+        );
+    }
+
+    #[test]
+    fn test_locals_and_globals() {
+        check_output(
+            r#"
+DECLARE PROCEDURE Test(INTEGER g)
+INTEGER g, h
+g = 1
+Test(5)
+PRINT g, h
+
+PROCEDURE Test(INTEGER g)
+    g = g + 1
+    h = g
+ENDPROC
+"#,
+            "16",
+        );
+    }
+
+    #[test]
+    fn test_declared_locals() {
+        check_output(
+            r#"
+DECLARE PROCEDURE Shadow()
+DECLARE PROCEDURE Counter()
+DECLARE PROCEDURE Table(INTEGER n)
+DECLARE FUNCTION Depth(INTEGER n) INTEGER
+INTEGER i
+i = 1
+Shadow()
+PRINT i, ","
+Counter()
+Counter()
+Table(2)
+PRINT Depth(3)
+
+PROCEDURE Shadow()
+    INTEGER i
+    i = 99
+ENDPROC
+
+PROCEDURE Counter()
+    INTEGER c
+    INC c
+    PRINT c, ","
+ENDPROC
+
+PROCEDURE Table(INTEGER n)
+    INTEGER a(3), b(n)
+    a(3) = 7
+    b(n) = 8
+    PRINT a(0), a(3), b(2), ","
+ENDPROC
+
+FUNCTION Depth(INTEGER n) INTEGER
+    INTEGER local
+    local = n
+    Depth = local
+    IF (n > 0) Depth = Depth(n - 1) * 10 + local
+ENDFUNC
+"#,
+            "1,1,1,078,123",
+        );
+    }
+
+    #[test]
+    fn test_recursion() {
+        check_output(
+            r#"
+DECLARE FUNCTION Fac(INTEGER n) INTEGER
+DECLARE FUNCTION Nothing() INTEGER
+PRINT Fac(6), ",", Nothing()
+
+FUNCTION Fac(INTEGER n) INTEGER
+    IF (n <= 1) THEN
+        Fac = 1
+    ELSE
+        Fac = n * Fac(n - 1)
+    ENDIF
+ENDFUNC
+
+FUNCTION Nothing() INTEGER
+ENDFUNC
+"#,
+            "720,0",
+        );
+    }
+
+    #[test]
+    fn test_symbol_resolution() {
+        use crate::symbols::{SymbolTable, VarSlot};
+        use ppl_engine::ast::{Expression, Statement};
+        let prg = parse_program(
+            r#"
+DECLARE PROCEDURE Test(INTEGER g)
+INTEGER g
+g = 1
+PROCEDURE Test(INTEGER g)
+    INTEGER l
+    l = g + U_SEC
+ENDPROC
+"#,
+        );
+        let symbols = SymbolTable::new(&prg);
+        let Statement::Let(global, _) = &prg.main_block.statements[0] else {
+            panic!("assignment expected");
+        };
+        assert_eq!(
+            Some(VarSlot::Global(0)),
+            symbols.variable(global.get_name())
+        );
+        // a copy of the name isn't part of the program
+        assert_eq!(None, symbols.variable(&global.get_name().clone()));
+
+        let Statement::Let(local, expr) = &prg.procedure_implementations[0].block.statements[0]
+        else {
+            panic!("assignment expected");
+        };
+        assert_eq!(Some(VarSlot::Local(1)), symbols.variable(local.get_name()));
+        let Expression::BinaryExpression(_, param, predefined) = expr.as_ref() else {
+            panic!("addition expected");
+        };
+        let (Expression::Identifier(param), Expression::Identifier(predefined)) =
+            (param.as_ref(), predefined.as_ref())
+        else {
+            panic!("identifiers expected");
+        };
+        assert_eq!(Some(VarSlot::Local(0)), symbols.variable(param));
+        assert_eq!(Some(VarSlot::Global(1)), symbols.variable(predefined));
+    }
+
+    #[test]
+    fn test_call_depth() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let res = run(
+            &parse_program(
+                r#"
+DECLARE PROCEDURE Endless()
+Endless()
+PROCEDURE Endless()
+    Endless()
+ENDPROC
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        );
+        assert!(res.unwrap_err().to_string().contains("call depth"));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_debugger() {
+        let prg = parse_program(
+            r#"
+DECLARE PROCEDURE Add(VAR INTEGER v)
+INTEGER i
+i = 1
+GOSUB sub
+Add(i)
+PRINT i
+END
+:sub
+i = i + 1
+RETURN
+PROCEDURE Add(VAR INTEGER v)
+    v = v + 10
+ENDPROC
+"#,
+        );
+        let commands = "break sub\nbreak Add:0\nbreak nowhere\nc\nbt\np i\nset i 5\nn\nc\nset v 20\nout\np i\nc\n";
+        let output = SharedBuffer::default();
+        let debugger = Debugger::new(
+            Box::new(Cursor::new(commands.as_bytes().to_vec())),
+            Box::new(output.clone()),
+        );
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        run_with_debugger(&prg, &mut ctx, &mut io, &IcyBoardData::default(), debugger).unwrap();
+        assert_eq!("30", ctx.output);
+
+        // the statements are left out, only the reason and location is checked
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let lines: Vec<String> = output
+            .lines()
+            .map(|l| l.split(' ').take(3).collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(
+            vec![
+                "STOPPED step MAIN:0",
+                "OK",
+                "OK",
+                "ERR label nowhere",
+                "OK",
+                "STOPPED breakpoint MAIN:6",
+                "MAIN:6",
+                "MAIN:1 GOSUB",
+                "OK",
+                "i = Integer(1)",
+                "OK",
+                "OK",
+                "OK",
+                "STOPPED step MAIN:7",
+                "OK",
+                "STOPPED breakpoint Add:0",
+                "OK",
+                "OK",
+                "STOPPED step MAIN:3",
+                "i = Integer(30)",
+                "OK",
+                "OK",
+                "TERMINATED",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn test_dbglevel() {
+        check_output(
+            r#"
+PRINT DBGLEVEL(), ","
+DBGLEVEL 2
+PRINT DBGLEVEL()
+"#,
+            "0,2",
+        );
+    }
+
+    #[test]
+    fn test_statement_limit() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let data = IcyBoardData {
+            ppe_limits: ExecutionLimits {
+                max_statements: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let res = run(&parse_program(":loop\nGOTO loop"), &mut ctx, &mut io, &data);
+        assert!(res.unwrap_err().to_string().contains("100 statements"));
+    }
+
+    #[test]
+    fn test_stack_abort() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let data = IcyBoardData {
+            ppe_limits: ExecutionLimits {
+                max_call_depth: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let prg = r#"
+INTEGER i
+PRINT STACKLEFT(), ",", STACKERR(), ","
+STACKABORT FALSE
+GOSUB deeper
+PRINT ",", STACKERR()
+END
+:deeper
+INC i
+PRINT i
+GOSUB deeper
+RETURN
+"#;
+        run(&parse_program(prg), &mut ctx, &mut io, &data).unwrap();
+        assert_eq!("384,0,123,1", ctx.output);
+
+        let mut ctx = TestContext::new();
+        let res = run(
+            &parse_program(&prg.replace("STACKABORT FALSE", "")),
+            &mut ctx,
+            &mut io,
+            &data,
+        );
+        assert!(res.unwrap_err().to_string().contains("call depth"));
+        assert_eq!("384,0,123", ctx.output);
+    }
+
     #[test]
     fn test_dim1() {
         check_output(
@@ -501,4 +1012,1209 @@ PRINT ARR(1) + ARR(2)
             "5",
         );
     }
+
+    #[test]
+    fn test_dim2_dim3() {
+        check_output(
+            r#"
+INTEGER A(2, 3)
+STRING S(1, 1, 2)
+A(0, 0) = 1
+A(2, 3) = 5
+S(1, 1, 2) = "x"
+S(0, 1, 0) = 42
+PRINT A(0, 0) + A(2, 3), ",", A(1, 1), ",", S(1, 1, 2), S(0, 1, 0)
+"#,
+            "6,0,x42",
+        );
+    }
+
+    #[test]
+    fn test_redim() {
+        check_output(
+            r#"
+INTEGER A(1)
+A(0) = 7
+A(1) = 8
+REDIM A, 3
+A(3) = 9
+PRINT A(0), A(1), A(2), A(3)
+"#,
+            "7809",
+        );
+    }
+
+    #[test]
+    fn test_array_out_of_bounds() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let res = run(
+            &parse_program("INTEGER A(2)\nA(3) = 1"),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        );
+        assert!(res.unwrap_err().to_string().contains("out of bounds"));
+    }
+
+    #[test]
+    fn test_inputstr() {
+        check_input(
+            r#"
+STRING s
+INPUTSTR "Name:", s, 7, 5, "", UPCASE
+PRINT ",", s
+"#,
+            "jo\x08hn doe\n",
+            "Name:JO\x08 \x08HN D,JHN D",
+        );
+    }
+
+    #[test]
+    fn test_inputint() {
+        check_input(
+            r#"
+INTEGER i
+INPUTINT "Num:", i, 7
+PRINT i + 1
+"#,
+            "x-\n-41\n",
+            "Num:-\nNum:-41\n-40",
+        );
+    }
+
+    #[test]
+    fn test_inputyn() {
+        check_input(
+            r#"
+BOOLEAN b
+STRING s
+INPUTYN "?", b, 7
+INPUTYN "?", s, 7
+PRINT b, s
+"#,
+            "xy\nn\n",
+            "?Y\n?N\n1N",
+        );
+    }
+
+    #[test]
+    fn test_date_functions() {
+        check_output(
+            r#"
+DATE d
+STRING s
+d = MKDATE(2024, 2, 29)
+PRINT d, ",", YEAR(d), ",", MONTH(d), ",", DAY(d), ",", DOW(d), ","
+d = d + 1
+s = d
+PRINT s, ",", d - MKDATE(2024, 1, 1), ",", MKDATE(2023, 2, 29), ","
+PRINT YEAR("12-31-99"), ",", DOW(0)
+"#,
+            "02-29-24,2024,2,29,4,03-01-24,60,01-01-00,1999,1",
+        );
+    }
+
+    #[test]
+    fn test_date_defaults() {
+        check_output(
+            r#"
+EDATE e
+DDATE d
+PRINT e, ",", d
+"#,
+            "01-01-1900,01-01-00",
+        );
+    }
+
+    #[test]
+    fn test_user_dates() {
+        use crate::{data::UserRecord, pcb_date::PcbDate};
+        let dir = TempDir::new("users");
+        let mut user = vec![b' '; 400];
+        user[..5].copy_from_slice(b"SYSOP");
+        user[187..193].copy_from_slice(b"240315");
+        user[385..389].copy_from_slice(&2u32.to_le_bytes());
+        std::fs::write(dir.join("USERS"), user).unwrap();
+
+        // two applications, the password section is at offset 60 of the 120 byte records
+        let mut inf = Vec::new();
+        for value in [150u16, 0, 33] {
+            inf.extend_from_slice(&value.to_le_bytes());
+        }
+        inf.extend_from_slice(&120u32.to_le_bytes());
+        inf.extend_from_slice(&2u16.to_le_bytes());
+        for (name, offset) in [("PCBALIAS", 33u32), ("PCBPASSWORD", 60)] {
+            let mut app = vec![0; 30];
+            app[..name.len()].copy_from_slice(name.as_bytes());
+            inf.extend_from_slice(&app);
+            inf.extend_from_slice(&offset.to_le_bytes());
+        }
+        let mut records = vec![0; 240];
+        let expires = PcbDate::new(2024, 12, 31).to_julian() as u16;
+        records[120 + 60 + 43..120 + 60 + 45].copy_from_slice(&expires.to_le_bytes());
+        inf.extend_from_slice(&records);
+        std::fs::write(dir.join("USERS.INF"), inf).unwrap();
+
+        let mut users = UserRecord::read_users(&dir.join("USERS")).unwrap();
+        UserRecord::read_users_inf(&dir.join("USERS.INF"), &mut users).unwrap();
+        assert_eq!(
+            PcbDate::new(2024, 3, 15).to_julian() as u16,
+            users[0].reg_exp_date
+        );
+        assert_eq!(expires, users[0].password_expire_date);
+    }
+
+    #[test]
+    fn test_time_functions() {
+        check_output(
+            r#"
+PRINT HOUR("13:05:09"), ",", MIN("13:05:09"), ",", SEC("13:05:09"), ","
+PRINT TIMEAP("13:05:09"), ",", TIMEAP("00:30"), ",", TIMEAP("12:00")
+"#,
+            "13,5,9,01:05:09 PM,12:30:00 AM,12:00:00 PM",
+        );
+    }
+
+    #[test]
+    fn test_date_format() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let data = IcyBoardData {
+            date_format: DateFormat::YyMmDd,
+            ..Default::default()
+        };
+        run(
+            &parse_program("PRINT MKDATE(2024, 3, 1), \",\", MONTH(\"24-03-01\")"),
+            &mut ctx,
+            &mut io,
+            &data,
+        )
+        .unwrap();
+        assert_eq!("24-03-01,3", ctx.output);
+    }
+
+    #[test]
+    fn test_inputdate_time() {
+        check_input(
+            r#"
+DATE d
+TIME t
+INPUTDATE "D", d, 7
+INPUTTIME "T", t, 7
+PRINT VALDATE("02-29-23"), VALDATE("02-29-24"), VALTIME("25:00"), VALTIME("12:30")
+"#,
+            "13-01-99\n12-31-99\n12:30\n",
+            "D13-01-99\nD12-31-99\nT12:30\n0101",
+        );
+    }
+
+    #[test]
+    fn test_cc_functions() {
+        check_output(
+            r#"
+PRINT VALCC("4111 1111 1111 1111"), VALCC("4111 1111 1111 1112"), ","
+PRINT CCTYPE("4111111111111111"), ",", CCTYPE("378282246310005"), ",", CCTYPE("5555555555554444"), ","
+PRINT FMTCC("4111111111111111"), ",", FMTCC("378282246310005")
+"#,
+            "10,VISA,AMERICAN EXPRESS,MASTERCARD,4111 1111 1111 1111,3782 822463 10005",
+        );
+    }
+
+    #[test]
+    fn test_inkey() {
+        check_input(
+            r#"
+PRINT INKEY(), ",", INKEY(), ",", INKEY(), ",", INKEY(), ",", INKEY(), ","
+PRINT INKEY(), ",", INKEY(), ",", INKEY(), ",", TINKEY(1), ",", INKEY()
+"#,
+            "a\x1B[A\x1B[1;5C\x1BOP\x1B[3~\x1B[24~\x1B[M\x1B",
+            "a,UP,RIGHT,F1,DEL,F12,F1,\x1B,,",
+        );
+    }
+
+    #[test]
+    fn test_sco_keys() {
+        check_input(
+            r#"
+INTEGER i
+FOR i = 1 TO 16
+    PRINT INKEY(), ","
+NEXT
+"#,
+            "\x1B[M\x1B[N\x1B[O\x1B[P\x1B[Q\x1B[R\x1B[S\x1B[T\x1B[U\x1B[V\x1B[W\x1B[X\x1B[I\x1B[G\x1B[H\x1B[F",
+            "F1,F2,F3,F4,F5,F6,F7,F8,F9,F10,F11,F12,PGUP,PGDN,HOME,END,",
+        );
+    }
+
+    #[test]
+    fn test_input_exhausted() {
+        let mut ctx = TestContext::new();
+        ctx.input = "ab".chars().collect();
+        let mut io = MemoryIO::new();
+        let res = run(
+            &parse_program("STRING s\nINPUT \"\", s"),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        );
+        assert!(res.unwrap_err().to_string().contains("exhausted"));
+    }
+
+    #[test]
+    fn test_kbdstuff() {
+        check_input(
+            r#"
+STRING s
+KBDSTUFF "stuffed" + CHR(13)
+KBDSTRING "typed"
+PRINT ",", KBDBUFSIZE(), ",", PPLBUFSIZE(), ","
+INPUT "", s
+PRINT ",", s, ","
+INPUT "", s
+PRINT ",", s, ","
+KBDSTUFF "x"
+KEYFLUSH
+PRINT PPLBUFSIZE(), INKEY()
+"#,
+            "user\n",
+            "typed,5,8,typedstuffed,typedstuffed,user,user,0",
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        check_output(
+            r#"
+STRING s
+TOKENIZE "J;3  R;;N "
+PRINT TOKCOUNT(), ","
+GETTOKEN s
+PRINT s, ",", GETTOKEN(), ",", TOKENSTR(), ",", TOKCOUNT(), ","
+TOKENIZE "a " + CHR(34) + "b c" + CHR(34) + ";d"
+PRINT TOKCOUNT(), ",", GETTOKEN(), ",", TOKENSTR()
+"#,
+            "4,J,3,R;N,0,3,a,\"b c\";d",
+        );
+    }
+
+    #[test]
+    fn test_input_from_token_stack() {
+        check_input(
+            r#"
+STRING s
+INTEGER i
+TOKENIZE "y;42"
+INPUTSTR "A:", s, 7, 10, "", UPCASE
+INPUTINT "B:", i, 7
+INPUTSTR "C:", s, 7, 10, "", STACKED
+PRINT s, ",", TOKENSTR()
+"#,
+            "x;z\n",
+            "A:YB:42\nC:x;zx,z",
+        );
+    }
+
+    #[test]
+    fn test_for_next() {
+        check_output(
+            r#"
+INTEGER i, j
+FOR i = 1 TO 3
+    FOR j = 10 TO 1 STEP -4
+        PRINT i, ":", j, ","
+    NEXT
+NEXT
+PRINT i
+"#,
+            "1:10,1:6,1:2,2:10,2:6,2:2,3:10,3:6,3:2,4",
+        );
+    }
+
+    #[test]
+    fn test_while_break_continue() {
+        check_output(
+            r#"
+INTEGER i, j
+WHILE (i < 10) DO
+    INC i
+    IF (i % 2 == 0) CONTINUE
+    IF (i > 7) BREAK
+    FOR j = 1 TO 10
+        IF (j > 2) BREAK
+        PRINT i, j, ","
+    NEXT
+ENDWHILE
+PRINT i
+"#,
+            "11,12,31,32,51,52,71,72,9",
+        );
+    }
+
+    #[test]
+    fn test_if_then_elseif() {
+        check_output(
+            r#"
+INTEGER i
+FOR i = 1 TO 4
+    IF (i == 1) THEN
+        PRINT "one"
+    ELSEIF (i == 2) THEN
+        PRINT "two"
+    ELSEIF (i == 3) THEN
+        PRINT "three"
+    ELSE
+        PRINT "many"
+    ENDIF
+NEXT
+"#,
+            "onetwothreemany",
+        );
+    }
+
+    #[test]
+    fn test_goto_out_of_loop() {
+        check_output(
+            r#"
+INTEGER i
+WHILE (TRUE) DO
+    INC i
+    IF (i == 3) GOTO done
+ENDWHILE
+PRINT "not reached"
+:done
+PRINT i
+"#,
+            "3",
+        );
+    }
+
+    #[test]
+    fn test_gosub_in_blocks() {
+        check_output(
+            r#"
+INTEGER i
+FOR i = 1 TO 3
+    GOSUB s
+    PRINT ","
+NEXT
+PRINT "end,"
+IF (1) THEN
+    GOSUB sub
+    PRINT "after"
+ENDIF
+WHILE (i < 6) DO
+    GOSUB s
+    IF (i == 5) GOTO done
+ENDWHILE
+:done
+END
+:s
+PRINT i
+INC i
+IF (i > 4) THEN
+    RETURN
+ENDIF
+DEC i
+RETURN
+:sub
+PRINT "sub"
+RETURN
+"#,
+            "1,2,3,end,subafter4",
+        );
+    }
+
+    #[test]
+    fn test_end_in_gosub() {
+        check_output(
+            r#"
+DECLARE PROCEDURE Early()
+INTEGER i
+Early()
+FOR i = 1 TO 3
+    GOSUB s
+    PRINT "after"
+NEXT
+PRINT "end"
+END
+:s
+PRINT i
+IF (i == 2) END
+RETURN
+
+PROCEDURE Early()
+    PRINT "a"
+    RETURN
+    PRINT "b"
+ENDPROC
+"#,
+            "a1after2",
+        );
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let res = run(
+            &parse_program(
+                r#"
+PRINT "a"
+GOTO nowhere
+PRINT "b"
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        );
+        assert!(res.unwrap_err().to_string().contains("nowhere"));
+        assert_eq!("a", ctx.output);
+
+        // errors in procedures & blocks report the statement that failed, not the call
+        let mut ctx = TestContext::new();
+        let res = run(
+            &parse_program(
+                r#"
+DECLARE PROCEDURE Fail(INTEGER i)
+Fail(1)
+PROCEDURE Fail(INTEGER i)
+    PRINT "b"
+    IF (i == 1) THEN
+        GOTO missing
+    ENDIF
+ENDPROC
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        );
+        let err = res.unwrap_err().to_string();
+        assert!(
+            err.contains("Goto") && !err.contains("ProcedureCall"),
+            "{}",
+            err
+        );
+        assert_eq!("b", ctx.output);
+    }
+
+    /// A directory for the `DiskIO` tests, it's removed when the test ends, even if it fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("pcx_board_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_disk_io_sandbox() {
+        let dir = TempDir::new("sandbox");
+        std::fs::create_dir_all(dir.join("SUB")).unwrap();
+        std::fs::write(dir.join("TEST.TXT"), "first\nsecond\n").unwrap();
+
+        let mut ctx = TestContext::new();
+        let mut io = DiskIO::new(dir.to_str().unwrap());
+        run(
+            &parse_program(
+                r#"
+STRING s
+PRINT EXIST("C:\TEST.TXT"), EXIST("TEST.TXT"), EXIST("..\TEST.TXT"), EXIST("/etc/passwd"), ","
+PRINT READLINE("SUB\..\TEST.TXT", 2), ","
+FOPEN 1, "C:\..\..\etc\passwd", O_RD, S_DN
+PRINT FERR(1)
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        )
+        .unwrap();
+        assert_eq!("1100,second,1", ctx.output);
+    }
+
+    #[test]
+    fn test_drives() {
+        let dir = TempDir::new("drives");
+        std::fs::create_dir_all(dir.join("C")).unwrap();
+        std::fs::create_dir_all(dir.join("D")).unwrap();
+        std::fs::write(dir.join("D").join("D.TXT"), "on d").unwrap();
+
+        let mut ctx = TestContext::new();
+        let mut io = DiskIO::new(dir.join("C").to_str().unwrap());
+        io.map_drive('d', dir.join("D").to_str().unwrap());
+        run(
+            &parse_program(
+                r#"
+PRINT GETDRIVE(), CWD(), ","
+MKDIR "pcb"
+MKDIR "pcb\main"
+CHDIR "pcb\main"
+PRINT CWD(), ",", EXIST("..\..\pcb"), ","
+CHDIR "\pcb"
+PRINT CWD(), ",", SETDRIVE(3), GETDRIVE(), CWD(), READLINE("D.TXT", 1), ","
+CHDIR "C:main"
+PRINT SETDRIVE(4), SETDRIVE(2), CWD(), ",", DRIVESPACE("D:") > 0, DRIVESPACE("E:")
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "2C:\\,C:\\PCB\\MAIN,1,C:\\PCB,03D:\\on d,-10C:\\PCB\\MAIN,1-1",
+            ctx.output
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_files() {
+        let dir = TempDir::new("case");
+        std::fs::create_dir_all(dir.join("pcb").join("Gen")).unwrap();
+        std::fs::write(dir.join("pcb").join("Gen").join("PcbText"), "text").unwrap();
+        std::fs::write(dir.join("a.txt"), "lower").unwrap();
+        std::fs::write(dir.join("A.TXT"), "upper").unwrap();
+        std::fs::write(dir.join("b.TXT"), "b").unwrap();
+
+        let mut ctx = TestContext::new();
+        let mut io = DiskIO::new(dir.to_str().unwrap());
+        run(
+            &parse_program(
+                r#"
+PRINT EXIST("C:\PCB\GEN\PCBTEXT"), READLINE("\Pcb\gen\pcbtext", 1), ","
+PRINT READLINE("a.txt", 1), READLINE("A.Txt", 1), READLINE("B.txt", 1), ","
+PRINT FINDFIRST("*.txt"), FINDNEXT(), FINDNEXT(), FINDNEXT(), ","
+CHDIR "PCB\GEN"
+PRINT CWD(), EXIST("PCBTEXT"), EXIST("MISSING")
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "1text,lowerupperb,A.TXTa.txtb.TXT,C:\\PCB\\GEN10",
+            ctx.output
+        );
+    }
+
+    #[test]
+    fn test_wildcards() {
+        use crate::match_wildcard;
+        assert!(match_wildcard("*.*", "README"));
+        assert!(match_wildcard("*.*", "menu.ppe"));
+        assert!(!match_wildcard("*", "menu.ppe"));
+        assert!(match_wildcard("*", "README"));
+        assert!(match_wildcard("M?NU.P*", "menu.ppe"));
+        assert!(match_wildcard("MENU?.PPE", "menu.ppe"));
+        assert!(!match_wildcard("MENU?.PPE", "menu12.ppe"));
+        assert!(match_wildcard("*.TXT", "a.b.txt"));
+        assert!(!match_wildcard("*.TX", "a.txt"));
+    }
+
+    #[test]
+    fn test_findfirst() {
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        for file in ["B.TXT", "A.TXT", "README", "C:\\DATA\\C.TXT", "MENU.PPE"] {
+            io.add_file(file, "12345");
+        }
+        run(
+            &parse_program(
+                r#"
+STRING s
+s = FINDFIRST("*.TXT")
+WHILE (s <> "") DO
+    PRINT s, " "
+    s = FINDNEXT()
+ENDWHILE
+PRINT FINDFIRST("*"), ",", FINDFIRST("C:\DATA\*.*"), FINDNEXT(), ",", FINDFIRST("*.BAK"), ","
+PRINT FILEINF("A.TXT", 1), FILEINF("X.TXT", 1), FILEINF("A.TXT", 4), ",", FILEINF("A.TXT", 5), ","
+PRINT FILEINF("C:\PCB\MENU.PPE", 6), FILEINF("C:\PCB\MENU.PPE", 7), FILEINF("C:\PCB\MENU.PPE", 8)
+PRINT FILEINF("C:\PCB\MENU.PPE", 9)
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "A.TXT B.TXT README,C.TXT,,105,32,C:\\PCB\\MENU.PPE",
+            ctx.output
+        );
+    }
+
+    #[test]
+    fn test_channel_encoding() {
+        let mut io = MemoryIO::new();
+        check_output_withio(
+            r#"
+STRING s, t
+s = CHR(200) + "x" + CHR(176)
+FCREATE 1, "C:\CP.TXT", O_WR, S_DN
+FWRITE 1, s, 3
+FPUTLN 1, s
+FCLOSE 1
+FOPEN 1, "C:\CP.TXT", O_RD, S_DN
+FGET 1, t
+PRINT t == s + s, ","
+FSEEK 1, 3, SEEK_SET
+FREAD 1, t, 3
+PRINT t == s, ",", READLINE("C:\CP.TXT", 1) == s + s
+FCLOSE 1
+"#,
+            &mut io,
+            "1,1,1",
+        );
+        let data = io.read_file(r"C:\CP.TXT").unwrap();
+        assert_eq!(b"\xC8x\xB0\xC8x\xB0\n", &data[..]);
+    }
+
+    #[test]
+    fn test_array_element_arguments() {
+        let mut io = MemoryIO::new();
+        io.add_file(r"C:\LINES.TXT", "first\nsecond\n");
+        check_output_withio(
+            r#"
+STRING a(3)
+INTEGER b(2), i
+a(1) = "one"
+a(3) = "three"
+b(0) = 7
+i = 2
+FOPEN 1, "C:\LINES.TXT", O_RD, S_DN
+FGET 1, a(i)
+FREAD 1, a(i + 1), 3
+FCLOSE 1
+TOKENIZE "x y"
+GETTOKEN a(0)
+BITSET b(1), 2
+PRINT a(0), ",", a(1), ",", a(2), ",", a(3), ",", b(0), b(1), b(2)
+"#,
+            &mut io,
+            "x,one,first,sec,740",
+        );
+    }
+
+    #[test]
+    fn test_binary_file_io() {
+        let dir = TempDir::new("binary");
+
+        let mut ctx = TestContext::new();
+        let mut io = DiskIO::new(dir.to_str().unwrap());
+        run(
+            &parse_program(
+                r#"
+INTEGER i
+WORD w
+MONEY m
+STRING s
+FCREATE 1, "DATA.BIN", O_RW, S_DN
+FPUTLN 1, "header"
+i = 258
+w = 65535
+m = 1.25
+FWRITE 1, i, 4
+FWRITE 1, w, 2
+FWRITE 1, "ab", 4
+FWRITE 1, m, 4
+PRINT FTELL(1), ","
+FREWIND 1
+FGET 1, s
+PRINT s, FTELL(1), ","
+i = 0
+FREAD 1, i, 4
+FSEEK 1, 2, SEEK_CUR
+FREAD 1, s, 4
+m = 0
+FREAD 1, m, 4
+PRINT i, s, m, FERR(1), ","
+FREAD 1, i, 4
+PRINT FERR(1), ","
+FSEEK 1, 7, SEEK_SET
+i = 1
+FWRITE 1, i, 1
+FFLUSH 1
+FSEEK 1, -14, SEEK_END
+i = -1
+FREAD 1, i, 1
+PRINT i
+FCLOSE 1
+"#,
+            ),
+            &mut ctx,
+            &mut io,
+            &IcyBoardData::default(),
+        )
+        .unwrap();
+        let data = std::fs::read(dir.join("DATA.BIN")).unwrap();
+        assert_eq!("21,header7,258ab$1.250,1,-255", ctx.output);
+        assert_eq!(b"header\n\x01\x01\0\0\xFF\xFFab\0\0\x7D\0\0\0", &data[..]);
+    }
+
+    #[test]
+    fn test_share_modes() {
+        use crate::{O_RD, O_RW, O_WR, S_DB, S_DN, S_DR, S_DW};
+        let dir = TempDir::new("share");
+        std::fs::write(dir.join("USERS.DAT"), "data").unwrap();
+
+        let mut node1 = DiskIO::new(dir.to_str().unwrap());
+        let mut node2 = DiskIO::new(dir.to_str().unwrap());
+        node1.fopen(1, "USERS.DAT", O_RW, S_DW);
+        assert!(!node1.ferr(1));
+        // reading is allowed, writing is denied
+        node2.fopen(1, "USERS.DAT", O_RD, S_DN);
+        assert!(!node2.ferr(1));
+        node2.fopen(2, "USERS.DAT", O_WR, S_DN);
+        assert!(node2.ferr(2));
+        node2.fcreate(2, "USERS.DAT", O_RW, S_DN);
+        assert!(node2.ferr(2));
+        assert_eq!(4, node2.get_file_size("USERS.DAT"));
+        // node1 reads, so denying read fails
+        node2.fopen(3, "USERS.DAT", O_RD, S_DR);
+        assert!(node2.ferr(3));
+
+        let mut ctx = TestContext::new();
+        run(
+            &parse_program(
+                r#"
+FOPEN 2, "USERS.DAT", O_RD, S_DB
+PRINT FERR(2)
+FCLOSE 1
+FOPEN 2, "USERS.DAT", O_RD, S_DW
+PRINT FERR(2)
+"#,
+            ),
+            &mut ctx,
+            &mut node1,
+            &IcyBoardData::default(),
+        )
+        .unwrap();
+        assert_eq!("10", ctx.output);
+        // node1 still denies writing on channel 2
+        node2.fclose(1);
+        node2.fappend(1, "USERS.DAT", O_WR, S_DN);
+        assert!(node2.ferr(1));
+        node1.fclose_all();
+        node2.fappend(1, "USERS.DAT", O_WR, S_DB);
+        assert!(!node2.ferr(1));
+    }
+
+    #[test]
+    fn test_memory_io_dirs() {
+        check_output(
+            r#"
+MKDIR "\DATA"
+CHDIR "data"
+PRINT CWD(), ","
+CHDIR "..\missing"
+CHDIR "..\.."
+PRINT CWD(), GETDRIVE()
+"#,
+            "C:\\DATA,C:\\DATA2",
+        );
+    }
+
+    #[test]
+    fn test_memory_io_like_disk_io() {
+        let prg = parse_program(
+            r#"
+STRING s
+INTEGER i
+MKDIR "data"
+MKDIR "DATA"
+FCREATE 1, "Data\Log.txt", O_RW, S_DN
+FPUTLN 1, "first line"
+FPUTLN 1, "second"
+FSEEK 1, 0, SEEK_SET
+FPUT 1, "FIRST"
+FSEEK 1, 2, SEEK_END
+i = 7
+FWRITE 1, i, 2
+PRINT FTELL(1), ","
+FREWIND 1
+FGET 1, s
+PRINT s, ","
+FGET 1, s
+FGET 1, s
+PRINT FERR(1), ","
+FSEEK 1, -4, SEEK_SET
+PRINT FERR(1), FTELL(1), ","
+FCLOSE 1
+FCLOSE 1
+PRINT FERR(1), ","
+FOPEN 2, "data\missing.txt", O_RD, S_DN
+PRINT FERR(2), ","
+FOPEN 2, "DATA\LOG.TXT", O_RD, S_DN
+FPUT 2, "x"
+PRINT FERR(2), ","
+FOPEN 3, "data", O_RD, S_DN
+PRINT FERR(3), ","
+FAPPEND 3, "data\log.txt", O_WR, S_DN
+FPUTLN 3, "appended"
+FGET 3, s
+PRINT FERR(3), ","
+FCLOSEALL
+COPY "data\log.txt", "copy.txt"
+RENAME "data", "moved"
+DELETE "copy.txt"
+DELETE "copy.txt"
+PRINT EXIST("data"), EXIST("moved\LOG.TXT"), EXIST("copy.txt"), FILEINF("moved\log.txt", 4), ","
+PRINT FILEINF("moved", 5), FILEINF("moved\log.txt", 5), FINDFIRST("MOVED\*.TXT"), ","
+CHDIR "moved"
+PRINT CWD(), READLINE("log.txt", 3), ","
+FOPEN 1, "log.txt", O_RD, S_DW
+FOPEN 2, "log.txt", O_RW, S_DN
+PRINT FERR(1), FERR(2)
+"#,
+        );
+        let expected =
+            "22,FIRST line,0,122,1,1,1,1,1,01031,1632Log.txt,C:\\MOVED\0\0\u{7}\0appended,01";
+
+        let dir = TempDir::new("parity");
+        let mut ctx = TestContext::new();
+        let mut disk = DiskIO::new(dir.to_str().unwrap());
+        run(&prg, &mut ctx, &mut disk, &IcyBoardData::default()).unwrap();
+        let on_disk = std::fs::read(dir.join("moved").join("Log.txt")).unwrap();
+        assert_eq!(expected, ctx.output);
+
+        let mut ctx = TestContext::new();
+        let mut memory = MemoryIO::new();
+        run(&prg, &mut ctx, &mut memory, &IcyBoardData::default()).unwrap();
+        assert_eq!(expected, ctx.output);
+        assert_eq!(on_disk, memory.read_file("C:\\MOVED\\LOG.TXT").unwrap());
+    }
+
+    #[test]
+    fn test_memory_io_clock() {
+        use std::time::SystemTime;
+        let now = Rc::new(RefCell::new(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000),
+        ));
+        let clock = now.clone();
+        let mut io = MemoryIO::with_clock(Box::new(move || *clock.borrow()));
+        io.add_file("A.TXT", "old");
+        *now.borrow_mut() += Duration::from_secs(60);
+        io.fopen(1, "a.txt", crate::O_RW, crate::S_DN);
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000),
+            io.get_file_date("A.TXT").unwrap()
+        );
+        io.fseek(1, 1, crate::SEEK_SET);
+        io.fwrite(1, b"\xFFx");
+        assert_eq!(*now.borrow(), io.get_file_date("A.TXT").unwrap());
+        assert_eq!(b"o\xFFx", &io.read_file("A.TXT").unwrap()[..]);
+        assert_eq!(3, io.get_file_size("A.TXT"));
+    }
+
+    #[test]
+    fn test_dbase() {
+        let mut io = MemoryIO::new();
+        check_output_withio(
+            r#"
+STRING f(3), s
+INTEGER i
+DATE d
+f(0) = "NAME,C,20,0"
+f(1) = "AGE,N,3,0"
+f(2) = "BORN,D"
+f(3) = "active,L"
+DCREATE 1, "C:\USERS", FALSE, f
+PRINT DCHKSTAT(1), DRECCOUNT(1), DEOF(1), DBOF(1), DFIELDS(1), ","
+PRINT DNAME(1, 2), DTYPE(1, "born"), DLENGTH(1, "NAME"), DGETALIAS(1), ","
+DNEW 1
+DPUT 1, "NAME", "Alice"
+DPUT 1, "AGE", 42
+DPUT 1, "BORN", "19800115"
+DPUT 1, "ACTIVE", TRUE
+DADD 1
+DNEW 1
+DPUT 1, "NAME", "Bob"
+DPUT 1, "AGE", 1234
+DADD 1
+DAPPEND 1
+DPUT 1, "NAME", "Carol"
+DTOP 1
+DGET 1, "NAME", s
+DGET 1, "AGE", i
+DGET 1, "BORN", d
+PRINT s, i, DGET(1, "ACTIVE"), DRECCOUNT(1), YEAR(d), MONTH(d), DAY(d), ","
+DSKIP 1, 1
+PRINT DGET(1, "NAME"), DRECNO(1), DSKIP(1, 1), DGET(1, "NAME"), DCHANGED(1), ","
+PRINT DSKIP(1, 1), DEOF(1), DRECNO(1), DSKIP(1, -5), DBOF(1), DRECNO(1), ","
+DDELETE 1
+PRINT DDELETED(1), DCHANGED(1), ","
+DPACK 1
+PRINT DERR(1), DCHKSTAT(1), DERRMSG(DCHKSTAT(1)), ","
+PRINT DLOCKF(1)
+DPACK 1
+PRINT DERR(1), DRECCOUNT(1), DGET(1, "NAME"), ","
+DUNLOCK 1
+DSETALIAS 1, "members"
+PRINT DSELECT("Members"), DSELECT("USERS"), DNEXT(), ","
+DCLOSE 1
+PRINT DGETALIAS(1), DOPEN(2, "USERS", TRUE), DRECCOUNT(2), DGET(2, "NAME"), ","
+DGET 2, "NOPE", s
+PRINT DERR(2), DCHKSTAT(2)
+DCLOSEALL
+"#,
+            &mut io,
+            "00114,AGED20USERS,Alice42131980115,Bob20Carol0,314411,11,1-50Locking record or file,002Bob,1-10,02Bob,1-210",
+        );
+        let data = io.read_file(r"C:\USERS.DBF").unwrap();
+        // header: version, record count, header & record length
+        assert_eq!(3, data[0]);
+        assert_eq!([2, 0, 0, 0], data[4..8]);
+        assert_eq!([161, 0, 33, 0], data[8..12]);
+        assert_eq!(b"ACTIVE\0\0\0\0\0L", &data[128..140]);
+        assert_eq!(0x0D, data[160]);
+        let mut bob = format!(" {:20}***{:9}", "Bob", "");
+        bob.push_str(&format!(" {:32}", "Carol"));
+        assert_eq!(bob.as_bytes(), &data[161..227]);
+        assert_eq!([0x1A], data[227..]);
+    }
+
+    fn check_record_locks(node1: &mut crate::dbase::Dbase, node2: &mut crate::dbase::Dbase) {
+        use crate::dbase::{E4_LOCK, R4_SUCCESS};
+        use ppl_engine::ast::VariableValue;
+        let format = DateFormat::default();
+        assert_eq!(R4_SUCCESS, node1.append_blank(0));
+        assert_eq!(R4_SUCCESS, node1.append_blank(0));
+        assert_eq!(R4_SUCCESS, node2.top(0));
+        // node1 holds record 1, node2 can't lock or write it
+        assert_eq!(R4_SUCCESS, node1.go(0, 1));
+        assert_eq!(R4_SUCCESS, node1.lock(0));
+        assert_eq!(E4_LOCK, node2.lock(0));
+        let name = VariableValue::String("node2".to_string());
+        assert_eq!(R4_SUCCESS, node2.put(0, "NAME", &name, format));
+        assert_eq!(E4_LOCK, node2.skip(0, 1));
+        assert_eq!(E4_LOCK, node2.lock_file(0));
+        // other records & appends are fine
+        assert_eq!(R4_SUCCESS, node2.lock_records(0, &[2]));
+        assert_eq!(E4_LOCK, node1.lock_records(0, &[1, 2]));
+        // DUNLOCK writes the pending change, that needs record 1
+        assert_eq!(E4_LOCK, node2.unlock(0));
+        assert_eq!(R4_SUCCESS, node1.unlock(0));
+        assert_eq!(R4_SUCCESS, node2.unlock(0));
+        assert_eq!(R4_SUCCESS, node1.lock_file(0));
+        assert_eq!(E4_LOCK, node2.add(0));
+        assert_eq!(R4_SUCCESS, node1.top(0));
+        assert_eq!(Some(name), node1.get(0, "NAME"));
+        assert_eq!(R4_SUCCESS, node1.unlock(0));
+        assert_eq!(R4_SUCCESS, node2.add(0));
+        assert_eq!(3, node1.file_mut(0).unwrap().record_count().unwrap());
+    }
+
+    #[test]
+    fn test_dbase_record_locks() {
+        use crate::dbase::{Dbase, E4_OPEN, R4_SUCCESS};
+        let fields = ["NAME,C,10".to_string()];
+        let dir = TempDir::new("dbase");
+        let mut io1 = DiskIO::new(dir.to_str().unwrap());
+        let mut io2 = DiskIO::new(dir.to_str().unwrap());
+        let (mut node1, mut node2) = (Dbase::default(), Dbase::default());
+        assert_eq!(
+            R4_SUCCESS,
+            node1.create(&mut io1, 0, "NODES", false, &fields)
+        );
+        assert_eq!(R4_SUCCESS, node2.open(&mut io2, 0, "NODES", false));
+        check_record_locks(&mut node1, &mut node2);
+        // an exclusive open fails while another node has the file open
+        assert_eq!(E4_OPEN, node2.open(&mut io2, 1, "NODES", true));
+        node1.close_all();
+        node2.close_all();
+
+        let mut io = MemoryIO::new();
+        let (mut node1, mut node2) = (Dbase::default(), Dbase::default());
+        assert_eq!(
+            R4_SUCCESS,
+            node1.create(&mut io, 0, "NODES", false, &fields)
+        );
+        assert_eq!(R4_SUCCESS, node2.open(&mut io, 0, "NODES", false));
+        check_record_locks(&mut node1, &mut node2);
+        assert_eq!(E4_OPEN, node2.open(&mut io, 1, "NODES", true));
+    }
+
+    #[test]
+    fn test_dbase_corrupt_header() {
+        let mut io = MemoryIO::new();
+        let mut header = vec![0; 32];
+        header[0] = 3;
+        // no room for the descriptors
+        header[8] = 16;
+        io.add_file(r"C:\SHORT.DBF", header.clone());
+        // longer than the file
+        header[8..10].copy_from_slice(&1000u16.to_le_bytes());
+        io.add_file(r"C:\LONG.DBF", header);
+        check_output_withio(
+            r#"
+PRINT DOPEN(1, "SHORT", FALSE), DOPEN(2, "LONG", FALSE), DERRMSG(DCHKSTAT(2))
+"#,
+            &mut io,
+            "-200-200File is not a data file",
+        );
+    }
+
+    #[test]
+    fn test_dbase_index() {
+        let mut io = MemoryIO::new();
+        check_output_withio(
+            r#"
+STRING f(1)
+f(0) = "NAME,C,10,0"
+f(1) = "AGE,N,3,0"
+DCREATE 1, "PEOPLE", FALSE, f
+DNEW 1
+DPUT 1, "NAME", "Dave"
+DPUT 1, "AGE", 40
+DADD 1
+DNEW 1
+DPUT 1, "NAME", "Carol"
+DPUT 1, "AGE", 25
+DADD 1
+DNEW 1
+DPUT 1, "NAME", "alice"
+DPUT 1, "AGE", 31
+DADD 1
+DNEW 1
+DPUT 1, "NAME", "Bob"
+DPUT 1, "AGE", 52
+DADD 1
+DNCREATE 1, "NAMES", "UPPER(NAME)"
+DNCREATE 1, "AGES", "AGE"
+DTAG 1, "NAMES"
+PRINT DTAG(1), ","
+DTOP 1
+WHILE (!DEOF(1)) DO
+  PRINT DGET(1, "NAME")
+  DSKIP 1, 1
+ENDWHILE
+PRINT ","
+DTAG 1, "AGES"
+DBOTTOM 1
+WHILE (!DBOF(1)) DO
+  PRINT DGET(1, "NAME")
+  DSKIP 1, -1
+ENDWHILE
+PRINT DRECNO(1), ","
+PRINT DSEEK(1, 31), DGET(1, "NAME"), DSEEK(1, 45), DGET(1, "NAME"), DSEEK(1, 60), DEOF(1), ","
+DTAG 1, "NAMES"
+PRINT DSEEK(1, "CA"), DGET(1, "NAME"), DSEEK(1, "CX"), DGET(1, "NAME"), ","
+DSEEK 1, "DAVE"
+DPUT 1, "NAME", "Aaron"
+DTOP 1
+PRINT DGET(1, "NAME")
+DNEW 1
+DPUT 1, "NAME", "Zed"
+DPUT 1, "AGE", 1
+DADD 1
+DBOTTOM 1
+PRINT DGET(1, "NAME")
+DTAG 1, "AGES"
+DTOP 1
+PRINT DGET(1, "NAME")
+DDELETE 1
+DTOP 1
+PRINT DGET(1, "NAME"), ","
+IF (DLOCKF(1) = 0) DPACK 1
+DUNLOCK 1
+PRINT DRECCOUNT(1), DRECNO(1), DGET(1, "NAME"), ","
+DNCLOSEALL 1
+DTAG 1, "AGES"
+PRINT DTAG(1), DERR(1), DNOPEN(1, "NAMES"), ","
+DTAG 1, "names"
+DBOTTOM 1
+PRINT DTAG(1), DGET(1, "NAME"), DRECNO(1), ","
+PRINT DNCLOSE(1, "NAMES"), DTAG(1), DSEEK(1, "BOB")
+DCLOSEALL
+"#,
+            &mut io,
+            "NAMES,aliceBobCarolDave,BobDavealiceCarol2,1alice2Bob31,1Carol2Dave,AaronZedZedCarol,42Carol,10,NAMESCarol2,00",
+        );
+        let data = io.read_file(r"C:\NAMES.NDX").unwrap();
+        // header: key length & the key expression
+        assert_eq!([10, 0], data[12..14]);
+        assert_eq!(b"UPPER(NAME)\0", &data[24..36]);
+    }
+
+    #[test]
+    fn test_dbase_index_pages() {
+        use crate::dbase::{Dbase, R4_AFTER, R4_EOF, R4_SUCCESS};
+        use ppl_engine::ast::VariableValue;
+        let format = DateFormat::default();
+        let mut io = MemoryIO::new();
+        let (mut node1, mut node2) = (Dbase::default(), Dbase::default());
+        let fields = ["NAME,C,10".to_string()];
+        assert_eq!(R4_SUCCESS, node1.create(&mut io, 0, "KEYS", false, &fields));
+        assert_eq!(R4_SUCCESS, node1.create_index(&mut io, 0, "KEYS", "NAME"));
+        // enough keys for a few levels of pages, inserted out of order
+        for i in 0..300 {
+            let name = VariableValue::String(format!("K{:03}", i * 7 % 300));
+            assert_eq!(R4_SUCCESS, node1.new_record(0));
+            assert_eq!(R4_SUCCESS, node1.put(0, "NAME", &name, format));
+            assert_eq!(R4_SUCCESS, node1.add(0));
+        }
+        for i in (0..300).step_by(3) {
+            assert_eq!(R4_SUCCESS, node1.go(0, i + 1));
+            assert_eq!(R4_SUCCESS, node1.set_deleted(0, true));
+        }
+        assert_eq!(R4_SUCCESS, node1.top(0));
+
+        // another node sees the changes
+        assert_eq!(R4_SUCCESS, node2.open(&mut io, 0, "KEYS", false));
+        assert_eq!(R4_SUCCESS, node2.open_index(&mut io, 0, "KEYS"));
+        assert_eq!(R4_SUCCESS, node2.select_tag(0, "KEYS"));
+        assert_eq!(R4_SUCCESS, node2.top(0));
+        let mut names = Vec::new();
+        while !node2.file(0).unwrap().eof {
+            names.push(node2.get(0, "NAME").unwrap());
+            assert_eq!(names.len() == 200, node2.skip(0, 1) == R4_EOF);
+        }
+        let expected: Vec<VariableValue> = (0..300)
+            .filter(|i| i % 3 != 0)
+            .map(|i| VariableValue::String(format!("K{:03}", i)))
+            .collect();
+        assert_eq!(expected, names);
+        let value = VariableValue::String("K2".to_string());
+        assert_eq!(R4_SUCCESS, node2.seek(0, &value, format));
+        let name = VariableValue::String("K200".to_string());
+        assert_eq!(Some(name), node2.get(0, "NAME"));
+        let value = VariableValue::String("K003".to_string());
+        assert_eq!(R4_AFTER, node2.seek(0, &value, format));
+        let name = VariableValue::String("K004".to_string());
+        assert_eq!(Some(name), node2.get(0, "NAME"));
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark() {
+        let prg = parse_program(include_str!("../../../manual_tests/benchmark.pps"));
+        let mut ctx = TestContext::new();
+        let mut io = MemoryIO::new();
+        let start = Instant::now();
+        run(&prg, &mut ctx, &mut io, &IcyBoardData::default()).unwrap();
+        println!("benchmark.pps: {:?}", start.elapsed());
+        assert_eq!("25000,100000,2584,10", ctx.output);
+    }
 }
@@ -11,10 +11,6 @@ pub struct RawCom {
 }
 
 impl RawCom {
-    pub fn push_str(&mut self, data: &str) {
-        self.buf.extend(data.as_bytes().iter());
-    }
-
     pub fn connect(addr: &SocketAddr, timeout: Duration) -> io::Result<Self> {
         let tcp_stream = std::net::TcpStream::connect_timeout(addr, timeout)?;
         tcp_stream.set_nonblocking(true)?;